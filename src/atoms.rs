@@ -4,22 +4,94 @@ use std::collections::HashMap;
 
 use x11rb::{
     connection::Connection,
-    errors::ReplyOrIdError,
     protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, PropMode, Screen, Window},
     wrapper::ConnectionExt as _,
 };
 
-use crate::connection::Res;
+use crate::{connection::Res, error::HematiteError};
+
+/// The names of every atom used by the manager.
+///
+/// These are published as supported on the root window.
+pub const ATOM_NAMES: &[&str] = &[
+    "_NET_SUPPORTED",
+    "_NET_CLIENT_LIST",
+    "_NET_CLIENT_LIST_STACKING",
+    "_NET_NUMBER_OF_DESKTOPS",
+    "_NET_DESKTOP_GEOMETRY",
+    "_NET_DESKTOP_VIEWPORT",
+    "_NET_CURRENT_DESKTOP",
+    "_NET_DESKTOP_NAMES",
+    "_NET_DESKTOP_LAYOUT",
+    "_NET_ACTIVE_WINDOW",
+    "_NET_WORKAREA",
+    "_NET_SHOWING_DESKTOP",
+    "_NET_SUPPORTING_WM_CHECK",
+    "_NET_CLOSE_WINDOW",
+    "_NET_MOVERESIZE_WINDOW",
+    "_NET_WM_MOVERESIZE",
+    "_NET_RESTACK_WINDOW",
+    "_NET_FRAME_EXTENTS",
+    "_NET_WM_NAME",
+    "_NET_WM_DESKTOP",
+    "_NET_WM_STATE",
+    "_NET_WM_STATE_FULLSCREEN",
+    "_NET_WM_STATE_MAXIMIZED_VERT",
+    "_NET_WM_STATE_MAXIMIZED_HORZ",
+    "_NET_WM_STATE_ABOVE",
+    "_NET_WM_STATE_BELOW",
+    "_NET_WM_STATE_STICKY",
+    "_NET_WM_STATE_DEMANDS_ATTENTION",
+    "_NET_WM_WINDOW_OPACITY",
+    "_NET_WM_BYPASS_COMPOSITOR",
+    "_NET_WM_ALLOWED_ACTIONS",
+    "_NET_WM_ACTION_FULLSCREEN",
+    "_NET_WM_ACTION_MAXIMIZE_VERT",
+    "_NET_WM_ACTION_MAXIMIZE_HORZ",
+    "_NET_WM_ACTION_ABOVE",
+    "_NET_WM_ACTION_BELOW",
+    "_NET_WM_USER_TIME",
+    "_NET_WM_PING",
+    "_NET_WM_PID",
+    "_NET_WM_STRUT",
+    "_NET_WM_STRUT_PARTIAL",
+    "_NET_WM_ICON",
+    "_NET_WM_WINDOW_TYPE",
+    "_NET_WM_WINDOW_TYPE_DESKTOP",
+    "_NET_WM_WINDOW_TYPE_DOCK",
+    "_NET_WM_WINDOW_TYPE_TOOLBAR",
+    "_NET_WM_WINDOW_TYPE_MENU",
+    "_NET_WM_WINDOW_TYPE_UTILITY",
+    "_NET_WM_WINDOW_TYPE_SPLASH",
+    "_NET_WM_WINDOW_TYPE_DIALOG",
+    "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
+    "_NET_WM_WINDOW_TYPE_POPUP_MENU",
+    "_NET_WM_WINDOW_TYPE_TOOLTIP",
+    "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+    "_NET_WM_WINDOW_TYPE_COMBO",
+    "_NET_WM_WINDOW_TYPE_DND",
+    "_NET_WM_WINDOW_TYPE_NORMAL",
+    "UTF8_STRING",
+    "WM_NAME",
+    "WM_PROTOCOLS",
+    "WM_STATE",
+    "WM_DELETE_WINDOW",
+    "WM_TRANSIENT_FOR",
+    "WM_CLASS",
+    "WM_WINDOW_ROLE",
+    "_MOTIF_WM_HINTS",
+];
+
 /// A helper for managing atoms.
 ///
 /// Atoms are integers defined by the X11 server or by the window manager, and act as shared names and types for clients to communicate between each other and the server.
-pub struct Atoms<'a, C> {
-    /// A connection to the X11 server.
-    conn: &'a C,
+pub struct Atoms {
     /// This atom lists the other supported atoms.
     pub net_supported: Atom,
     /// This atom lists the managed clients.
     pub net_client_list: Atom,
+    /// This atom lists the managed clients from bottom to top.
+    pub net_client_list_stacking: Atom,
     /// This atom lists the number of desktops.
     pub net_number_of_desktops: Atom,
     /// This is set to the screen's geometry.
@@ -28,26 +100,60 @@ pub struct Atoms<'a, C> {
     pub net_desktop_viewport: Atom,
     /// The current active tag.
     pub net_current_desktop: Atom,
+    /// The names of the tags.
+    pub net_desktop_names: Atom,
+    /// How pagers should lay out the tags.
+    pub net_desktop_layout: Atom,
     /// The window that has the input focus.
     pub net_active_window: Atom,
     /// The workarea a window can be in.
     pub net_workarea: Atom,
+    /// Whether the windows are hidden to show the desktop.
+    pub net_showing_desktop: Atom,
     /// The id of the heartbeat window.
     pub net_supporting_wm_check: Atom,
+    /// A request to close a window.
+    pub net_close_window: Atom,
+    /// A request to start moving or resizing a window with the pointer.
+    pub net_wm_moveresize: Atom,
     /// The border width of the window.
     pub net_frame_extents: Atom,
     /// The name of the window.
     pub net_wm_name: Atom,
     /// The current tag of the window.
     pub net_wm_desktop: Atom,
-    /// The state (fullscreen) of the window.
+    /// The state (fullscreen, maximized, above, below, sticky, demanding attention) of the window.
     pub net_wm_state: Atom,
     /// The fullscreen state of the window.
     pub net_wm_state_fullscreen: Atom,
+    /// The vertically maximized state of the window.
+    pub net_wm_state_maximized_vert: Atom,
+    /// The horizontally maximized state of the window.
+    pub net_wm_state_maximized_horz: Atom,
+    /// The state of a window kept above other windows.
+    pub net_wm_state_above: Atom,
+    /// The state of a window kept below other windows.
+    pub net_wm_state_below: Atom,
+    /// The state of a window shown on every tag.
+    pub net_wm_state_sticky: Atom,
+    /// The state of a window demanding attention.
+    pub net_wm_state_demands_attention: Atom,
+    /// The opacity a compositor should draw the window with.
+    pub net_wm_window_opacity: Atom,
+    /// Whether a compositor should stop compositing the window, drawing it directly to the screen.
+    pub net_wm_bypass_compositor: Atom,
     /// A list of atoms representing the allowed actions of a window.
     pub net_wm_allowed_actions: Atom,
     /// The fullscreen action.
     pub net_wm_action_fullscreen: Atom,
+    /// The vertical maximize action.
+    pub net_wm_action_maximize_vert: Atom,
+    /// The horizontal maximize action.
+    pub net_wm_action_maximize_horz: Atom,
+    /// The action of keeping a window above other windows.
+    pub net_wm_action_above: Atom,
+    /// The action of keeping a window below other windows.
+    pub net_wm_action_below: Atom,
     /// An icon representing the window.
     pub net_wm_icon: Atom,
     /// Represents the utf8 type.
@@ -58,6 +164,16 @@ pub struct Atoms<'a, C> {
     pub wm_state: Atom,
     /// The window deletion protocol.
     pub wm_delete_window: Atom,
+    /// The protocol used to check whether a window is still responding.
+    pub net_wm_ping: Atom,
+    /// The process id of the client owning the window.
+    pub net_wm_pid: Atom,
+    /// The time of the last user interaction with the window.
+    pub net_wm_user_time: Atom,
+    /// The space a dock reserves at each edge of the screen.
+    pub net_wm_strut: Atom,
+    /// The space a dock reserves at each edge of the screen, along with the part of the edge it covers.
+    pub net_wm_strut_partial: Atom,
     pub net_wm_window_type: Atom,
     pub net_wm_window_type_desktop: Atom,
     pub net_wm_window_type_dock: Atom,
@@ -75,82 +191,67 @@ pub struct Atoms<'a, C> {
     pub net_wm_window_type_normal: Atom,
     pub wm_transient_for: Atom,
     pub wm_class: Atom,
+    /// The role of the window, set by some applications to tell their windows apart.
+    pub wm_window_role: Atom,
+    /// The Motif hints of the window, which applications drawing their own title bars use to ask for no decorations.
+    pub motif_wm_hints: Atom,
 }
 
-impl<'a, C: Connection> Atoms<'a, C> {
+impl Atoms {
     /// Creates a new atom helper.
     /// # Errors
     /// May return an error if the atoms are incorrect.
-    pub fn new(conn: &'a C, screen: &Screen) -> Result<Self, ReplyOrIdError> {
-        let atom_strings = vec![
-            "_NET_SUPPORTED",
-            "_NET_CLIENT_LIST",
-            "_NET_NUMBER_OF_DESKTOPS",
-            "_NET_DESKTOP_GEOMETRY",
-            "_NET_DESKTOP_VIEWPORT",
-            "_NET_CURRENT_DESKTOP",
-            "_NET_DESKTOP_NAMES",
-            "_NET_ACTIVE_WINDOW",
-            "_NET_WORKAREA",
-            "_NET_SUPPORTING_WM_CHECK",
-            "_NET_CLOSE_WINDOW",
-            "_NET_MOVERESIZE_WINDOW",
-            "_NET_WM_MOVERESIZE",
-            "_NET_RESTACK_WINDOW",
-            "_NET_FRAME_EXTENTS",
-            "_NET_WM_NAME",
-            "_NET_WM_DESKTOP",
-            "_NET_WM_STATE",
-            "_NET_WM_STATE_FULLSCREEN",
-            "_NET_WM_ALLOWED_ACTIONS",
-            "_NET_WM_ACTION_FULLSCREEN",
-            "_NET_WM_USER_TIME",
-            "_NET_WM_ICON",
-            "_NET_WM_WINDOW_TYPE",
-            "_NET_WM_WINDOW_TYPE_DESKTOP",
-            "_NET_WM_WINDOW_TYPE_DOCK",
-            "_NET_WM_WINDOW_TYPE_TOOLBAR",
-            "_NET_WM_WINDOW_TYPE_MENU",
-            "_NET_WM_WINDOW_TYPE_UTILITY",
-            "_NET_WM_WINDOW_TYPE_SPLASH",
-            "_NET_WM_WINDOW_TYPE_DIALOG",
-            "_NET_WM_WINDOW_TYPE_DROPDOWN_MENU",
-            "_NET_WM_WINDOW_TYPE_POPUP_MENU",
-            "_NET_WM_WINDOW_TYPE_TOOLTIP",
-            "_NET_WM_WINDOW_TYPE_NOTIFICATION",
-            "_NET_WM_WINDOW_TYPE_COMBO",
-            "_NET_WM_WINDOW_TYPE_DND",
-            "_NET_WM_WINDOW_TYPE_NORMAL",
-            "UTF8_STRING",
-            "WM_NAME",
-            "WM_PROTOCOLS",
-            "WM_STATE",
-            "WM_DELETE_WINDOW",
-            "WM_TRANSIENT_FOR",
-            "WM_CLASS",
-        ];
-
-        let atom_nums = get_atom_nums(conn, &atom_strings);
-        let atoms = get_atom_mapping(&atom_strings, &atom_nums);
+    pub fn new(conn: &impl Connection, screen: &Screen) -> Result<Self, HematiteError> {
+        let atom_nums = get_atom_nums(conn, ATOM_NAMES);
+        let new_self = Self::from_atom_nums(&atom_nums);
+        new_self.setup_atoms(conn, screen, &atom_nums)?;
+        Ok(new_self)
+    }
 
-        let new_self = Self {
-            conn,
+    /// Creates a new atom helper from the ids of the atoms in `ATOM_NAMES`, in the same order, without setting up the root window.
+    ///
+    /// This is useful when the atoms don't come from an X11 server, e.g. in a mock connection.
+    ///
+    /// # Panics
+    /// Panics if an atom is missing.
+    #[must_use]
+    pub fn from_atom_nums(atom_nums: &[Atom]) -> Self {
+        let atoms = get_atom_mapping(ATOM_NAMES, atom_nums);
+        Self {
             net_supported: atoms["_NET_SUPPORTED"],
             net_client_list: atoms["_NET_CLIENT_LIST"],
+            net_client_list_stacking: atoms["_NET_CLIENT_LIST_STACKING"],
             net_number_of_desktops: atoms["_NET_NUMBER_OF_DESKTOPS"],
             net_desktop_geometry: atoms["_NET_DESKTOP_GEOMETRY"],
             net_desktop_viewport: atoms["_NET_DESKTOP_VIEWPORT"],
             net_current_desktop: atoms["_NET_CURRENT_DESKTOP"],
+            net_desktop_names: atoms["_NET_DESKTOP_NAMES"],
+            net_desktop_layout: atoms["_NET_DESKTOP_LAYOUT"],
             net_active_window: atoms["_NET_ACTIVE_WINDOW"],
             net_workarea: atoms["_NET_WORKAREA"],
+            net_showing_desktop: atoms["_NET_SHOWING_DESKTOP"],
             net_supporting_wm_check: atoms["_NET_SUPPORTING_WM_CHECK"],
+            net_close_window: atoms["_NET_CLOSE_WINDOW"],
+            net_wm_moveresize: atoms["_NET_WM_MOVERESIZE"],
             net_frame_extents: atoms["_NET_FRAME_EXTENTS"],
             net_wm_name: atoms["_NET_WM_NAME"],
             net_wm_desktop: atoms["_NET_WM_DESKTOP"],
             net_wm_state: atoms["_NET_WM_STATE"],
             net_wm_state_fullscreen: atoms["_NET_WM_STATE_FULLSCREEN"],
+            net_wm_state_maximized_vert: atoms["_NET_WM_STATE_MAXIMIZED_VERT"],
+            net_wm_state_maximized_horz: atoms["_NET_WM_STATE_MAXIMIZED_HORZ"],
+            net_wm_state_above: atoms["_NET_WM_STATE_ABOVE"],
+            net_wm_state_below: atoms["_NET_WM_STATE_BELOW"],
+            net_wm_state_sticky: atoms["_NET_WM_STATE_STICKY"],
+            net_wm_state_demands_attention: atoms["_NET_WM_STATE_DEMANDS_ATTENTION"],
+            net_wm_window_opacity: atoms["_NET_WM_WINDOW_OPACITY"],
+            net_wm_bypass_compositor: atoms["_NET_WM_BYPASS_COMPOSITOR"],
             net_wm_allowed_actions: atoms["_NET_WM_ALLOWED_ACTIONS"],
             net_wm_action_fullscreen: atoms["_NET_WM_ACTION_FULLSCREEN"],
+            net_wm_action_maximize_vert: atoms["_NET_WM_ACTION_MAXIMIZE_VERT"],
+            net_wm_action_maximize_horz: atoms["_NET_WM_ACTION_MAXIMIZE_HORZ"],
+            net_wm_action_above: atoms["_NET_WM_ACTION_ABOVE"],
+            net_wm_action_below: atoms["_NET_WM_ACTION_BELOW"],
             net_wm_icon: atoms["_NET_WM_ICON"],
             net_wm_window_type: atoms["_NET_WM_WINDOW_TYPE"],
             net_wm_window_type_desktop: atoms["_NET_WM_WINDOW_TYPE_DESKTOP"],
@@ -171,11 +272,16 @@ impl<'a, C: Connection> Atoms<'a, C> {
             wm_protocols: atoms["WM_PROTOCOLS"],
             wm_state: atoms["WM_STATE"],
             wm_delete_window: atoms["WM_DELETE_WINDOW"],
+            net_wm_ping: atoms["_NET_WM_PING"],
+            net_wm_pid: atoms["_NET_WM_PID"],
+            net_wm_user_time: atoms["_NET_WM_USER_TIME"],
+            net_wm_strut: atoms["_NET_WM_STRUT"],
+            net_wm_strut_partial: atoms["_NET_WM_STRUT_PARTIAL"],
             wm_transient_for: atoms["WM_TRANSIENT_FOR"],
             wm_class: atoms["WM_CLASS"],
-        };
-        new_self.setup_atoms(screen, &atom_nums)?;
-        Ok(new_self)
+            wm_window_role: atoms["WM_WINDOW_ROLE"],
+            motif_wm_hints: atoms["_MOTIF_WM_HINTS"],
+        }
     }
 
     /// Gets the name of an atom if it's defined.
@@ -183,19 +289,26 @@ impl<'a, C: Connection> Atoms<'a, C> {
     /// # Errors
     /// If the string is partly invalid, the default character is used.
     ///
-    /// If there is no atom by that name then a `ReplyOrIdError` is thrown.
-    pub fn get_atom_name(&self, atom: Atom) -> Result<String, ReplyOrIdError> {
-        Ok(String::from_utf8_lossy(&self.conn.get_atom_name(atom)?.reply()?.name).to_string())
+    /// If there is no atom by that name then a `HematiteError` is returned.
+    pub fn get_atom_name(
+        &self,
+        conn: &impl Connection,
+        atom: Atom,
+    ) -> Result<String, HematiteError> {
+        Ok(String::from_utf8_lossy(&conn.get_atom_name(atom)?.reply()?.name).to_string())
     }
 
     /// Sets up the root window's properties.
     ///
+    /// Tags are published as desktops named 1-9, laid out in a single row for pagers, each with its viewport at the origin.
+    ///
     /// # Errors
     /// May return an error if the data is malformed.
-    pub fn setup_atoms(&self, screen: &Screen, atom_nums: &[Atom]) -> Res {
-        self.change_atom_prop(screen.root, self.net_supported, atom_nums)?;
-        self.change_cardinal_prop(screen.root, self.net_number_of_desktops, &[9])?;
+    pub fn setup_atoms(&self, conn: &impl Connection, screen: &Screen, atom_nums: &[Atom]) -> Res {
+        self.change_atom_prop(conn, screen.root, self.net_supported, atom_nums)?;
+        self.change_cardinal_prop(conn, screen.root, self.net_number_of_desktops, &[9])?;
         self.change_cardinal_prop(
+            conn,
             screen.root,
             self.net_desktop_geometry,
             &[
@@ -203,17 +316,18 @@ impl<'a, C: Connection> Atoms<'a, C> {
                 u32::from(screen.height_in_pixels),
             ],
         )?;
-        self.change_cardinal_prop(screen.root, self.net_desktop_viewport, &[0, 0])?;
-        self.change_cardinal_prop(
+        self.change_cardinal_prop(conn, screen.root, self.net_desktop_viewport, &[0; 18])?;
+        self.change_cardinal_prop(conn, screen.root, self.net_current_desktop, &[0])?;
+        // horizontal orientation, 9 columns, 1 row, starting from the top left corner
+        self.change_cardinal_prop(conn, screen.root, self.net_desktop_layout, &[0, 9, 1, 0])?;
+        conn.change_property8(
+            PropMode::REPLACE,
             screen.root,
-            self.net_workarea,
-            &[
-                0,
-                0,
-                u32::from(screen.width_in_pixels),
-                u32::from(screen.height_in_pixels),
-            ],
+            self.net_desktop_names,
+            self.utf8_string,
+            b"1\x002\x003\x004\x005\x006\x007\x008\x009\x00",
         )?;
+        self.change_cardinal_prop(conn, screen.root, self.net_showing_desktop, &[0])?;
         Ok(())
     }
 
@@ -221,9 +335,14 @@ impl<'a, C: Connection> Atoms<'a, C> {
     ///
     /// # Errors
     /// May return an error if the data is malformed or has an inappropriate size, or if the atom or window is missing.
-    pub fn change_atom_prop(&self, window: Window, property: Atom, data: &[u32]) -> Res {
-        self.conn
-            .change_property32(PropMode::REPLACE, window, property, AtomEnum::ATOM, data)?
+    pub fn change_atom_prop(
+        &self,
+        conn: &impl Connection,
+        window: Window,
+        property: Atom,
+        data: &[u32],
+    ) -> Res {
+        conn.change_property32(PropMode::REPLACE, window, property, AtomEnum::ATOM, data)?
             .check()?;
         Ok(())
     }
@@ -232,17 +351,28 @@ impl<'a, C: Connection> Atoms<'a, C> {
     ///
     /// # Errors
     /// May return an error if the data is malformed or has an inappropriate size, or if the atom or window is missing.
-    pub fn change_window_prop(&self, window: Window, property: Atom, data: &[u32]) -> Res {
-        self.conn
-            .change_property32(PropMode::REPLACE, window, property, AtomEnum::WINDOW, data)?;
+    pub fn change_window_prop(
+        &self,
+        conn: &impl Connection,
+        window: Window,
+        property: Atom,
+        data: &[u32],
+    ) -> Res {
+        conn.change_property32(PropMode::REPLACE, window, property, AtomEnum::WINDOW, data)?;
         Ok(())
     }
 
     /// Changes a window's integer property to the specified data.
     /// # Errors
     /// May return an error if the data is malformed or has an inappropriate size, or if the atom or window is missing.
-    pub fn change_cardinal_prop(&self, window: Window, property: Atom, data: &[u32]) -> Res {
-        self.conn.change_property32(
+    pub fn change_cardinal_prop(
+        &self,
+        conn: &impl Connection,
+        window: Window,
+        property: Atom,
+        data: &[u32],
+    ) -> Res {
+        conn.change_property32(
             PropMode::REPLACE,
             window,
             property,
@@ -255,8 +385,14 @@ impl<'a, C: Connection> Atoms<'a, C> {
     /// Changes a window's string property to the specified data.
     /// # Errors
     /// May return an error if the data is malformed or has an inappropriate size, or if the atom or window is missing.
-    pub fn change_string_prop(&self, window: Window, property: Atom, data: &str) -> Res {
-        self.conn.change_property8(
+    pub fn change_string_prop(
+        &self,
+        conn: &impl Connection,
+        window: Window,
+        property: Atom,
+        data: &str,
+    ) -> Res {
+        conn.change_property8(
             PropMode::REPLACE,
             window,
             property,
@@ -269,8 +405,8 @@ impl<'a, C: Connection> Atoms<'a, C> {
     /// Removes the data from a window's property.
     /// # Errors
     /// May return an error if the atom or window is missing.
-    pub fn remove_atom_prop(&self, window: Window, property: Atom) -> Res {
-        self.change_atom_prop(window, property, &[0])?;
+    pub fn remove_atom_prop(&self, conn: &impl Connection, window: Window, property: Atom) -> Res {
+        self.change_atom_prop(conn, window, property, &[0])?;
         Ok(())
     }
 
@@ -279,12 +415,12 @@ impl<'a, C: Connection> Atoms<'a, C> {
     /// Returns an error if the property or window is missing.
     pub fn get_property(
         &self,
+        conn: &impl Connection,
         window: Window,
         property: Atom,
         prop_type: AtomEnum,
-    ) -> Result<Vec<u8>, ReplyOrIdError> {
-        Ok(self
-            .conn
+    ) -> Result<Vec<u8>, HematiteError> {
+        Ok(conn
             .get_property(false, window, property, prop_type, 0, u32::MAX)?
             .reply()?
             .value)
@@ -308,7 +444,7 @@ fn get_atom_mapping(atom_strings: &[&str], atom_nums: &[u32]) -> HashMap<String,
 fn get_atom_nums<C: Connection>(conn: &C, atom_strings: &[&str]) -> std::vec::Vec<u32> {
     atom_strings
         .iter()
-        .flat_map(|s| -> Result<u32, ReplyOrIdError> {
+        .flat_map(|s| -> Result<u32, HematiteError> {
             Ok(conn.intern_atom(false, s.as_bytes())?.reply()?.atom)
         })
         .collect()
@@ -1,27 +1,70 @@
 //!
 //! This module provides a status bar that displays tag and window information as well as status text provided by the user.
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-use fontdue::Metrics;
-use x11rb::{
-    errors::ReplyOrIdError,
-    protocol::xproto::{Gcontext, Pixmap, Rectangle, Window},
-};
+use serde::{Deserialize, Serialize};
+use x11rb::protocol::xproto::{Gcontext, Pixmap, Rectangle, Window};
 
 use crate::{
     config::Config,
     connection::{Colors, ConnectionActionExt, ConnectionAtomExt, ConnectionStateExt, Res},
-    render::{Image, ImageHandler},
-    state::{WindowGroup, WindowState},
+    error::HematiteError,
+    icons, markup,
+    render::{Image, ImageHandler, color_to_rgba, solid_image},
+    state::{BarPosition, LAYOUT_SYMBOLS, Layer, SizeHints, WindowGroup, WindowState},
 };
 
 /// The number of available tags.
 const TAG_COUNT: usize = 9;
+/// The text placed between pieces of text sharing a segment.
+pub const SEPARATOR: &str = " | ";
+/// The maximum amount of window icons kept in the cache.
+const MAX_CACHED_ICONS: usize = 64;
+/// How long the icon of a window that isn't drawn is kept in the cache.
+const ICON_LIFETIME: Duration = Duration::from_mins(10);
+/// The symbol of the close button on title bars.
+const CLOSE_SYMBOL: &str = "×";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// What the bar shows between the tags and the status text.
+pub enum BarMode {
+    /// The focused window's icon and title.
+    #[default]
+    Title,
+    /// Every window of the active tag, with the focused one highlighted. Clicking a window's entry focuses it.
+    Taskbar,
+    /// The focused window's icon, class and title, such as `firefox | Mozilla Firefox`.
+    Class,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Whether the bar is shown, and whether windows are tiled around it.
+pub enum BarVisibility {
+    /// The bar is always shown, and windows are tiled around it.
+    #[default]
+    Shown,
+    /// The bar is hidden, and windows take up its area.
+    Hidden,
+    /// The bar is hidden until the pointer touches its edge of the screen, and is then shown over the windows until the pointer leaves it.
+    Autohide,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+/// What a bar shows.
+pub enum BarRole {
+    /// The tags, the layout indicator, the windows and the status text.
+    Main,
+    /// Only the blocks drawn on the secondary bar, at the opposite edge of the screen from the main bar.
+    Secondary,
+}
 
 /// A cache for the left side of the bar to minimize redraws.
 pub struct Cache {
-    /// Icons pertaining to specific windows.
-    pub icons: HashMap<Window, Image>,
+    /// Icons pertaining to specific windows, along with when they were last drawn.
+    pub icons: HashMap<Window, (Image, Instant)>,
     /// Window names pertaining to specific windows.
     ///
     /// Names still have to be asked to see if they are updated, but the draw call can be avoided.
@@ -30,6 +73,12 @@ pub struct Cache {
     active_tag: usize,
     /// The tags which have a window in them, represented as a bitmask.
     used_tags: u16,
+    /// The layout indicator currently drawn.
+    layout: String,
+    /// The pieces of the status segments currently drawn, with their x coordinates.
+    pieces: Vec<(String, i16)>,
+    /// The windows listed in taskbar mode, with the x coordinate and width of their entries.
+    entries: Vec<(Window, i16, i16)>,
 }
 
 impl Default for Cache {
@@ -39,16 +88,90 @@ impl Default for Cache {
             names: HashMap::default(),
             active_tag: usize::MAX,
             used_tags: Default::default(),
+            layout: String::new(),
+            pieces: Vec::new(),
+            entries: Vec::new(),
         }
     }
 }
 
+impl Cache {
+    /// Drops everything cached for a window, such as when it's destroyed.
+    pub fn forget(&mut self, window: Window) {
+        self.icons.remove(&window);
+        self.names.remove(&window);
+    }
+
+    /// Drops the icons of windows that haven't been drawn recently, and the least recently drawn icons if there are too many.
+    pub fn evict_icons(&mut self) {
+        self.icons
+            .retain(|_, (_, used)| used.elapsed() < ICON_LIFETIME);
+        while self.icons.len() > MAX_CACHED_ICONS {
+            let Some(oldest) = self
+                .icons
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(w, _)| *w)
+            else {
+                break;
+            };
+            self.icons.remove(&oldest);
+        }
+    }
+}
+
+/// The status sections of the bar, each measured and placed on its own.
+///
+/// A segment is made of pieces (e.g. the status text and each block), drawn one after another with a separator between them. Only pieces that changed are redrawn.
+///
+/// The left side of the bar always holds the tags and the focused window's title, which takes up the space left over by the other segments.
+#[derive(Default)]
+pub struct Segments {
+    /// The pieces of text drawn in the middle of the bar.
+    pub center: Vec<String>,
+    /// The pieces of text drawn on the right side of the bar.
+    pub right: Vec<String>,
+}
+
+impl Segments {
+    /// Splits the status text into segments.
+    ///
+    /// Text before the first tab is drawn in the center, and the rest on the right. Text without a tab is drawn on the right.
+    #[must_use]
+    pub fn from_status(status_text: &str) -> Self {
+        let (center, right) = status_text.split_once('\t').unwrap_or(("", status_text));
+        let piece = |text: &str| {
+            if text.is_empty() {
+                Vec::new()
+            } else {
+                vec![text.to_owned()]
+            }
+        };
+        Self {
+            center: piece(center),
+            right: piece(right),
+        }
+    }
+
+    /// Appends the pieces of other segments to these.
+    pub fn append(&mut self, other: Self) {
+        self.center.extend(other.center);
+        self.right.extend(other.right);
+    }
+}
+
 /// A helper for drawing the bar.
 pub struct BarPainter {
     /// The bar as a window with state.
     pub bar: WindowState,
+    /// An invisible window along the bar's edge of the screen, revealing the bar in autohide mode.
+    pub edge: WindowState,
+    /// Whether the bar is shown, and whether windows are tiled around it.
+    pub visibility: BarVisibility,
     /// The base x coordinate to draw letters from.
     base_x: i16,
+    /// The width of the area after the tags holding the layout indicator.
+    layout_width: i16,
     /// The base y coordinate to draw letters from.
     base_y: i16,
     /// The pixmap associated with the bar.
@@ -57,66 +180,206 @@ pub struct BarPainter {
     gc: Gcontext,
     /// A graphics context with inverted colors to draw highlighted elements.
     inverted_gc: Gcontext,
+    /// Graphics contexts drawing with each tag's accent color.
+    tag_gcs: Vec<Gcontext>,
+    /// The accent colors of each tag as RGBA, used to draw the active tag's number.
+    tag_colors: Vec<(u8, u8, u8, u8)>,
     /// A helper for drawing text.
     image: ImageHandler,
+    /// Whether colors are drawn without their alpha, as the bar has no ARGB visual.
+    opaque: bool,
+    /// What the bar shows between the tags and the status text.
+    mode: BarMode,
+    /// What the bar shows.
+    role: BarRole,
     /// A cache for reducing draw calls.
     pub cache: Cache,
+    /// Whether the bar needs to be redrawn once the pending events are handled.
+    pub dirty: bool,
 }
 
 impl BarPainter {
-    /// Creates a new helper.
+    /// Creates a new helper for the main bar.
     /// # Errors
     /// Returns an error if the config or colors are incorrect.
     pub fn new(
         conn: &(impl ConnectionActionExt + ConnectionStateExt + ConnectionAtomExt),
         colors: &Colors,
         config: &Config,
-    ) -> Result<Self, ReplyOrIdError> {
-        let gc = conn.generate_id()?;
-        let inverted_gc = conn.generate_id()?;
+    ) -> Result<Self, HematiteError> {
+        Self::create(conn, colors, config, config.bar_position, BarRole::Main)
+    }
 
-        conn.create_gc(gc, colors.main, colors.secondary)?;
-        conn.create_gc(inverted_gc, colors.secondary, colors.main)?;
-        let text = ImageHandler::new(config);
+    /// Creates a new helper for the secondary bar, placed at the opposite edge of the screen from the main bar.
+    /// # Errors
+    /// Returns an error if the config or colors are incorrect.
+    pub fn new_secondary(
+        conn: &(impl ConnectionActionExt + ConnectionStateExt + ConnectionAtomExt),
+        colors: &Colors,
+        config: &Config,
+    ) -> Result<Self, HematiteError> {
+        let position = match config.bar_position {
+            BarPosition::Top => BarPosition::Bottom,
+            BarPosition::Bottom => BarPosition::Top,
+        };
+        Self::create(conn, colors, config, position, BarRole::Secondary)
+    }
+
+    /// Creates a bar with the specified role at an edge of the screen.
+    /// # Errors
+    /// Returns an error if the config or colors are incorrect.
+    fn create(
+        conn: &(impl ConnectionActionExt + ConnectionStateExt + ConnectionAtomExt),
+        colors: &Colors,
+        config: &Config,
+        position: BarPosition,
+        role: BarRole,
+    ) -> Result<Self, HematiteError> {
+        let opaque = !conn.has_argb_visual();
+        let tag_colors = config
+            .tag_colors
+            .iter()
+            .map(|c| color_to_rgba(c.unwrap_or(config.tag_highlight_color), opaque))
+            .collect();
+        let text = ImageHandler::new(config, opaque)?;
 
         let pixmap = conn.generate_id()?;
 
+        let height = text.metrics.height as u16 * 3 / 2;
         let bar = WindowState {
             window: conn.generate_id()?,
             frame_window: conn.generate_id()?,
             x: 0,
-            y: 0,
+            y: match position {
+                BarPosition::Top => 0,
+                BarPosition::Bottom => conn.get_screen_geometry().1.saturating_sub(height) as i16,
+            },
             width: conn.get_screen_geometry().0,
-            height: text.metrics.height as u16 * 3 / 2,
+            height,
             group: WindowGroup::Floating,
+            layer: Layer::Normal,
+            sticky: false,
+            urgent: false,
+            decorated: false,
+            last_focus: Instant::now(),
+            hints: SizeHints::default(),
+            restore: None,
+            leader: None,
         };
 
-        let base_x = bar.height as i16 * TAG_COUNT as i16 + bar.height as i16 / 2;
-        let base_y = (bar.height as i16 / 2) + text.metrics.height as i16 / 5 * 2;
+        let (base_x, layout_width, base_y) = text_offsets(&text, bar.height);
 
         conn.create_window(&bar)?;
         conn.add_window(&bar)?;
         conn.create_pixmap_from_win(pixmap, &bar)?;
         conn.set_class("bar", bar.window)?;  
+        conn.select_bar_events(bar.window)?;
+
+        let edge_window = conn.generate_id()?;
+        let edge = WindowState {
+            window: edge_window,
+            frame_window: edge_window,
+            y: match position {
+                BarPosition::Top => 0,
+                BarPosition::Bottom => bar.y + bar.height as i16 - 1,
+            },
+            height: 1,
+            ..bar
+        };
+        conn.create_edge_window(&edge)?;
+
+        // graphics contexts must match the depth of the pixmap they draw to
+        let gc = conn.generate_id()?;
+        let inverted_gc = conn.generate_id()?;
+
+        conn.create_gc(gc, pixmap, colors.bar_background, colors.bar_foreground)?;
+        conn.create_gc(
+            inverted_gc,
+            pixmap,
+            colors.bar_foreground,
+            colors.bar_background,
+        )?;
+        let tag_gcs = colors
+            .tags
+            .iter()
+            .map(|c| {
+                let tag_gc = conn.generate_id()?;
+                conn.create_gc(tag_gc, pixmap, colors.bar_background, *c)?;
+                Ok(tag_gc)
+            })
+            .collect::<Result<Vec<Gcontext>, HematiteError>>()?;
+        conn.fill_rectangle(
+            pixmap,
+            inverted_gc,
+            Rectangle {
+                x: 0,
+                y: 0,
+                width: bar.width,
+                height: bar.height,
+            },
+        )?;
         Ok(Self {
             bar,
+            edge,
+            visibility: BarVisibility::Shown,
             base_x,
+            layout_width,
             base_y,
             pixmap,
             gc,
             inverted_gc,
+            tag_gcs,
+            tag_colors,
             image: text,
+            opaque,
+            mode: config.bar_mode,
+            role,
             cache: Cache::default(),
+            dirty: false,
         })
     }
 
+    /// Switches to the colors and font of the config, such as after switching themes.
+    ///
+    /// The bar keeps its height, and is redrawn entirely on the next draw.
+    /// # Errors
+    /// Returns an error if the font couldn't be loaded or the graphics contexts don't exist.
+    pub fn set_theme(
+        &mut self,
+        conn: &impl ConnectionActionExt,
+        colors: &Colors,
+        config: &Config,
+    ) -> Res {
+        self.tag_colors = config
+            .tag_colors
+            .iter()
+            .map(|c| color_to_rgba(c.unwrap_or(config.tag_highlight_color), self.opaque))
+            .collect();
+        self.image = ImageHandler::new(config, self.opaque)?;
+        (self.base_x, self.layout_width, self.base_y) = text_offsets(&self.image, self.bar.height);
+
+        conn.change_gc(self.gc, colors.bar_background, colors.bar_foreground)?;
+        conn.change_gc(
+            self.inverted_gc,
+            colors.bar_foreground,
+            colors.bar_background,
+        )?;
+        for (tag_gc, c) in self.tag_gcs.iter().zip(&colors.tags) {
+            conn.change_gc(*tag_gc, colors.bar_background, *c)?;
+        }
+        self.cache = Cache::default();
+        self.dirty = true;
+        Ok(())
+    }
+
     /// Draws the entire bar in this order:
     /// - Clears the pixmap
     /// - Draws tag rectangles
     /// - Draws the tag numbers
+    /// - Draws the layout indicator
+    /// - Draws the center and right status segments that changed, followed by the blocks and prefixed with the session indicator (if it exists)
     /// - Draws the window icon (if it exists)
-    /// - Draws the window text
-    /// - Draws the status text
+    /// - Draws the window text, or every window of the tag in taskbar mode or as tabs
     /// - Copies the pixmap to the bar
     /// # Errors
     /// Returns an error if the connection is faulty or the specified active window does not exist.
@@ -126,70 +389,197 @@ impl BarPainter {
         tag_bitmask: u16,
         conn: &(impl ConnectionActionExt + ConnectionStateExt + ConnectionAtomExt),
         active_window: Option<Window>,
+        windows: &[Window],
+        tabs: bool,
+        layout: &str,
+        indicator: Option<char>,
+        blocks: Segments,
     ) -> Res {
-        if self.cache.active_tag != active_tag || self.cache.used_tags != tag_bitmask {
+        if self.cache.active_tag != active_tag
+            || self.cache.used_tags != tag_bitmask
+            || self.cache.layout != layout
+        {
             conn.fill_rectangle(
                 self.pixmap,
                 self.inverted_gc,
                 Rectangle {
                     x: 0,
                     y: 0,
-                    width: self.bar.height * TAG_COUNT as u16,
+                    width: self.get_window_area_x() as u16,
                     height: self.bar.height,
                 },
             )?;
 
             self.draw_rectangles(active_tag, tag_bitmask, conn)?;
             self.draw_tag_letters(conn, active_tag, self.base_y)?;
+            self.draw_text(
+                conn,
+                layout,
+                self.bar.height as i16 * TAG_COUNT as i16 + self.bar.height as i16 / 4,
+                self.base_y,
+            )?;
             self.cache.active_tag = active_tag;
             self.cache.used_tags = tag_bitmask;
+            layout.clone_into(&mut self.cache.layout);
         }
 
-        if let Some(window) = active_window {
-            let text = conn.get_window_name(window)?;
+        let mut segments = Segments::from_status(&conn.get_window_name(conn.get_root())?);
+        segments.append(blocks);
+        if let Some(c) = indicator {
+            match segments.right.first_mut() {
+                Some(text) => *text = format!("{c} {text}"),
+                None => segments.right.push(c.to_string()),
+            }
+        }
+        let pieces = self.layout_segments(&segments);
+        let title_end = pieces.first().map_or(self.bar.width as i16, |p| p.1);
+        self.draw_segments(conn, pieces)?;
+
+        self.cache.entries.clear();
+        if self.mode == BarMode::Taskbar || tabs {
+            self.draw_taskbar(conn, windows, active_window, title_end)?;
+            // the title is drawn over the tabs once they're gone
+            self.cache.names.clear();
+        } else if let Some(window) = active_window {
+            let mut text = conn.get_window_name(window)?;
+            if self.mode == BarMode::Class {
+                let class = conn.get_window_class(window)?;
+                if !class.is_empty() {
+                    text = format!("{class}{SEPARATOR}{text}");
+                }
+            }
             if let Some(cached_text) = self.cache.names.get(&window) {
                 if *cached_text != text {
-                    self.draw_window_properties(conn, &text)?;
+                    self.draw_window_properties(conn, &text, title_end)?;
                     self.cache.names.entry(window).and_modify(|s| *s = text);
                 }
             } else {
-                self.draw_window_properties(conn, &text)?;
+                self.draw_window_properties(conn, &text, title_end)?;
                 self.cache.names.entry(window).and_modify(|s| *s = text);
             }
             self.draw_icon(conn, window)?;
         } else {
-            self.draw_window_properties(conn, "")?;
+            self.draw_window_properties(conn, "", title_end)?;
         }
 
-        self.draw_status_bar(conn)?;
         self.clear_and_copy_bar(conn)?;
         Ok(())
     }
 
-    /// aaa
-    fn draw_window_properties(&mut self, conn: &impl ConnectionActionExt, text: &str) -> Res {
-        // let length = self.text.get_text_length(text);
+    /// Draws the blocks of the secondary bar, then copies the pixmap to the bar.
+    /// # Errors
+    /// Returns an error if the connection is faulty.
+    pub fn draw_status(
+        &mut self,
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        segments: &Segments,
+    ) -> Res {
+        let pieces = self.layout_segments(segments);
+        self.draw_segments(conn, pieces)?;
+        self.clear_and_copy_bar(conn)
+    }
+
+    /// Clears the window area of the bar up to the specified x coordinate, and draws the window's name truncated to fit within it.
+    fn draw_window_properties(
+        &mut self,
+        conn: &impl ConnectionActionExt,
+        text: &str,
+        end_x: i16,
+    ) -> Res {
+        let start_x = self.get_window_area_x();
+        conn.fill_rectangle(
+            self.pixmap,
+            self.inverted_gc,
+            Rectangle {
+                x: start_x,
+                y: 0,
+                width: (end_x - start_x).max(0) as u16,
+                height: self.bar.height,
+            },
+        )?;
+        let max_width = end_x - self.bar.height as i16 / 2 - (self.base_x + 16);
+        let text = self.image.truncate_text(text, max_width);
+        self.draw_text(conn, &text, self.base_x + 16, self.base_y)?;
+        Ok(())
+    }
+
+    /// Clears the window area of the bar up to the specified x coordinate, and lists the names of the windows in equally wide entries.
+    ///
+    /// The focused window's entry is drawn with inverted colors.
+    fn draw_taskbar(
+        &mut self,
+        conn: &impl ConnectionActionExt,
+        windows: &[Window],
+        focus: Option<Window>,
+        end_x: i16,
+    ) -> Res {
+        let start_x = self.get_window_area_x();
         conn.fill_rectangle(
             self.pixmap,
             self.inverted_gc,
             Rectangle {
-                x: self.bar.height as i16 * TAG_COUNT as i16,
+                x: start_x,
                 y: 0,
-                width: self.bar.width - self.bar.height * TAG_COUNT as u16,
+                width: (end_x - start_x).max(0) as u16,
                 height: self.bar.height,
             },
         )?;
-        self.draw_text(conn, text, self.base_x + 16, self.base_y)?;
+        self.cache.entries.clear();
+        if windows.is_empty() {
+            return Ok(());
+        }
+
+        let padding = self.bar.height as i16 / 4;
+        let width = (end_x - start_x - padding) / windows.len() as i16;
+        for (i, &window) in windows.iter().enumerate() {
+            let x = start_x + width * i as i16;
+            let (mut foreground, mut background) =
+                (self.image.colors.foreground, self.image.colors.background);
+            if focus == Some(window) {
+                (foreground, background) = (background, foreground);
+                let image =
+                    solid_image(width.max(0) as u32, u32::from(self.bar.height), background);
+                self.put_image(conn, &image, x, 0)?;
+            }
+            let text = self
+                .image
+                .truncate_text(&conn.get_window_name(window)?, width - 2 * padding);
+            let (image, offset_x, offset_y) =
+                self.image.rasterize_text(&text, foreground, background);
+            self.put_image(conn, &image, x + padding + offset_x, self.base_y + offset_y)?;
+            self.cache.entries.push((window, x, width));
+        }
         Ok(())
     }
 
+    /// Gets the tag whose square is at the specified x coordinate, if any.
+    #[must_use]
+    pub const fn get_tag_at(&self, x: i16) -> Option<usize> {
+        let tag = x / self.bar.height as i16;
+        if x >= 0 && (tag as usize) < TAG_COUNT {
+            Some(tag as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the window whose taskbar entry is at the specified x coordinate, if any.
+    #[must_use]
+    pub fn get_entry_window(&self, x: i16) -> Option<Window> {
+        self.cache
+            .entries
+            .iter()
+            .find(|(_, start, width)| (*start..start + width).contains(&x))
+            .map(|e| e.0)
+    }
+
     /// Draws the window icon to the bar.
     ///
     /// An icon is an ARGB byte sequence with the first eight bytes being the width and height of the icon.
     ///
     /// An icon can be of any size and usually we need to scale it up or down to match the font size.
     ///
-    /// We also cache icons pertaining to a window to not have to calculate and draw the icon every refresh, and drop them when the window is dropped.
+    /// We also cache icons pertaining to a window to not have to calculate and draw the icon every refresh, and drop them when the window is dropped or hasn't been drawn in a while. Windows without an icon are cached as well, so that fallback icons aren't searched for on every refresh.
     /// # Errors
     /// Returns an error if the window is invalid.
     fn draw_icon(
@@ -197,14 +587,52 @@ impl BarPainter {
         conn: &(impl ConnectionActionExt + ConnectionAtomExt),
         window: Window,
     ) -> Res {
-        let icon = if let Some(icon) = self.cache.icons.get(&window) {
+        if !self.cache.icons.contains_key(&window) {
+            let icon = self.load_icon(conn, window)?.unwrap_or(Image {
+                width: 0,
+                height: 0,
+                data: Vec::new(),
+            });
+            self.cache.icons.insert(window, (icon, Instant::now()));
+            self.cache.evict_icons();
+        }
+        let Some((icon, used)) = self.cache.icons.get_mut(&window) else {
+            return Ok(());
+        };
+        *used = Instant::now();
+        if icon.data.is_empty() {
+            return Ok(());
+        }
+
+        conn.draw_to_pixmap(
+            self.pixmap,
+            self.gc,
+            self.base_x - icon.width as i16 / 2,
+            self.bar.height as i16 / 2 - icon.height as i16 / 2,
+            icon.width as u16,
+            icon.height as u16,
+            &icon.data,
+        )?;
+        Ok(())
+    }
+
+    /// Loads the icon of a window, scaled to the height of the text.
+    ///
+    /// Windows without an icon of their own get the icon of their application from the icon theme, if one is found.
+    /// # Errors
+    /// Returns an error if the window is invalid.
+    fn load_icon(
+        &self,
+        conn: &(impl ConnectionActionExt + ConnectionAtomExt),
+        window: Window,
+    ) -> Result<Option<Image>, HematiteError> {
+        let icon_with_dimensions = conn.get_icon(window)?;
+        let icon = if icon_with_dimensions.len() < 8 {
+            let Some(icon) = icons::find_icon(&conn.get_window_class(window)?) else {
+                return Ok(None);
+            };
             icon
         } else {
-            let icon_with_dimensions = conn.get_icon(window)?;
-            if icon_with_dimensions.is_empty() {
-                return Ok(());
-            }
-
             let width = u32::from_ne_bytes(
                 icon_with_dimensions[0..4]
                     .try_into()
@@ -215,63 +643,120 @@ impl BarPainter {
                     .try_into()
                     .unwrap_or([0, 0, 0, 0]),
             );
-
-            let Some(icon) = self.image.resize_image_to_text_height(Image {
+            Image {
                 width,
                 height,
                 data: icon_with_dimensions,
-            }) else {
-                return Ok(());
-            };
+            }
+        };
+        Ok(self.image.resize_image_to_text_height(icon))
+    }
 
-            self.cache.icons.insert(window, icon);
-            let Some(icon) = self.cache.icons.get(&window) else {
-                return Ok(());
-            };
-            icon
+    /// Places the pieces of the center and right segments, getting the text and x coordinate of each piece in order.
+    ///
+    /// Pieces after the first of their segment are prefixed with a separator, and are measured without their markup. The right segment ends at the right edge of the bar. The center segment is centered on the bar, but is moved left to avoid the right segment and, on the main bar, right to avoid the tags.
+    fn layout_segments(&self, segments: &Segments) -> Vec<(String, i16)> {
+        let separate = |pieces: &[String]| {
+            pieces
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    if i == 0 {
+                        p.clone()
+                    } else {
+                        format!("{SEPARATOR}{p}")
+                    }
+                })
+                .collect::<Vec<String>>()
+        };
+        let length = |pieces: &[String]| pieces.iter().map(|p| self.piece_length(p)).sum::<i16>();
+        let place = |pieces: Vec<String>, mut x: i16| {
+            pieces.into_iter().map(move |p| {
+                let start = x;
+                x += self.piece_length(&p);
+                (p, start)
+            })
         };
 
-        conn.draw_to_pixmap(
-            self.pixmap,
-            self.gc,
-            self.base_x - icon.width as i16 / 2,
-            self.bar.height as i16 / 2 - icon.height as i16 / 2,
-            icon.width as u16,
-            icon.height as u16,
-            &icon.data,
-        )?;
-        Ok(())
+        let (center, right) = (separate(&segments.center), separate(&segments.right));
+        let right_x = self.bar.width as i16 - length(&right);
+        let center_length = length(&center);
+        let center_x = ((self.bar.width as i16 - center_length) / 2)
+            .min(right_x - self.bar.height as i16 / 2 - center_length)
+            .max(match self.role {
+                BarRole::Main => self.base_x + 16,
+                BarRole::Secondary => 0,
+            });
+        place(center, center_x)
+            .chain(place(right, right_x))
+            .collect()
     }
 
-    /// Draws the status text to the bar.
+    /// Draws the pieces of the status segments that changed since the last draw.
     ///
-    /// The text is drawn on the right side of the bar.
+    /// Pieces that changed, moved or disappeared are cleared first. Pieces drawn at the same place with the same text are skipped.
     /// # Errors
-    /// Returns an error if the status text overflows.
-    fn draw_status_bar(&self, conn: &impl ConnectionActionExt) -> Res {
-        let status_text = conn.get_window_name(conn.get_root())?;
-
-        log::trace!("drawing root windows name on bar with text: {status_text}");
+    /// Returns an error if a piece overflows.
+    fn draw_segments(
+        &mut self,
+        conn: &impl ConnectionActionExt,
+        pieces: Vec<(String, i16)>,
+    ) -> Res {
+        let piece_rectangle = |(text, x): &(String, i16)| Rectangle {
+            x: *x,
+            y: 0,
+            width: self.piece_length(text) as u16,
+            height: self.bar.height,
+        };
 
-        let length = self.image.get_text_length(&status_text);
+        self.cache
+            .pieces
+            .iter()
+            .filter(|p| !pieces.contains(p))
+            .try_for_each(|p| {
+                conn.fill_rectangle(self.pixmap, self.inverted_gc, piece_rectangle(p))
+            })?;
+
+        pieces
+            .iter()
+            .filter(|p| !self.cache.pieces.contains(p))
+            .try_for_each(|p| {
+                log::trace!("drawing status piece on bar with text: {}", p.0);
+                conn.fill_rectangle(self.pixmap, self.inverted_gc, piece_rectangle(p))?;
+                self.draw_markup(conn, &p.0, p.1)
+            })?;
+
+        self.cache.pieces = pieces;
+        Ok(())
+    }
 
-        conn.fill_rectangle(
-            self.pixmap,
-            self.inverted_gc,
-            Rectangle {
-                x: self.bar.width as i16 - length,
-                y: 0,
-                width: length as u16,
-                height: self.bar.height,
-            },
-        )?;
+    /// Gets the width of a status piece without its markup.
+    fn piece_length(&self, text: &str) -> i16 {
+        self.image.get_text_length(&markup::strip(text))
+    }
 
-        self.draw_text(
-            conn,
-            &status_text,
-            self.bar.width as i16 - length,
-            self.base_y,
-        )?;
+    /// Draws a status piece with markup, starting at the specified x coordinate.
+    ///
+    /// Runs with a background color have it drawn over the whole height of the bar.
+    fn draw_markup(&self, conn: &impl ConnectionActionExt, text: &str, mut x: i16) -> Res {
+        for span in markup::parse(text) {
+            let width = self.image.get_text_length(&span.text);
+            let foreground = span.foreground.map_or(self.image.colors.foreground, |c| {
+                color_to_rgba(c, self.opaque)
+            });
+            let background = span.background.map_or(self.image.colors.background, |c| {
+                color_to_rgba(c, self.opaque)
+            });
+            if span.background.is_some() {
+                let image = solid_image(width as u32, u32::from(self.bar.height), background);
+                self.put_image(conn, &image, x, 0)?;
+            }
+            let (image, offset_x, offset_y) = self
+                .image
+                .rasterize_text(&span.text, foreground, background);
+            self.put_image(conn, &image, x + offset_x, self.base_y + offset_y)?;
+            x += width;
+        }
         Ok(())
     }
 
@@ -284,6 +769,8 @@ impl BarPainter {
 
     /// Draws the rectangles indicating whether a tag has windows in it or not, and the active tag's rectangle
     ///
+    /// The active tag's rectangle is drawn with the tag's accent color.
+    ///
     /// Indicator rectangles are smaller and occupy the top left side of the outer rectangle.
     ///
     /// These rectangles are drawn on the left side of the bar.
@@ -295,7 +782,7 @@ impl BarPainter {
     ) -> Res {
         conn.fill_rectangle(
             self.pixmap,
-            self.gc,
+            self.tag_gcs[active_tag],
             self.create_tag_rectangle(active_tag + 1),
         )?;
 
@@ -327,7 +814,7 @@ impl BarPainter {
 
     /// Draws the numbers of the tags onto the bar.
     ///
-    /// The active tag's number has inverted colors.
+    /// The active tag's number has inverted colors, using the tag's accent color as the background.
     fn draw_tag_letters(
         &mut self,
         conn: &impl ConnectionActionExt,
@@ -335,26 +822,17 @@ impl BarPainter {
         base_y: i16,
     ) -> Res {
         (1..=TAG_COUNT).try_for_each(|x| {
-            if x == active_tag + 1 {
-                let (metrics, data) = self.image.rasterize_letter(
-                    char::from_digit(x as u32, 10).unwrap_or_default(),
-                    self.image.colors.foreground,
-                    self.image.colors.background,
-                );
-                let base_x = self.bar.height * (x as u16 - 1)
-                    + (self.bar.height / 2 - (metrics.advance_width as u16 / 2));
-                self.put_text_data(conn, metrics, data.as_slice(), base_x as i16, base_y)?;
+            let letter = x.to_string();
+            let (foreground, background) = if x == active_tag + 1 {
+                (self.image.colors.background, self.tag_colors[active_tag])
             } else {
-                let (metrics, data) = self.image.rasterize_letter(
-                    char::from_digit(x as u32, 10).unwrap_or_default(),
-                    self.image.colors.background,
-                    self.image.colors.foreground,
-                );
-                let base_x = self.bar.height * (x as u16 - 1)
-                    + (self.bar.height / 2 - (metrics.advance_width as u16 / 2));
-                self.put_text_data(conn, metrics, data.as_slice(), base_x as i16, base_y)?;
-            }
-            Ok::<(), ReplyOrIdError>(())
+                (self.image.colors.foreground, self.image.colors.background)
+            };
+            let (image, offset_x, offset_y) =
+                self.image.rasterize_text(&letter, foreground, background);
+            let base_x = self.bar.height * (x as u16 - 1)
+                + (self.bar.height / 2 - (self.image.get_text_length(&letter) as u16 / 2));
+            self.put_image(conn, &image, base_x as i16 + offset_x, base_y + offset_y)
         })?;
         Ok(())
     }
@@ -369,18 +847,17 @@ impl BarPainter {
         base_x: i16,
         base_y: i16,
     ) -> Res {
-        let mut total_width = 0;
-        text.chars().try_for_each(|c| {
-            let (metrics, data) = self.image.rasterize_letter(
-                c,
-                self.image.colors.background,
-                self.image.colors.foreground,
-            );
-            self.put_text_data(conn, metrics, data.as_slice(), base_x + total_width, base_y)?;
-            total_width += metrics.advance_width as i16;
-            Ok::<(), ReplyOrIdError>(())
-        })?;
-        Ok(())
+        let (image, offset_x, offset_y) = self.image.rasterize_text(
+            text,
+            self.image.colors.foreground,
+            self.image.colors.background,
+        );
+        self.put_image(conn, &image, base_x + offset_x, base_y + offset_y)
+    }
+
+    /// Gets the x coordinate where the window area starts, after the tags and the layout indicator.
+    const fn get_window_area_x(&self) -> i16 {
+        self.bar.height as i16 * TAG_COUNT as i16 + self.layout_width
     }
 
     /// Creates a rectangle representing a tag on the bar.
@@ -393,27 +870,110 @@ impl BarPainter {
         }
     }
 
-    /// Draws the specified byte array to the pixmap at the given coordinates.
+    /// Draws a window's title bar at the top of its frame, with the title on the left and a close button on the right.
+    ///
+    /// The title bar of the focused window is drawn with the accent color of its tag.
     /// # Errors
-    /// Returns an error if the metrics or data is faulty.
-    fn put_text_data(
+    /// Returns an error if the frame window doesn't exist.
+    pub fn draw_title_bar(
         &self,
         conn: &impl ConnectionActionExt,
-        metrics: Metrics,
-        data: &[u8],
-        base_x: i16,
-        base_y: i16,
+        window: &WindowState,
+        title: &str,
+        height: u16,
+        focused_tag: Option<usize>,
     ) -> Res {
+        let (gc, foreground, background) = match focused_tag {
+            Some(tag) => (
+                self.tag_gcs[tag],
+                self.image.colors.background,
+                self.tag_colors[tag],
+            ),
+            None => (
+                self.inverted_gc,
+                self.image.colors.foreground,
+                self.image.colors.background,
+            ),
+        };
+        conn.fill_rectangle(
+            window.frame_window,
+            gc,
+            Rectangle {
+                x: 0,
+                y: 0,
+                width: window.width,
+                height,
+            },
+        )?;
+
+        let base_y = height as i16 / 2 + self.image.metrics.height as i16 / 5 * 2;
+        let padding = height as i16 / 4;
+        let text = self
+            .image
+            .truncate_text(title, window.width as i16 - height as i16 - padding * 2);
+        let (image, offset_x, offset_y) = self.image.rasterize_text(&text, foreground, background);
+        self.put_image_on(
+            conn,
+            window.frame_window,
+            &image,
+            padding + offset_x,
+            base_y + offset_y,
+        )?;
+
+        let (image, offset_x, offset_y) =
+            self.image
+                .rasterize_text(CLOSE_SYMBOL, foreground, background);
+        let x =
+            window.width as i16 - height as i16 / 2 - self.image.get_text_length(CLOSE_SYMBOL) / 2;
+        self.put_image_on(
+            conn,
+            window.frame_window,
+            &image,
+            x + offset_x,
+            base_y + offset_y,
+        )
+    }
+
+    /// Returns true if the point (relative to the frame) is on the close button of a window's title bar.
+    #[must_use]
+    pub const fn is_on_close_button(window: &WindowState, x: i16, y: i16, height: u16) -> bool {
+        y < height as i16 && x >= window.width as i16 - height as i16
+    }
+
+    /// Draws the specified image to the pixmap with its top left corner at the given coordinates.
+    ///
+    /// Empty images are skipped.
+    /// # Errors
+    /// Returns an error if the image data is faulty.
+    fn put_image(&self, conn: &impl ConnectionActionExt, image: &Image, x: i16, y: i16) -> Res {
+        self.put_image_on(conn, self.pixmap, image, x, y)
+    }
+
+    /// Draws the specified image to a drawable (e.g. a pixmap or a frame window) with its top left corner at the given coordinates.
+    ///
+    /// Empty images are skipped.
+    /// # Errors
+    /// Returns an error if the image data is faulty.
+    fn put_image_on(
+        &self,
+        conn: &impl ConnectionActionExt,
+        drawable: Pixmap,
+        image: &Image,
+        x: i16,
+        y: i16,
+    ) -> Res {
+        if image.width == 0 || image.height == 0 {
+            return Ok(());
+        }
         conn.draw_to_pixmap(
-            self.pixmap,
+            drawable,
             self.gc,
-            base_x + metrics.xmin as i16,
-            base_y - metrics.height as i16 - metrics.ymin as i16,
-            metrics.width as u16,
-            metrics.height as u16,
-            data,
-        )?;
-        Ok(())
+            x,
+            y,
+            image.width as u16,
+            image.height as u16,
+            &image.data,
+        )
     }
 }
 
@@ -423,3 +983,17 @@ impl BarPainter {
 fn tag_is_used(bitmask: u16, tag: usize) -> bool {
     bitmask & (1 << tag) != 0
 }
+
+/// Computes where text is drawn from for a bar of the specified height, returning the base x coordinate, the width of the layout indicator and the base y coordinate.
+fn text_offsets(text: &ImageHandler, height: u16) -> (i16, i16, i16) {
+    // fits any layout symbol with a two digit window count
+    let layout_width = LAYOUT_SYMBOLS
+        .iter()
+        .map(|s| text.get_text_length(&format!("{s} 99")))
+        .max()
+        .unwrap_or_default()
+        + height as i16 / 4;
+    let base_x = height as i16 * TAG_COUNT as i16 + layout_width + height as i16 / 2;
+    let base_y = (height as i16 / 2) + text.metrics.height as i16 / 5 * 2;
+    (base_x, layout_width, base_y)
+}
@@ -0,0 +1,93 @@
+//!
+//! This module monitors the battery, warning about a low charge even while a fullscreen window hides the bar.
+//!
+//! The charge is read from `/sys/class/power_supply` every so often. When it drops to the low or critical threshold while discharging, a warning is shown on the OSD, and the configured command (e.g. `systemctl suspend`) is run at the critical threshold. Each threshold warns once until the battery is charged above it again.
+use core::time::Duration;
+use std::time::Instant;
+
+use crate::{blocks::battery_level, config::Config, connection::spawn_command};
+
+/// How often the battery is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A warning about the battery's charge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryWarning {
+    /// The charge dropped to the low threshold.
+    Low(u32),
+    /// The charge dropped to the critical threshold.
+    Critical(u32),
+}
+
+/// A helper that checks the battery's charge against the configured thresholds.
+pub struct BatteryMonitor {
+    /// The name of the battery (e.g. `BAT0`).
+    name: String,
+    /// The charge in percent at which a warning is shown. Is 0 if disabled.
+    low: u32,
+    /// The charge in percent at which a warning is shown and the command is run. Is 0 if disabled.
+    critical: u32,
+    /// The command run at the critical threshold. Nothing is run if empty.
+    command: String,
+    /// When the battery was last checked. Is `None` if it was never checked.
+    checked: Option<Instant>,
+    /// The lowest threshold already warned about since the battery was last above it.
+    warned: Option<BatteryWarning>,
+}
+
+impl BatteryMonitor {
+    /// Creates a new monitor from the battery parameters in the config.
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        Self {
+            name: config.battery_name.clone(),
+            low: config.battery_low,
+            critical: config.battery_critical,
+            command: config.battery_critical_command.clone(),
+            checked: None,
+            warned: None,
+        }
+    }
+
+    /// Checks the battery if the interval has passed, running the critical command if needed.
+    ///
+    /// Returns the warning to show, if the charge dropped to a threshold that wasn't warned about yet.
+    pub fn check(&mut self) -> Option<BatteryWarning> {
+        if self.low == 0 && self.critical == 0
+            || self.checked.is_some_and(|c| c.elapsed() < CHECK_INTERVAL)
+        {
+            return None;
+        }
+        self.checked = Some(Instant::now());
+        let (capacity, charging) = battery_level(&self.name)?;
+
+        let warning = if charging {
+            None
+        } else if capacity <= self.critical {
+            Some(BatteryWarning::Critical(capacity))
+        } else if capacity <= self.low {
+            Some(BatteryWarning::Low(capacity))
+        } else {
+            None
+        };
+        let new = warning.filter(|_| severity(warning) > severity(self.warned));
+        self.warned = warning;
+
+        if let Some(BatteryWarning::Critical(capacity)) = new {
+            log::warn!("battery {} is critically low at {capacity}%", self.name);
+            if !self.command.is_empty() {
+                spawn_command(&self.command);
+            }
+        }
+        new
+    }
+}
+
+/// Ranks warnings, so that only a worse warning than the last one is shown.
+const fn severity(warning: Option<BatteryWarning>) -> u8 {
+    match warning {
+        None => 0,
+        Some(BatteryWarning::Low(_)) => 1,
+        Some(BatteryWarning::Critical(_)) => 2,
+    }
+}
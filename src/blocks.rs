@@ -0,0 +1,557 @@
+//!
+//! This module provides built-in status blocks for the bar, such as a clock or the battery's charge.
+//!
+//! Blocks are configured in the config file and drawn next to the status text, in the center or on the right side of the bar. Each block is refreshed at its own pace, so reading the volume doesn't happen on every redraw.
+//!
+//! Command blocks run a shell command in the background and show the first line of its output, like `i3blocks` or `dwmblocks`. Volume and notification blocks read their state the same way, so a slow sound server or notification daemon never holds up the manager.
+use std::{
+    fs,
+    io::Read,
+    mem,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex, mpsc::Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{bar::Segments, config::Config};
+
+/// The shell command printing the volume of the default output and whether it's muted.
+///
+/// `pactl` is tried first, falling back to `amixer` for plain ALSA setups.
+const VOLUME_COMMAND: &str = "pactl get-sink-volume @DEFAULT_SINK@ && pactl get-sink-mute @DEFAULT_SINK@ || amixer get Master";
+/// The shell command printing whether `dunst` is paused, then its number of waiting and of displayed notifications.
+const NOTIFICATIONS_COMMAND: &str =
+    "dunstctl is-paused && dunstctl count waiting && dunstctl count displayed";
+
+/// The segment of the bar a block is drawn in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+    /// The middle of the bar.
+    Center,
+    /// The right side of the bar, after the status text.
+    #[default]
+    Right,
+}
+
+/// The bar a block is drawn on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockBar {
+    /// The main bar, after the status text.
+    #[default]
+    Main,
+    /// The secondary bar at the opposite edge of the screen, which only shows its blocks.
+    Secondary,
+}
+
+/// The kinds of built-in blocks and their parameters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BlockKind {
+    /// The current date and time, formatted with a `strftime` format string (e.g. `%a %d %b %H:%M`).
+    Clock {
+        /// The format string.
+        #[serde(default = "default_clock_format")]
+        format: String,
+    },
+    /// The charge of a battery, read from `/sys/class/power_supply`.
+    Battery {
+        /// The name of the battery (e.g. `BAT0`).
+        #[serde(default = "default_battery")]
+        name: String,
+    },
+    /// The volume of the default audio output, read with `pactl` or `amixer`.
+    Volume,
+    /// The brightness of the first backlight, read from `/sys/class/backlight`.
+    Backlight,
+    /// Whether `dunst` is paused (do not disturb), along with its number of unread notifications, read with `dunstctl`.
+    Notifications,
+    /// Whether a network interface is up, along with its download and upload rates.
+    Network {
+        /// The name of the interface (e.g. `wlan0`).
+        interface: String,
+    },
+    /// The first line printed by a shell command.
+    Command {
+        /// The command, run with `sh -c`.
+        command: String,
+    },
+}
+
+/// A level changed with a hotkey, read once the command changing it has finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// The volume in percent and whether it's muted, if it could be read.
+    Volume(Option<(u32, bool)>),
+    /// The brightness of the first backlight in percent, if it could be read.
+    Brightness(Option<u32>),
+    /// Whether `dunst` is paused along with its number of notifications, if it could be read.
+    Notifications(Option<(bool, u32)>),
+}
+
+/// A built-in block as found in the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockConfig {
+    /// The kind of the block.
+    #[serde(flatten)]
+    pub kind: BlockKind,
+    /// The segment of the bar the block is drawn in.
+    #[serde(default)]
+    pub align: Alignment,
+    /// The bar the block is drawn on.
+    #[serde(default)]
+    pub bar: BlockBar,
+    /// How often the block is refreshed, in seconds. Uses the kind's default if not set.
+    #[serde(default)]
+    pub interval: Option<u64>,
+}
+
+/// A block along with its last output.
+struct Block {
+    /// The configuration of the block.
+    config: BlockConfig,
+    /// The text the block last produced.
+    text: String,
+    /// The last time the block was refreshed. Is `None` if the block was never refreshed.
+    updated: Option<Instant>,
+    /// The received and transmitted byte counts of a network block at its last refresh.
+    traffic: (u64, u64),
+    /// The running command of a command or volume block, if any.
+    running: Option<Child>,
+}
+
+/// A helper that refreshes the configured blocks and keeps their output.
+pub struct BlockHandler {
+    /// The configured blocks, in order.
+    blocks: Vec<Block>,
+    /// The levels read after hotkey commands finished, until they're taken.
+    levels: Arc<Mutex<Vec<Level>>>,
+    /// Wakes the event loop once a level was read, so it's shown right away.
+    waker: Option<Sender<()>>,
+}
+
+impl BlockHandler {
+    /// Creates a new handler from the blocks in the config. Blocks are refreshed on the first update.
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        Self {
+            blocks: config
+                .blocks
+                .iter()
+                .map(|b| Block {
+                    config: b.clone(),
+                    text: String::new(),
+                    updated: None,
+                    traffic: (0, 0),
+                    running: None,
+                })
+                .collect(),
+            levels: Arc::new(Mutex::new(Vec::new())),
+            waker: None,
+        }
+    }
+
+    /// Sets the channel used to wake the event loop once a level was read.
+    pub fn set_waker(&mut self, waker: Sender<()>) {
+        self.waker = Some(waker);
+    }
+
+    /// Runs a command changing the volume, the brightness or whether notifications are paused in a separate thread, then reads the new level there, so neither holds up the manager.
+    ///
+    /// The level can be taken with `take_levels` once the event loop is woken. Other kinds of blocks have no level, so their command is only run.
+    pub fn change_level(&self, command: &str, kind: &BlockKind) {
+        let command = command.to_owned();
+        let kind = kind.clone();
+        let levels = Arc::clone(&self.levels);
+        let waker = self.waker.clone();
+        thread::spawn(move || {
+            crate::connection::run_command(&command);
+            let level = match kind {
+                BlockKind::Volume => Level::Volume(volume_level()),
+                BlockKind::Backlight => Level::Brightness(brightness_level()),
+                BlockKind::Notifications => Level::Notifications(notification_state()),
+                _ => return,
+            };
+            if let Ok(mut levels) = levels.lock() {
+                levels.push(level);
+            }
+            if let Some(waker) = waker {
+                let _ = waker.send(());
+            }
+        });
+    }
+
+    /// Takes the levels read since the last call, oldest first, showing them on the blocks of their kind right away.
+    pub fn take_levels(&mut self) -> Vec<Level> {
+        let levels = self
+            .levels
+            .lock()
+            .map(|mut l| mem::take(&mut *l))
+            .unwrap_or_default();
+        for level in &levels {
+            let (kind, text) = match *level {
+                Level::Volume(volume) => (BlockKind::Volume, format_volume(volume)),
+                Level::Notifications(state) => {
+                    (BlockKind::Notifications, format_notifications(state))
+                }
+                Level::Brightness(brightness) => {
+                    (BlockKind::Backlight, format_backlight(brightness))
+                }
+            };
+            self.blocks
+                .iter_mut()
+                .filter(|b| mem::discriminant(&b.config.kind) == mem::discriminant(&kind))
+                .for_each(|b| {
+                    b.text.clone_from(&text);
+                    b.updated = Some(Instant::now());
+                });
+        }
+        levels
+    }
+
+    /// Refreshes every block whose interval has passed, and collects the output of finished commands.
+    ///
+    /// Returns whether the output of any block changed.
+    pub fn update(&mut self) -> bool {
+        let mut changed = false;
+        for block in &mut self.blocks {
+            let text = if block.running.is_some() {
+                match collect_command(&mut block.running) {
+                    Some(output) => block.config.kind.format_output(&output),
+                    None => continue,
+                }
+            } else {
+                let interval = block
+                    .config
+                    .interval
+                    .map_or_else(|| block.config.kind.interval(), Duration::from_secs);
+                if block.updated.is_some_and(|u| u.elapsed() < interval) {
+                    continue;
+                }
+                let elapsed = block.updated.map_or(interval, |u| u.elapsed());
+                block.updated = Some(Instant::now());
+
+                if let Some(command) = block.config.kind.command() {
+                    block.running = spawn_command(command);
+                    continue;
+                }
+                match &block.config.kind {
+                    BlockKind::Clock { format } => read_clock(format),
+                    BlockKind::Battery { name } => read_battery(name),
+                    BlockKind::Backlight => format_backlight(brightness_level()),
+                    BlockKind::Network { interface } => {
+                        read_network(interface, &mut block.traffic, elapsed)
+                    }
+                    BlockKind::Volume | BlockKind::Notifications | BlockKind::Command { .. } => {
+                        continue;
+                    }
+                }
+            };
+            if text != block.text {
+                block.text = text;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Refreshes the blocks of the same kind right away, such as the volume blocks after the volume was changed.
+    ///
+    /// Returns whether the output of any block changed.
+    pub fn refresh(&mut self, kind: &BlockKind) -> bool {
+        self.blocks
+            .iter_mut()
+            .filter(|b| mem::discriminant(&b.config.kind) == mem::discriminant(kind))
+            .for_each(|b| b.updated = None);
+        self.update()
+    }
+
+    /// Gets the output of the blocks drawn on the specified bar, as pieces of the segments they're drawn in.
+    #[must_use]
+    pub fn segments(&self, bar: BlockBar) -> Segments {
+        let pieces = |align: Alignment| {
+            self.blocks
+                .iter()
+                .filter(|b| b.config.bar == bar && b.config.align == align && !b.text.is_empty())
+                .map(|b| b.text.clone())
+                .collect()
+        };
+        Segments {
+            center: pieces(Alignment::Center),
+            right: pieces(Alignment::Right),
+        }
+    }
+}
+
+impl BlockKind {
+    /// Gets how often the block is refreshed.
+    const fn interval(&self) -> Duration {
+        match self {
+            Self::Clock { .. } => Duration::from_secs(1),
+            Self::Battery { .. } => Duration::from_secs(30),
+            Self::Volume | Self::Backlight | Self::Notifications => Duration::from_secs(2),
+            Self::Network { .. } => Duration::from_secs(3),
+            Self::Command { .. } => Duration::from_secs(5),
+        }
+    }
+
+    /// Gets the shell command whose output the block shows, for blocks read in the background.
+    fn command(&self) -> Option<&str> {
+        match self {
+            Self::Volume => Some(VOLUME_COMMAND),
+            Self::Notifications => Some(NOTIFICATIONS_COMMAND),
+            Self::Command { command } => Some(command),
+            _ => None,
+        }
+    }
+
+    /// Gets the text of the block from the output of its command.
+    fn format_output(&self, output: &str) -> String {
+        match self {
+            Self::Volume => format_volume(parse_volume(output)),
+            Self::Notifications => format_notifications(parse_notifications(output)),
+            _ => output.lines().next().unwrap_or_default().trim().to_owned(),
+        }
+    }
+}
+
+/// Gets the default clock format.
+fn default_clock_format() -> String {
+    String::from("%a %d %b %H:%M")
+}
+
+/// Gets the default battery name.
+fn default_battery() -> String {
+    String::from("BAT0")
+}
+
+/// Formats the current local time.
+fn read_clock(format: &str) -> String {
+    jiff::fmt::strtime::format(format, &jiff::Zoned::now()).unwrap_or_else(|e| {
+        log::error!("bad clock format {format}: {e}");
+        String::new()
+    })
+}
+
+/// Reads the charge of a battery, such as `BAT 85%+` while charging.
+fn read_battery(name: &str) -> String {
+    match battery_level(name) {
+        Some((capacity, true)) => format!("BAT {capacity}%+"),
+        Some((capacity, false)) => format!("BAT {capacity}%"),
+        None => String::new(),
+    }
+}
+
+/// Reads the charge of a battery in percent, and whether it's charging.
+#[must_use]
+pub fn battery_level(name: &str) -> Option<(u32, bool)> {
+    let path = format!("/sys/class/power_supply/{name}");
+    let capacity = fs::read_to_string(format!("{path}/capacity"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    let status = fs::read_to_string(format!("{path}/status")).unwrap_or_default();
+    Some((capacity, status.trim() == "Charging"))
+}
+
+/// Formats the volume of the default output, such as `VOL 40%` or `VOL muted`.
+fn format_volume(level: Option<(u32, bool)>) -> String {
+    match level {
+        Some((_, true)) => String::from("VOL muted"),
+        Some((volume, false)) => format!("VOL {volume}%"),
+        None => String::new(),
+    }
+}
+
+/// Formats the brightness of the first backlight, such as `BRI 60%`.
+fn format_backlight(brightness: Option<u32>) -> String {
+    brightness.map_or_else(String::new, |b| format!("BRI {b}%"))
+}
+
+/// Gets the sysfs directory of the first backlight.
+fn backlight_device() -> Option<PathBuf> {
+    Some(
+        fs::read_dir("/sys/class/backlight")
+            .ok()?
+            .flatten()
+            .next()?
+            .path(),
+    )
+}
+
+/// Reads a number from a file of a backlight's sysfs directory.
+fn read_backlight_value(device: &Path, name: &str) -> Option<u32> {
+    fs::read_to_string(device.join(name))
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+}
+
+/// Reads the brightness of the first backlight in percent.
+#[must_use]
+pub fn brightness_level() -> Option<u32> {
+    let device = backlight_device()?;
+    Some(
+        read_backlight_value(&device, "brightness")? * 100
+            / read_backlight_value(&device, "max_brightness")?.max(1),
+    )
+}
+
+/// Changes the brightness of the first backlight by the specified amount in percent, writing to sysfs directly instead of running a command. Returns the new brightness in percent.
+///
+/// The brightness never goes below 1%, so the screen doesn't turn off. Writing the brightness needs permission, usually given to the `video` group by a udev rule.
+/// # Errors
+/// Returns an error if there's no backlight, or its brightness can't be read or written.
+#[cfg(feature = "native-backlight")]
+pub fn change_brightness(change: i32) -> std::io::Result<u32> {
+    let missing = || std::io::Error::new(std::io::ErrorKind::NotFound, "no readable backlight");
+    let device = backlight_device().ok_or_else(missing)?;
+    let max = read_backlight_value(&device, "max_brightness")
+        .ok_or_else(missing)?
+        .max(1);
+    let current = read_backlight_value(&device, "brightness").ok_or_else(missing)?;
+    let percent = (i64::from(current * 100 / max) + i64::from(change)).clamp(1, 100) as u32;
+    fs::write(
+        device.join("brightness"),
+        (percent * max / 100).max(1).to_string(),
+    )?;
+    Ok(percent)
+}
+
+/// Reads the volume of the default output in percent, and whether it's muted, waiting for `VOLUME_COMMAND` to finish.
+#[must_use]
+pub fn volume_level() -> Option<(u32, bool)> {
+    parse_volume(&run("sh", &["-c", VOLUME_COMMAND])?)
+}
+
+/// Parses the volume in percent and whether it's muted from the output of `VOLUME_COMMAND`.
+fn parse_volume(output: &str) -> Option<(u32, bool)> {
+    let muted = output.contains("Mute: yes") || output.contains("[off]");
+    output
+        .split(|c: char| c.is_whitespace() || c == '[' || c == ']')
+        .find_map(|w| w.strip_suffix('%')?.parse::<u32>().ok())
+        .map(|v| (v, muted))
+}
+
+/// Formats the notification state of `dunst`, such as `DND 3` while paused with 3 notifications waiting, or `MSG 2` with 2 notifications shown.
+fn format_notifications(state: Option<(bool, u32)>) -> String {
+    match state {
+        Some((true, 0)) => String::from("DND"),
+        Some((true, waiting)) => format!("DND {waiting}"),
+        Some((false, shown)) if shown > 0 => format!("MSG {shown}"),
+        _ => String::new(),
+    }
+}
+
+/// Reads whether `dunst` is paused (do not disturb), along with its number of notifications waiting if paused, or shown if not, waiting for `NOTIFICATIONS_COMMAND` to finish.
+#[must_use]
+pub fn notification_state() -> Option<(bool, u32)> {
+    parse_notifications(&run("sh", &["-c", NOTIFICATIONS_COMMAND])?)
+}
+
+/// Parses the notification state of `dunst` from the output of `NOTIFICATIONS_COMMAND`.
+fn parse_notifications(output: &str) -> Option<(bool, u32)> {
+    let mut lines = output.lines().map(str::trim);
+    let paused = lines.next()? == "true";
+    let waiting = lines.next().and_then(|c| c.parse().ok()).unwrap_or(0);
+    let displayed = lines.next().and_then(|c| c.parse().ok()).unwrap_or(0);
+    Some((paused, if paused { waiting } else { displayed }))
+}
+
+/// Runs a program and gets its output, if it ran successfully.
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).into_owned())
+}
+
+/// Reads whether a network interface is up, along with its download and upload rates since the last refresh, such as `wlan0 ↓1.2K ↑300B`.
+fn read_network(interface: &str, traffic: &mut (u64, u64), elapsed: Duration) -> String {
+    let path = format!("/sys/class/net/{interface}");
+    let Ok(state) = fs::read_to_string(format!("{path}/operstate")) else {
+        return String::new();
+    };
+    // some interfaces (e.g. loopback) report an unknown state while working
+    if state.trim() == "down" {
+        return format!("{interface} down");
+    }
+
+    let read = |name: &str| {
+        fs::read_to_string(format!("{path}/statistics/{name}"))
+            .ok()
+            .and_then(|b| b.trim().parse::<u64>().ok())
+            .unwrap_or_default()
+    };
+    let (rx, tx) = (read("rx_bytes"), read("tx_bytes"));
+    let seconds = elapsed.as_secs_f64().max(1.0);
+    let rate = |now: u64, before: u64| format_bytes((now.saturating_sub(before)) as f64 / seconds);
+    let text = if *traffic == (0, 0) {
+        format!("{interface} up")
+    } else {
+        format!(
+            "{interface} ↓{} ↑{}",
+            rate(rx, traffic.0),
+            rate(tx, traffic.1)
+        )
+    };
+    *traffic = (rx, tx);
+    text
+}
+
+impl Drop for BlockHandler {
+    /// Kills and reaps the running block commands, so none are left behind when the manager exits.
+    fn drop(&mut self) {
+        for mut child in self.blocks.iter_mut().filter_map(|b| b.running.take()) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Starts a block's command in the background, with its output piped.
+fn spawn_command(command: &str) -> Option<Child> {
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .inspect_err(|e| log::error!("could not run block command {command}: {e}"))
+        .ok()
+}
+
+/// Gets the output of a block's command if it has finished, without waiting for it.
+///
+/// The command is taken once it has finished, so the block can be refreshed again.
+fn collect_command(running: &mut Option<Child>) -> Option<String> {
+    let child = running.as_mut()?;
+    match child.try_wait() {
+        Ok(None) => return None,
+        Ok(Some(_)) => {}
+        Err(e) => log::error!("could not wait for block command: {e}"),
+    }
+    let mut child = running.take()?;
+    let mut output = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        let _ = stdout.read_to_string(&mut output);
+    }
+    Some(output)
+}
+
+/// Formats a byte count with a short unit, such as `1.2K`.
+fn format_bytes(bytes: f64) -> String {
+    match bytes {
+        b if b >= 1_048_576.0 => format!("{:.1}M", b / 1_048_576.0),
+        b if b >= 1024.0 => format!("{:.1}K", b / 1024.0),
+        b => format!("{b:.0}B"),
+    }
+}
@@ -2,9 +2,21 @@
 //! This module uses the `serde` crate to serialize and deserialize a config file.
 //!
 //! The config is used to change the appearance of the manager, how it tiles windows, and the functions of hotkeys.
-use crate::keys::HotkeyAction;
+use crate::{
+    bar::{BarMode, BarVisibility},
+    blocks::BlockConfig,
+    error::HematiteError,
+    idle::IdleInhibit,
+    keys::{HotkeyAction, parse_keysym, parse_modifier},
+    state::{
+        BarPosition, BypassCompositor, Corner, FocusCycle, FocusStealing, Increments, Placement,
+        TagCycle, TitleBars,
+    },
+    validate::Validator,
+};
+use core::fmt::Write as _;
 use serde::{Deserialize, Serialize};
-use std::num::ParseIntError;
+use std::{collections::HashMap, num::ParseIntError, path::Path};
 use x11rb::protocol::render::Color;
 
 /// The default gap between a window's edge and its surrounding edge.
@@ -27,63 +39,412 @@ pub const SECONDARY_COLOR: Color = Color {
     blue: 60652,
     alpha: 65535,
 }; // #74c7ec
+/// The default color of urgent windows' borders.
+pub const URGENT_COLOR: Color = Color {
+    red: 62451,
+    green: 35723,
+    blue: 43176,
+    alpha: 65535,
+}; // #f38ba8
 /// The default font.
 pub const FONT: &str = "/usr/share/fonts/gnu-free/FreeSans.otf";
 /// The default font size.
 pub const FONT_SIZE:u32 = 12;
+/// The default terminal emulator.
+pub const TERMINAL: &str = "alacritty";
+/// The default tag idle windows are moved to.
+pub const PARKING_TAG: usize = 9;
+/// The default command notifying the user about parked windows.
+pub const PARKING_NOTIFY: &str =
+    "notify-send hematite \"moved $HEMATITE_PARKED idle window(s) to tag $HEMATITE_TAG\"";
+/// The default distance within which moved floating windows snap to edges.
+pub const SNAP_DISTANCE: u16 = 10;
+/// The default height of title bars.
+pub const TITLE_HEIGHT: u16 = 20;
+/// The default size of window previews on the bar.
+pub const PREVIEW_SIZE: u16 = 240;
+/// The default number of seconds on-screen display messages are shown for.
+pub const OSD_TIMEOUT: u64 = 1;
+/// The lowest opacity a window can be given, so it never disappears completely.
+pub const MIN_OPACITY: f32 = 0.1;
+/// The DPI sizes are given for, at which they aren't scaled.
+pub const BASE_DPI: f32 = 96.0;
+/// The default battery monitored for warnings.
+pub const BATTERY: &str = "BAT0";
+/// The default battery charge in percent at which a warning is shown.
+pub const BATTERY_LOW: u32 = 15;
+/// The default battery charge in percent at which the critical command is run.
+pub const BATTERY_CRITICAL: u32 = 5;
+/// The default maximum width of picture-in-picture windows.
+pub const PIP_MAX_WIDTH: u16 = 480;
+/// The default maximum height of picture-in-picture windows.
+pub const PIP_MAX_HEIGHT: u16 = 270;
 
-/// A map between a regular RGBA color and X11's color format
-fn hex_color_to_argb(hex: &str) -> Result<Color, ParseIntError> {
+/// A map between a regular RGB(A) color and X11's color format
+///
+/// Colors without an alpha component (`#RRGGBB`) are opaque.
+/// # Errors
+/// Returns an error if a component isn't valid hex.
+/// # Panics
+/// Panics if the color is shorter than `#RRGGBB`.
+pub fn hex_color_to_argb(hex: &str) -> Result<Color, ParseIntError> {
     Ok(Color {
         red: u16::from_str_radix(&hex[1..3], 16)? * 257,
         green: u16::from_str_radix(&hex[3..5], 16)? * 257,
         blue: u16::from_str_radix(&hex[5..7], 16)? * 257,
-        alpha: 65535,
+        alpha: hex
+            .get(7..9)
+            .map_or(Ok(255), |a| u16::from_str_radix(a, 16))?
+            * 257,
     })
 }
 
+/// Parses an optional hex color, falling back to the default if it's missing or malformed.
+fn parse_color(hex: Option<&str>, default: Color) -> Color {
+    hex.map_or(Ok(default), hex_color_to_argb)
+        .unwrap_or_else(|_| {
+            log::debug!("BAD COLOR VALUE");
+            default
+        })
+}
+
 #[derive(Clone)]
 /// All the things a user might want to change about the application.
 pub struct Config {
-    /// The gap between the window's edge and the surrounding edge.
-    pub spacing: u32,
+    /// The gap between windows.
+    pub inner_spacing: u32,
+    /// The gap between windows and the edges of the screen.
+    pub outer_spacing: u32,
     /// The ratio between `Master` and `Stack` group sizes.
     pub ratio: f32,
     /// The size of the window border.
     pub border_size: u32,
-    /// The main color to be used for backgrounds.
-    pub main_color: Color,
-    /// The secondary color to be used for text and borders.
-    pub secondary_color: Color,
+    /// Whether a tag's only tiled window drops its outer gaps.
+    pub smart_gaps: bool,
+    /// Whether a tag's only tiled window drops its border.
+    pub smart_borders: bool,
+    /// How the size increments of tiled windows are treated.
+    pub increments: Increments,
+    /// The distance in pixels within which moved floating windows snap to edges.
+    pub snap_distance: u16,
+    /// Where new floating windows that didn't ask for a position are placed.
+    pub placement: Placement,
+    /// The DPI sizes are scaled for, or zero to detect it.
+    pub dpi: u32,
+    /// What the focus moves between when cycling through windows.
+    pub focus_cycle: FocusCycle,
+    /// Which tags are gone through when cycling through tags.
+    pub tag_cycle: TagCycle,
+    /// Whether new windows may take the focus while the user is busy with another window.
+    pub focus_stealing: FocusStealing,
+    /// Which windows get a title bar.
+    pub title_bars: TitleBars,
+    /// The height of title bars in pixels.
+    pub title_height: u16,
+    /// Which windows the compositor is asked to stop compositing.
+    pub bypass_compositor: BypassCompositor,
+    /// The shell commands run on window and tag events.
+    pub hooks: Hooks,
+    /// The border color of the focused window.
+    pub focused_border_color: Color,
+    /// The border color of unfocused windows.
+    pub unfocused_border_color: Color,
+    /// The border color of windows demanding attention.
+    pub urgent_border_color: Color,
+    /// The background color of the bar.
+    pub bar_background_color: Color,
+    /// The text color of the bar.
+    pub bar_foreground_color: Color,
+    /// The color of the active tag's square on the bar.
+    pub tag_highlight_color: Color,
+    /// The accent colors of each tag, overriding the tag highlight and focused border colors while the tag is active.
+    pub tag_colors: Vec<Option<Color>>,
     /// The font to use for drawing text.
     pub font: String,
+    /// The fonts to use for characters missing from the main font, in order of priority.
+    pub fallback_fonts: Vec<String>,
     /// The size to render text at.
     pub font_size: u32,
+    /// The named themes the colors and font can be switched to at runtime.
+    pub themes: HashMap<String, Theme>,
+    /// Overrides of the tiling and bar parameters for specific outputs, keyed by output name (e.g. `DP-1`).
+    pub monitors: HashMap<String, MonitorConfig>,
     /// The hotkeys to track.
     pub hotkeys: Vec<HotkeyConfig>,
+    /// The terminal emulator used to run terminal commands.
+    pub terminal: String,
+    /// The amount of hours a window can go unfocused before being parked. Parking is disabled if zero.
+    pub parking_hours: u64,
+    /// The index of the tag idle windows are moved to.
+    pub parking_tag: usize,
+    /// The command run when windows are parked. Nothing is run if empty.
+    pub parking_notify_command: String,
+    /// The command used to lock the session. Session integration is disabled if empty.
+    pub lock_command: String,
+    /// The number of times to try connecting again after losing the X11 server.
+    pub reconnect_attempts: u32,
+    /// The number of seconds without input before the dim command is run. Dimming is disabled if zero.
+    pub idle_dim_after: u64,
+    /// The command run when the user goes idle, e.g. to dim the screen.
+    pub idle_dim_command: String,
+    /// The command run when the user comes back after the dim command was run.
+    pub idle_undim_command: String,
+    /// The number of seconds without input before the session is locked with the lock command. Locking on idle is disabled if zero.
+    pub idle_lock_after: u64,
+    /// Which windows keep the user from going idle.
+    pub idle_inhibit: IdleInhibit,
+    /// The name of the battery monitored for warnings (e.g. `BAT0`).
+    pub battery_name: String,
+    /// The battery charge in percent at which a warning is shown. Disabled if zero.
+    pub battery_low: u32,
+    /// The battery charge in percent at which a warning is shown and the critical command is run. Disabled if zero.
+    pub battery_critical: u32,
+    /// The command run when the battery is critically low. Nothing is run if empty.
+    pub battery_critical_command: String,
+    /// Rules assigning applications to tags.
+    pub assign: Vec<AssignRule>,
+    /// Rules giving unfocused windows of applications a default opacity.
+    pub opacity: Vec<OpacityRule>,
+    /// The built-in blocks shown on the bar.
+    pub blocks: Vec<BlockConfig>,
+    /// What the bar shows between the tags and the status text.
+    pub bar_mode: BarMode,
+    /// The edge of the screen the bar is placed at.
+    pub bar_position: BarPosition,
+    /// Whether the bar is shown at startup, and whether windows are tiled around it.
+    pub bar_visibility: BarVisibility,
+    /// The largest width and height of window previews shown when hovering the bar. Previews are disabled if 0.
+    pub preview_size: u16,
+    /// The number of seconds on-screen display messages are shown for. The OSD is disabled if 0.
+    pub osd_timeout: u64,
+    /// Commands run once when hematite starts.
+    pub autostart: Vec<String>,
+    /// Commands run every time hematite starts managing windows, including after reconnecting.
+    pub exec_always: Vec<String>,
+    /// Whether picture-in-picture windows are detected and placed automatically.
+    pub pip_enabled: bool,
+    /// The corner picture-in-picture windows are snapped to.
+    pub pip_corner: Corner,
+    /// The maximum width of picture-in-picture windows.
+    pub pip_max_width: u16,
+    /// The maximum height of picture-in-picture windows.
+    pub pip_max_height: u16,
+}
+
+impl Config {
+    /// Scales the font size, gaps, borders and title bars for a DPI, unless the configured DPI overrides the detected one.
+    ///
+    /// The scale is rounded to halves and never shrinks sizes, so regular displays whose DPI is a little above 96 keep their sizes. The bar scales along with the font.
+    pub fn scale_to_dpi(&mut self, detected: f32) {
+        let dpi = if self.dpi == 0 {
+            detected
+        } else {
+            self.dpi as f32
+        };
+        let scale = ((dpi / BASE_DPI) * 2.0).round().max(2.0) / 2.0;
+        if scale <= 1.0 {
+            return;
+        }
+        log::info!("scaling sizes by {scale} for {dpi} dpi");
+        let scaled = |size: u32| (size as f32 * scale).round() as u32;
+        self.font_size = scaled(self.font_size);
+        self.inner_spacing = scaled(self.inner_spacing);
+        self.outer_spacing = scaled(self.outer_spacing);
+        self.border_size = scaled(self.border_size);
+        self.title_height = scaled(u32::from(self.title_height)) as u16;
+        for monitor in self.monitors.values_mut() {
+            for size in [
+                &mut monitor.spacing,
+                &mut monitor.inner_spacing,
+                &mut monitor.outer_spacing,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                *size = scaled(*size);
+            }
+        }
+    }
+
+    /// Switches the colors, and the font if the theme has one, to those of a theme.
+    pub fn apply_theme(&mut self, theme: &Theme) {
+        self.focused_border_color = theme.focused_border_color;
+        self.unfocused_border_color = theme.unfocused_border_color;
+        self.urgent_border_color = theme.urgent_border_color;
+        self.bar_background_color = theme.bar_background_color;
+        self.bar_foreground_color = theme.bar_foreground_color;
+        self.tag_highlight_color = theme.tag_highlight_color;
+        self.tag_colors.clone_from(&theme.tag_colors);
+        if let Some(font) = &theme.font {
+            font.clone_into(&mut self.font);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The colors and font of a named theme, switched to at runtime.
+pub struct Theme {
+    /// The border color of the focused window.
+    pub focused_border_color: Color,
+    /// The border color of unfocused windows.
+    pub unfocused_border_color: Color,
+    /// The border color of windows demanding attention.
+    pub urgent_border_color: Color,
+    /// The background color of the bar.
+    pub bar_background_color: Color,
+    /// The text color of the bar.
+    pub bar_foreground_color: Color,
+    /// The color of the active tag's square on the bar.
+    pub tag_highlight_color: Color,
+    /// The accent colors of each tag.
+    pub tag_colors: Vec<Option<Color>>,
+    /// The path of the font to draw text with. The font isn't changed if `None`.
+    pub font: Option<String>,
+}
+
+impl Theme {
+    /// Parses the colors of a config section, deriving missing colors from the main and secondary colors.
+    fn new(colors: &Colors, font: Option<String>) -> Self {
+        let main_color = parse_color(Some(&colors.main_color), MAIN_COLOR);
+        let secondary_color = parse_color(Some(&colors.secondary_color), SECONDARY_COLOR);
+        let focused_border_color = parse_color(colors.focused_border.as_deref(), secondary_color);
+        let tag_colors = (0..9)
+            .map(|i| {
+                colors.tags.get(i).map(|c| {
+                    hex_color_to_argb(c).unwrap_or_else(|_| {
+                        log::debug!("BAD COLOR VALUE");
+                        focused_border_color
+                    })
+                })
+            })
+            .collect();
+        Self {
+            focused_border_color,
+            unfocused_border_color: parse_color(colors.unfocused_border.as_deref(), main_color),
+            urgent_border_color: parse_color(colors.urgent_border.as_deref(), URGENT_COLOR),
+            bar_background_color: parse_color(colors.bar_background.as_deref(), main_color),
+            bar_foreground_color: parse_color(colors.bar_foreground.as_deref(), secondary_color),
+            tag_highlight_color: parse_color(colors.tag_highlight.as_deref(), secondary_color),
+            tag_colors,
+            font,
+        }
+    }
 }
 
 impl From<ConfigDeserialized> for Config {
     fn from(config: ConfigDeserialized) -> Self {
-        let main_color = hex_color_to_argb(&config.colors.main_color).unwrap_or_else(|_| {
-            log::debug!("BAD COLOR VALUE");
-            MAIN_COLOR
-        });
-        let secondary_color =
-            hex_color_to_argb(&config.colors.secondary_color).unwrap_or_else(|_| {
-                log::debug!("BAD COLOR VALUE");
-                SECONDARY_COLOR
-            });
+        let theme = Theme::new(&config.colors, None);
 
         Self {
-            main_color,
-            secondary_color,
-            spacing: config.sizing.spacing.clamp(0, 1000),
+            focused_border_color: theme.focused_border_color,
+            unfocused_border_color: theme.unfocused_border_color,
+            urgent_border_color: theme.urgent_border_color,
+            bar_background_color: theme.bar_background_color,
+            bar_foreground_color: theme.bar_foreground_color,
+            tag_highlight_color: theme.tag_highlight_color,
+            tag_colors: theme.tag_colors,
+            inner_spacing: config
+                .sizing
+                .inner_spacing
+                .unwrap_or(config.sizing.spacing)
+                .clamp(0, 1000),
+            outer_spacing: config
+                .sizing
+                .outer_spacing
+                .unwrap_or(config.sizing.spacing)
+                .clamp(0, 1000),
             ratio: config.sizing.ratio.clamp(0.0, 1.0),
             border_size: config.sizing.border_size.clamp(0, 1000),
+            smart_gaps: config.sizing.smart_gaps,
+            smart_borders: config.sizing.smart_borders,
+            increments: config.sizing.increments,
+            snap_distance: config.sizing.snap_distance,
+            placement: config.sizing.placement,
+            dpi: config.sizing.dpi,
+            focus_cycle: config.focus.cycle,
+            tag_cycle: config.focus.tags,
+            focus_stealing: config.focus.stealing,
+            title_bars: config.decorations.title_bars,
+            title_height: config.decorations.title_height,
+            bypass_compositor: config.compositor.bypass,
+            hooks: config.hooks,
             font: config.font.path,
+            fallback_fonts: config.font.fallbacks,
             font_size: config.font.size,
+            themes: config
+                .themes
+                .into_iter()
+                .map(|(name, t)| (name, Theme::new(&t.colors, t.font)))
+                .collect(),
+            monitors: config
+                .monitor
+                .into_iter()
+                .map(|(name, m)| {
+                    let monitor = MonitorConfig {
+                        spacing: m.spacing.map(|s| s.clamp(0, 1000)),
+                        inner_spacing: m.inner_spacing.map(|s| s.clamp(0, 1000)),
+                        outer_spacing: m.outer_spacing.map(|s| s.clamp(0, 1000)),
+                        ratio: m.ratio.map(|r| r.clamp(0.0, 1.0)),
+                        tag: m.tag.filter(|t| (1..=9).contains(t)),
+                        ..m
+                    };
+                    if monitor.tag != m.tag {
+                        log::error!("ignoring invalid tag {:?} for monitor {name}", m.tag);
+                    }
+                    (name, monitor)
+                })
+                .collect(),
             hotkeys: config.hotkeys,
+            terminal: config.terminal,
+            parking_hours: config.parking.hours,
+            parking_tag: config.parking.tag.clamp(1, 9) - 1,
+            parking_notify_command: config.parking.notify_command,
+            lock_command: config.session.lock_command,
+            reconnect_attempts: config.session.reconnect_attempts,
+            idle_dim_after: config.idle.dim_after,
+            idle_dim_command: config.idle.dim_command,
+            idle_undim_command: config.idle.undim_command,
+            idle_lock_after: config.idle.lock_after,
+            idle_inhibit: config.idle.inhibit,
+            battery_name: config.battery.name,
+            battery_low: config.battery.low.min(100),
+            battery_critical: config.battery.critical.min(100),
+            battery_critical_command: config.battery.critical_command,
+            assign: config
+                .assign
+                .into_iter()
+                .filter(|r| {
+                    let valid = (1..=9).contains(&r.tag);
+                    if !valid {
+                        log::error!(
+                            "ignoring assign rule for {} with invalid tag {}",
+                            r.class,
+                            r.tag
+                        );
+                    }
+                    valid
+                })
+                .collect(),
+            opacity: config
+                .opacity
+                .into_iter()
+                .map(|r| OpacityRule {
+                    unfocused: r.unfocused.clamp(MIN_OPACITY, 1.0),
+                    ..r
+                })
+                .collect(),
+            blocks: config.blocks,
+            bar_mode: config.bar.mode,
+            bar_position: config.bar.position,
+            bar_visibility: config.bar.visibility,
+            preview_size: config.bar.preview_size,
+            osd_timeout: config.osd.timeout,
+            autostart: config.autostart.commands,
+            exec_always: config.autostart.exec_always,
+            pip_enabled: config.pip.enabled,
+            pip_corner: config.pip.corner,
+            pip_max_width: config.pip.max_width.max(1),
+            pip_max_height: config.pip.max_height.max(1),
         }
     }
 }
@@ -93,40 +454,556 @@ impl From<ConfigDeserialized> for Config {
 ///
 /// This struct is then parsed into the `Config` struct.
 pub struct ConfigDeserialized {
+    /// The terminal emulator.
+    #[serde(default = "default_terminal")]
+    terminal: String,
+    /// Rules assigning applications to tags.
+    #[serde(default)]
+    assign: Vec<AssignRule>,
+    /// Rules giving unfocused windows of applications a default opacity.
+    #[serde(default)]
+    opacity: Vec<OpacityRule>,
+    /// The built-in blocks shown on the bar.
+    #[serde(default)]
+    blocks: Vec<BlockConfig>,
+    /// Other config files merged into this one (e.g. `keys.toml`), relative to this file's directory.
+    ///
+    /// Entries of this file take precedence over included ones.
+    #[serde(default)]
+    include: Vec<String>,
     /// Tiling parameters.
+    #[serde(default)]
     sizing: Sizing,
     /// Color parameters.
+    #[serde(default)]
     colors: Colors,
+    /// Named themes, each with its own colors and optionally font.
+    #[serde(default)]
+    themes: HashMap<String, ThemeConfig>,
+    /// Overrides for specific outputs, keyed by output name.
+    #[serde(default)]
+    monitor: HashMap<String, MonitorConfig>,
     /// The specified font.
+    #[serde(default)]
     font: Font,
     /// The specified hotkeys.
+    #[serde(default = "default_hotkeys")]
     hotkeys: Vec<HotkeyConfig>,
+    /// Bar parameters.
+    #[serde(default)]
+    bar: Bar,
+    /// Focus parameters.
+    #[serde(default)]
+    focus: Focus,
+    /// Window decoration parameters.
+    #[serde(default)]
+    decorations: Decorations,
+    /// Compositor parameters.
+    #[serde(default)]
+    compositor: Compositor,
+    /// Shell commands run on events.
+    #[serde(default)]
+    hooks: Hooks,
+    /// Idle window parking parameters.
+    #[serde(default)]
+    parking: Parking,
+    /// Session integration parameters.
+    #[serde(default)]
+    session: Session,
+    /// Idle detection parameters.
+    #[serde(default)]
+    idle: Idle,
+    /// Battery warning parameters.
+    #[serde(default)]
+    battery: Battery,
+    /// Picture-in-picture window parameters.
+    #[serde(default)]
+    pip: Pip,
+    /// On-screen display parameters.
+    #[serde(default)]
+    osd: Osd,
+    /// Commands run at startup.
+    #[serde(default)]
+    autostart: Autostart,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+/// Tiling parameters.
 struct Sizing {
-    /// The gap between the window's edge and the surrounding edge.
+    /// The gap between windows, and between windows and the edges of the screen.
+    #[serde(default = "default_spacing")]
     spacing: u32,
+    /// The gap between windows. Overrides `spacing` if set.
+    #[serde(default)]
+    inner_spacing: Option<u32>,
+    /// The gap between windows and the edges of the screen. Overrides `spacing` if set.
+    #[serde(default)]
+    outer_spacing: Option<u32>,
     /// The ratio between `Master` and `Stack` group sizes.
+    #[serde(default = "default_ratio")]
     ratio: f32,
     /// The size of the window border.
+    #[serde(default = "default_border_size")]
     border_size: u32,
+    /// Whether a tag's only tiled window drops its outer gaps, using the full work area.
+    #[serde(default)]
+    smart_gaps: bool,
+    /// Whether a tag's only tiled window drops its border.
+    #[serde(default)]
+    smart_borders: bool,
+    /// How the size increments of tiled windows (e.g. terminals) are treated (`respect` or `ignore`).
+    #[serde(default)]
+    increments: Increments,
+    /// The distance in pixels within which moved floating windows snap to the edges of the screen, the bar and other floating windows. Zero disables snapping.
+    #[serde(default = "default_snap_distance")]
+    snap_distance: u16,
+    /// Where new floating windows that didn't ask for a position are placed (`center`, `cascade` or `smart`).
+    #[serde(default)]
+    placement: Placement,
+    /// The DPI the font, gaps and borders are scaled for, relative to 96. Detected from `Xft.dpi` or the size of the primary monitor if zero.
+    #[serde(default)]
+    dpi: u32,
+}
+
+impl Default for Sizing {
+    fn default() -> Self {
+        Self {
+            spacing: SPACING,
+            inner_spacing: None,
+            outer_spacing: None,
+            ratio: RATIO,
+            border_size: BORDER_SIZE,
+            smart_gaps: false,
+            smart_borders: false,
+            increments: Increments::Respect,
+            snap_distance: SNAP_DISTANCE,
+            placement: Placement::Center,
+            dpi: 0,
+        }
+    }
+}
+
+/// Gets the default gap between windows.
+const fn default_spacing() -> u32 {
+    SPACING
+}
+
+/// Gets the default ratio between the master and stack groups.
+const fn default_ratio() -> f32 {
+    RATIO
+}
+
+/// Gets the default border size.
+const fn default_border_size() -> u32 {
+    BORDER_SIZE
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+/// Color parameters.
 struct Colors {
     /// The main color to be used for backgrounds (in hex format).
+    #[serde(default = "default_main_color")]
     main_color: String,
     /// The secondary color to be used for text and borders (in hex format).
+    #[serde(default = "default_secondary_color")]
     secondary_color: String,
+    /// The border color of the focused window (in hex format). Defaults to the secondary color.
+    #[serde(default)]
+    focused_border: Option<String>,
+    /// The border color of unfocused windows (in hex format). Defaults to the main color.
+    #[serde(default)]
+    unfocused_border: Option<String>,
+    /// The border color of windows demanding attention (in hex format).
+    #[serde(default)]
+    urgent_border: Option<String>,
+    /// The background color of the bar (in hex format). Defaults to the main color.
+    #[serde(default)]
+    bar_background: Option<String>,
+    /// The text color of the bar (in hex format). Defaults to the secondary color.
+    #[serde(default)]
+    bar_foreground: Option<String>,
+    /// The color of the active tag's square on the bar (in hex format). Defaults to the secondary color.
+    #[serde(default)]
+    tag_highlight: Option<String>,
+    /// The accent colors of tags 1-9 (in hex format), overriding the tag highlight and focused border colors while the tag is active.
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            main_color: default_main_color(),
+            secondary_color: default_secondary_color(),
+            focused_border: None,
+            unfocused_border: None,
+            urgent_border: None,
+            bar_background: None,
+            bar_foreground: None,
+            tag_highlight: None,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// Gets the default main color.
+fn default_main_color() -> String {
+    String::from("#11111b")
+}
+
+/// Gets the default secondary color.
+fn default_secondary_color() -> String {
+    String::from("#74c7ec")
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+/// A named theme, switched to at runtime with the `SetTheme` hotkey action.
+struct ThemeConfig {
+    /// The colors of the theme, with the same keys as the `[colors]` section.
+    colors: Colors,
+    /// The path of the theme's font. The font size isn't themed, as it sets the height of the bar.
+    #[serde(default)]
+    font: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Font parameters.
 struct Font {
     /// The path of the font.
+    #[serde(default = "default_font")]
     path: String,
     /// The size to render the text at.
+    #[serde(default = "default_font_size")]
     size: u32,
+    /// The paths of fonts used for characters missing from the main font, in order of priority.
+    #[serde(default)]
+    fallbacks: Vec<String>,
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self {
+            path: default_font(),
+            size: FONT_SIZE,
+            fallbacks: Vec::new(),
+        }
+    }
+}
+
+/// Gets the default font path.
+fn default_font() -> String {
+    FONT.to_owned()
+}
+
+/// Gets the default font size.
+const fn default_font_size() -> u32 {
+    FONT_SIZE
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Bar parameters.
+struct Bar {
+    /// What the bar shows between the tags and the status text (`title`, `taskbar` or `class`).
+    #[serde(default)]
+    mode: BarMode,
+    /// The edge of the screen the bar is placed at (`top` or `bottom`).
+    #[serde(default)]
+    position: BarPosition,
+    /// Whether the bar is shown at startup (`shown`, `hidden` or `autohide`).
+    #[serde(default)]
+    visibility: BarVisibility,
+    /// The largest width and height of window previews in pixels, or 0 to disable them.
+    #[serde(default = "default_preview_size")]
+    preview_size: u16,
+}
+
+impl Default for Bar {
+    fn default() -> Self {
+        Self {
+            mode: BarMode::default(),
+            position: BarPosition::default(),
+            visibility: BarVisibility::default(),
+            preview_size: PREVIEW_SIZE,
+        }
+    }
+}
+
+/// Gets the default preview size.
+const fn default_preview_size() -> u16 {
+    PREVIEW_SIZE
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+/// Focus parameters.
+struct Focus {
+    /// What the focus moves between when cycling through windows (`windows` or `groups`).
+    #[serde(default)]
+    cycle: FocusCycle,
+    /// Which tags are gone through when cycling through tags (`all` or `occupied`).
+    #[serde(default)]
+    tags: TagCycle,
+    /// Whether new windows may take the focus while the user is busy with another window (`allow` or `prevent`).
+    #[serde(default)]
+    stealing: FocusStealing,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Window decoration parameters.
+struct Decorations {
+    /// Which windows get a title bar (`none` or `floating`).
+    #[serde(default)]
+    title_bars: TitleBars,
+    /// The height of title bars in pixels.
+    #[serde(default = "default_title_height")]
+    title_height: u16,
+}
+
+impl Default for Decorations {
+    fn default() -> Self {
+        Self {
+            title_bars: TitleBars::default(),
+            title_height: TITLE_HEIGHT,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+/// Compositor parameters.
+struct Compositor {
+    /// Which windows the compositor is asked to stop compositing with `_NET_WM_BYPASS_COMPOSITOR` (`never` or `fullscreen`).
+    #[serde(default)]
+    bypass: BypassCompositor,
+}
+
+/// Gets the default title bar height.
+const fn default_title_height() -> u16 {
+    TITLE_HEIGHT
+}
+
+/// Gets the default snapping distance.
+const fn default_snap_distance() -> u16 {
+    SNAP_DISTANCE
+}
+
+/// Gets the default terminal emulator.
+fn default_terminal() -> String {
+    TERMINAL.to_owned()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Idle window parking parameters.
+struct Parking {
+    /// The amount of hours a window can go unfocused before being moved to the parking tag. Zero disables parking.
+    hours: u64,
+    /// The tag (1-9) idle windows are moved to.
+    tag: usize,
+    /// The command run when windows are parked, given their number in `HEMATITE_PARKED` and the parking tag in `HEMATITE_TAG`.
+    #[serde(default = "default_parking_notify")]
+    notify_command: String,
+}
+
+impl Default for Parking {
+    fn default() -> Self {
+        Self {
+            hours: 0,
+            tag: PARKING_TAG,
+            notify_command: default_parking_notify(),
+        }
+    }
+}
+
+/// Gets the default command notifying about parked windows.
+fn default_parking_notify() -> String {
+    PARKING_NOTIFY.to_owned()
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+/// Session integration parameters.
+struct Session {
+    /// The command used to lock the session before sleeping. It should keep running until the session is unlocked (e.g. `i3lock -n`).
+    lock_command: String,
+    /// The number of times to try connecting again after losing the X11 server. The manager exits if it's 0.
+    #[serde(default)]
+    reconnect_attempts: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+/// Idle detection parameters.
+struct Idle {
+    /// The number of seconds without keyboard or pointer input before the dim command is run, or 0 to never dim.
+    #[serde(default)]
+    dim_after: u64,
+    /// The command run when the user goes idle (e.g. `brightnessctl -s set 10%`).
+    #[serde(default)]
+    dim_command: String,
+    /// The command run when the user comes back after the dim command was run (e.g. `brightnessctl -r`).
+    #[serde(default)]
+    undim_command: String,
+    /// The number of seconds without input before the session is locked with the lock command of the `[session]` section, or 0 to never lock on idle.
+    #[serde(default)]
+    lock_after: u64,
+    /// Which windows keep the user from going idle: `never` or `fullscreen`.
+    #[serde(default)]
+    inhibit: IdleInhibit,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Battery warning parameters.
+struct Battery {
+    /// The name of the battery in `/sys/class/power_supply`.
+    #[serde(default = "default_battery")]
+    name: String,
+    /// The charge in percent at which a warning is shown, or 0 to never warn.
+    #[serde(default = "default_battery_low")]
+    low: u32,
+    /// The charge in percent at which a warning is shown and `critical_command` is run, or 0 to disable it.
+    #[serde(default = "default_battery_critical")]
+    critical: u32,
+    /// The command run when the battery is critically low (e.g. `systemctl suspend`).
+    #[serde(default)]
+    critical_command: String,
+}
+
+impl Default for Battery {
+    fn default() -> Self {
+        Self {
+            name: default_battery(),
+            low: BATTERY_LOW,
+            critical: BATTERY_CRITICAL,
+            critical_command: String::new(),
+        }
+    }
+}
+
+/// Gets the default battery name.
+fn default_battery() -> String {
+    BATTERY.to_owned()
+}
+
+/// Gets the default low battery threshold.
+const fn default_battery_low() -> u32 {
+    BATTERY_LOW
+}
+
+/// Gets the default critical battery threshold.
+const fn default_battery_critical() -> u32 {
+    BATTERY_CRITICAL
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+/// Commands run at startup.
+struct Autostart {
+    /// Shell commands run once when hematite starts.
+    #[serde(default)]
+    commands: Vec<String>,
+    /// Shell commands run every time hematite starts managing windows, including after reconnecting.
+    #[serde(default)]
+    exec_always: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Picture-in-picture window parameters.
+///
+/// Picture-in-picture windows of browsers are made floating, shown on every tag and kept above other windows.
+struct Pip {
+    /// Whether picture-in-picture windows are detected.
+    enabled: bool,
+    /// The corner (`top_left`, `top_right`, `bottom_left` or `bottom_right`) picture-in-picture windows are snapped to.
+    corner: Corner,
+    /// The maximum width of picture-in-picture windows. Bigger windows are shrunk, keeping their aspect ratio.
+    max_width: u16,
+    /// The maximum height of picture-in-picture windows. Bigger windows are shrunk, keeping their aspect ratio.
+    max_height: u16,
+}
+
+impl Default for Pip {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            corner: Corner::BottomRight,
+            max_width: PIP_MAX_WIDTH,
+            max_height: PIP_MAX_HEIGHT,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// On-screen display parameters.
+struct Osd {
+    /// How many seconds messages (e.g. the new tag or volume) are shown for, or 0 to disable the OSD.
+    #[serde(default = "default_osd_timeout")]
+    timeout: u64,
+}
+
+impl Default for Osd {
+    fn default() -> Self {
+        Self {
+            timeout: OSD_TIMEOUT,
+        }
+    }
+}
+
+/// Gets the default OSD timeout.
+const fn default_osd_timeout() -> u64 {
+    OSD_TIMEOUT
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// Shell commands run on events, with details about the event in environment variables.
+///
+/// Every hook gets the active tag (1-9) in `HEMATITE_TAG`. Window hooks also get the window's id, class and title in `HEMATITE_WINDOW`, `HEMATITE_CLASS` and `HEMATITE_TITLE`. Empty commands aren't run.
+pub struct Hooks {
+    /// Run when a window is opened.
+    #[serde(default)]
+    pub window_open: String,
+    /// Run when a window is closed.
+    #[serde(default)]
+    pub window_close: String,
+    /// Run when the active tag changes.
+    #[serde(default)]
+    pub tag_change: String,
+    /// Run when the focused window changes.
+    #[serde(default)]
+    pub focus_change: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A rule that opens the windows of an application on a specific tag.
+pub struct AssignRule {
+    /// The class of the application's windows (e.g. `firefox`), matched case insensitively.
+    pub class: String,
+    /// The tag (1-9) the windows are opened on.
+    pub tag: usize,
+    /// Whether to switch to the tag when a window opens. Otherwise the window opens in the background.
+    #[serde(default)]
+    pub switch: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A rule giving the unfocused windows of an application a default opacity.
+pub struct OpacityRule {
+    /// The class of the application's windows (e.g. `Alacritty`), matched case insensitively.
+    pub class: String,
+    /// The opacity (0.1-1.0) of the windows while they're unfocused.
+    pub unfocused: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+/// Overrides of the tiling and bar parameters on a specific output. Unset parameters keep their global value.
+pub struct MonitorConfig {
+    /// The gap between windows, and between windows and the edges of the screen.
+    pub spacing: Option<u32>,
+    /// The gap between windows. Overrides `spacing` if set.
+    pub inner_spacing: Option<u32>,
+    /// The gap between windows and the edges of the screen. Overrides `spacing` if set.
+    pub outer_spacing: Option<u32>,
+    /// The ratio between `Master` and `Stack` group sizes.
+    pub ratio: Option<f32>,
+    /// Whether the bar is shown, and whether windows are tiled around it.
+    pub bar_visibility: Option<BarVisibility>,
+    /// The tag (1-9) shown at startup.
+    pub tag: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -140,7 +1017,72 @@ pub struct HotkeyConfig {
     pub action: HotkeyAction,
 }
 
+/// The comment written at the top of the default config file.
+const HEADER_COMMENT: &str = "# The default hematite config, read from $XDG_CONFIG_HOME/hematite/config.toml.
+# Print it again with `hematite --print-default-config`, and a JSON schema of it with `hematite --print-schema`.
+
+";
+
+/// The comments written above each section of the default config file.
+const SECTION_COMMENTS: &[(&str, &str)] = &[
+    ("sizing", "Gaps, borders and how windows are tiled."),
+    ("colors", "Colors in hex format (#rrggbb or #rrggbbaa)."),
+    ("font", "The font of the bar and popups."),
+    (
+        "hotkeys",
+        "Each hotkey's modifiers (e.g. CONTROL|MOD), key (an X keysym name) and action.",
+    ),
+    ("bar", "What the bar shows and where."),
+    ("focus", "Which windows and tags cycling goes through."),
+    ("decorations", "Title bars of windows."),
+    ("compositor", "Hints for a running compositor."),
+    ("hooks", "Shell commands run on events."),
+    ("parking", "Parking windows that were idle for a while."),
+    ("session", "Locking the session and inhibiting sleep."),
+    (
+        "idle",
+        "Dimming the screen and locking the session when idle. Thresholds are in seconds, 0 disables.",
+    ),
+    (
+        "battery",
+        "Warnings about a low battery. Thresholds are in percent, 0 disables.",
+    ),
+    ("pip", "Picture-in-picture windows."),
+    ("osd", "The popup showing what a hotkey changed."),
+    ("autostart", "Commands run at startup."),
+];
+
 impl ConfigDeserialized {
+    /// Serializes the default config to TOML, with a comment above each section.
+    /// # Errors
+    /// Returns an error if the default config can't be serialized.
+    pub fn default_toml() -> Result<String, HematiteError> {
+        let serialized =
+            toml::to_string(&Self::default()).map_err(|e| HematiteError::Config(e.to_string()))?;
+        let mut commented = HEADER_COMMENT.to_owned();
+        for line in serialized.lines() {
+            let section = line.trim_start_matches('[').trim_end_matches(']');
+            if line.starts_with('[')
+                && let Some((_, comment)) = SECTION_COMMENTS.iter().find(|(s, _)| *s == section)
+                && !commented.contains(&format!("\n{line}\n"))
+            {
+                let _ = writeln!(commented, "# {comment}");
+            }
+            commented.push_str(line);
+            commented.push('\n');
+        }
+        Ok(commented)
+    }
+
+    /// Generates a JSON schema of the config file from the default config.
+    /// # Errors
+    /// Returns an error if the default config can't be serialized.
+    pub fn schema() -> Result<String, HematiteError> {
+        let table = toml::Table::try_from(Self::default())
+            .map_err(|e| HematiteError::Config(e.to_string()))?;
+        Ok(crate::schema::generate(&table))
+    }
+
     /// Creates a new config from a file.
     #[must_use] 
     pub fn new() -> Self {
@@ -160,7 +1102,7 @@ impl ConfigDeserialized {
             Err(e) => {
                 log::info!("config not found {e:?}, serializing default");
 
-                let Ok(serialized) = toml::to_string(&Self::default()) else {
+                let Ok(serialized) = Self::default_toml() else {
                     log::error!("couldn't serialize config into file, using default");
                     return Self::default();
                 };
@@ -176,156 +1118,295 @@ impl ConfigDeserialized {
             }
         };
 
-        match toml::from_str(&config_str) {
-            Ok(d) => d,
+        Self::parse(&config_str, path.parent())
+    }
+
+    /// Parses a config, logging each invalid entry with its key path and line.
+    ///
+    /// Invalid entries are dropped or replaced by their defaults, and so are hotkeys with an unknown key or modifier or a tag outside 1 to 9. Only a syntax error falls back to the default config.
+    ///
+    /// The files listed in `include` are merged in, relative to the specified directory. Includes are ignored without a directory.
+    #[must_use]
+    pub fn parse(source: &str, dir: Option<&Path>) -> Self {
+        let default = match toml::Table::try_from(Self::default()) {
+            Ok(t) => t,
             Err(e) => {
-                log::error!("error parsing config {e:?}, using default");
-                Self::default()
+                log::error!("couldn't serialize default config {e:?}, using default");
+                return Self::default();
             }
+        };
+        let mut validator = match Validator::new("config.toml", source, default) {
+            Ok(v) => v,
+            Err(d) => {
+                log::error!("error parsing config: {d}, using default");
+                return Self::default();
+            }
+        };
+        if let Some(dir) = dir {
+            validator.include_files(dir);
+        }
+        validator.check_list("hotkeys", check_hotkey);
+
+        let (config, diagnostics) = validator.deserialize();
+        for d in &diagnostics {
+            log::error!("invalid config: {d}");
         }
+        config.unwrap_or_else(|| {
+            log::error!("couldn't fix config, using default");
+            Self::default()
+        })
+    }
+}
+
+/// Checks that a hotkey's key and modifiers are known and that its tag exists, returning the offending field and what is wrong with it.
+pub(crate) fn check_hotkey(hotkey: &toml::Value) -> Option<(String, String)> {
+    let tag = hotkey
+        .get("action")
+        .and_then(toml::Value::as_table)
+        .and_then(|action| {
+            ["SwitchTag", "MoveWindow", "MoveWindowAndFollow"]
+                .iter()
+                .find_map(|k| action.get(*k))
+        });
+    if let Some(tag) = tag.and_then(toml::Value::as_integer)
+        && !(1..=9).contains(&tag)
+    {
+        return Some(("action".to_owned(), format!("invalid tag {tag}")));
+    }
+    if let Some(key) = hotkey.get("key").and_then(toml::Value::as_str)
+        && parse_keysym(key).is_none()
+    {
+        return Some(("key".to_owned(), format!("unknown keysym `{key}`")));
     }
+    let modifiers = hotkey.get("modifiers").and_then(toml::Value::as_str)?;
+    modifiers
+        .split('|')
+        .find(|m| !m.is_empty() && parse_modifier(m).is_none())
+        .map(|m| ("modifiers".to_owned(), format!("unknown modifier `{m}`")))
 }
 
 impl Default for ConfigDeserialized {
     /// Creates a new default Config if there was a problem with the specified path or config file
     fn default() -> Self {
-        log::info!("using default config");
-        let mut hotkeys = vec![
-            // terminal
-            HotkeyConfig {
-                modifiers: "CONTROL|MOD".to_string(),
-                key: "XK_Return".to_string(),
-                action: HotkeyAction::Spawn("alacritty".to_string()),
-            },
-            // browser
-            HotkeyConfig {
-                modifiers: "CONTROL|MOD".to_string(),
-                key: "l".to_string(),
-                action: HotkeyAction::Spawn("librewolf".to_string()),
-            },
-            // quit window
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "q".to_string(),
-                action: HotkeyAction::ExitFocusedWindow,
-            },
-            // shutdown
-            HotkeyConfig {
-                modifiers: "CONTROL|MOD".to_string(),
-                key: "q".to_string(),
-                action: HotkeyAction::Spawn("killall hematite".to_string()),
-            },
-            // app starter
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "c".to_string(),
-                action: HotkeyAction::Spawn("rofi -show drun".to_string()),
-            },
-            // screenshot
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "u".to_string(),
-                action: HotkeyAction::Spawn(
-                    "maim --select | xclip -selection clipboard -t image/png".to_string(),
-                ),
-            },
-            // change ratio
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "h".to_string(),
-                action: HotkeyAction::ChangeRatio(0.05),
-            },
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "j".to_string(),
-                action: HotkeyAction::ChangeRatio(-0.05),
-            },
-            // change focus
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "k".to_string(),
-                action: HotkeyAction::NextFocus(1),
-            },
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "l".to_string(),
-                action: HotkeyAction::NextFocus(-1),
-            },
-            // change tag
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "XK_Left".to_string(),
-                action: HotkeyAction::NextTag(-1),
-            },
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "XK_Right".to_string(),
-                action: HotkeyAction::NextTag(1),
-            },
-            // swap master
-            HotkeyConfig {
-                modifiers: "MOD".to_string(),
-                key: "XK_Return".to_string(),
-                action: HotkeyAction::SwapMaster,
-            },
-            //media
-            HotkeyConfig {
-                modifiers: String::new(),
-                key: "XF86_AudioRaiseVolume".to_string(),
-                action: HotkeyAction::Spawn("/usr/bin/pactl set-sink-volume 0 +5%".to_string()),
-            },
-            HotkeyConfig {
-                modifiers: String::new(),
-                key: "XF86_AudioLowerVolume".to_string(),
-                action: HotkeyAction::Spawn("/usr/bin/pactl set-sink-volume 0 -5%".to_string()),
-            },
-            HotkeyConfig {
-                modifiers: String::new(),
-                key: "XF86_AudioMute".to_string(),
-                action: HotkeyAction::Spawn("/usr/bin/pactl set-sink-mute 0 toggle".to_string()),
-            },
-            HotkeyConfig {
-                modifiers: String::new(),
-                key: "XF86_MonBrightnessUp".to_string(),
-                action: HotkeyAction::Spawn("light -A 5".to_string()),
-            },
-            HotkeyConfig {
-                modifiers: String::new(),
-                key: "XF86_MonBrightnessDown".to_string(),
-                action: HotkeyAction::Spawn("light -U 5".to_string()),
-            },
-        ];
-        hotkeys.extend(
-            // switch to tag
-            (1..=9)
-                .map(|x| HotkeyConfig {
-                    modifiers: "MOD".to_string(),
-                    key: x.to_string(),
-                    action: HotkeyAction::SwitchTag(x),
-                })
-                // move window to tag
-                .chain((1..=9).map(|x| HotkeyConfig {
-                    modifiers: "MOD|SHIFT".to_string(),
-                    key: x.to_string(),
-                    action: HotkeyAction::MoveWindow(x),
-                })),
-        );
-
         Self {
-            sizing: Sizing {
-                spacing: SPACING,
-                ratio: RATIO,
-                border_size: BORDER_SIZE,
-            },
-            colors: Colors {
-                main_color: String::from("#11111b"),
-                secondary_color: String::from("#74c7ec"),
-            },
-            font: Font {
-                path: FONT.to_owned(),
-                size: FONT_SIZE,
-            },
-            hotkeys,
+            terminal: default_terminal(),
+            assign: Vec::new(),
+            opacity: Vec::new(),
+            blocks: Vec::new(),
+            include: Vec::new(),
+            sizing: Sizing::default(),
+            colors: Colors::default(),
+            font: Font::default(),
+            themes: HashMap::new(),
+            monitor: HashMap::new(),
+            hotkeys: default_hotkeys(),
+            bar: Bar::default(),
+            focus: Focus::default(),
+            decorations: Decorations::default(),
+            compositor: Compositor::default(),
+            hooks: Hooks::default(),
+            parking: Parking::default(),
+            session: Session::default(),
+            idle: Idle::default(),
+            battery: Battery::default(),
+            pip: Pip::default(),
+            osd: Osd::default(),
+            autostart: Autostart::default(),
         }
     }
 }
+
+/// Gets the default hotkeys.
+fn default_hotkeys() -> Vec<HotkeyConfig> {
+    let mut hotkeys = vec![
+        // terminal
+        HotkeyConfig {
+            modifiers: "CONTROL|MOD".to_string(),
+            key: "XK_Return".to_string(),
+            action: HotkeyAction::SpawnInTerminal(String::new()),
+        },
+        // browser
+        HotkeyConfig {
+            modifiers: "CONTROL|MOD".to_string(),
+            key: "l".to_string(),
+            action: HotkeyAction::Spawn("librewolf".to_string()),
+        },
+        // quit window
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "q".to_string(),
+            action: HotkeyAction::ExitFocusedWindow,
+        },
+        // shutdown
+        HotkeyConfig {
+            modifiers: "CONTROL|MOD".to_string(),
+            key: "q".to_string(),
+            action: HotkeyAction::Spawn("killall hematite".to_string()),
+        },
+        // app starter
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "c".to_string(),
+            action: HotkeyAction::Spawn("rofi -show drun".to_string()),
+        },
+        // screenshot
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "u".to_string(),
+            action: HotkeyAction::Spawn(
+                "maim --select | xclip -selection clipboard -t image/png".to_string(),
+            ),
+        },
+        // change ratio
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "h".to_string(),
+            action: HotkeyAction::ChangeRatio(0.05),
+        },
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "j".to_string(),
+            action: HotkeyAction::ChangeRatio(-0.05),
+        },
+        // change focus
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "k".to_string(),
+            action: HotkeyAction::NextFocus(1),
+        },
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "l".to_string(),
+            action: HotkeyAction::NextFocus(-1),
+        },
+        // change tag
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "XK_Left".to_string(),
+            action: HotkeyAction::NextTag(-1),
+        },
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "XK_Right".to_string(),
+            action: HotkeyAction::NextTag(1),
+        },
+        // swap master
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "XK_Return".to_string(),
+            action: HotkeyAction::SwapMaster,
+        },
+        // show desktop
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "d".to_string(),
+            action: HotkeyAction::ToggleShowDesktop,
+        },
+        // maximize
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "m".to_string(),
+            action: HotkeyAction::ToggleMaximize,
+        },
+        // always on top
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "t".to_string(),
+            action: HotkeyAction::ToggleAlwaysOnTop,
+        },
+        // gaps
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "=".to_string(),
+            action: HotkeyAction::AdjustGaps(5),
+        },
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "-".to_string(),
+            action: HotkeyAction::AdjustGaps(-5),
+        },
+        // opacity
+        HotkeyConfig {
+            modifiers: "CONTROL|MOD".to_string(),
+            key: "=".to_string(),
+            action: HotkeyAction::AdjustOpacity(0.1),
+        },
+        HotkeyConfig {
+            modifiers: "CONTROL|MOD".to_string(),
+            key: "-".to_string(),
+            action: HotkeyAction::AdjustOpacity(-0.1),
+        },
+        // bar
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "b".to_string(),
+            action: HotkeyAction::ToggleBar,
+        },
+        // do not disturb
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "n".to_string(),
+            action: HotkeyAction::ToggleDnd,
+        },
+        // launcher
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "p".to_string(),
+            action: HotkeyAction::Launcher,
+        },
+        // window finder
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "g".to_string(),
+            action: HotkeyAction::FindWindow,
+        },
+        // tabbed layout
+        HotkeyConfig {
+            modifiers: "MOD".to_string(),
+            key: "w".to_string(),
+            action: HotkeyAction::ToggleTabbed,
+        },
+        //media
+        HotkeyConfig {
+            modifiers: String::new(),
+            key: "XF86_AudioRaiseVolume".to_string(),
+            action: HotkeyAction::Volume("/usr/bin/pactl set-sink-volume 0 +5%".to_string()),
+        },
+        HotkeyConfig {
+            modifiers: String::new(),
+            key: "XF86_AudioLowerVolume".to_string(),
+            action: HotkeyAction::Volume("/usr/bin/pactl set-sink-volume 0 -5%".to_string()),
+        },
+        HotkeyConfig {
+            modifiers: String::new(),
+            key: "XF86_AudioMute".to_string(),
+            action: HotkeyAction::Volume("/usr/bin/pactl set-sink-mute 0 toggle".to_string()),
+        },
+        HotkeyConfig {
+            modifiers: String::new(),
+            key: "XF86_MonBrightnessUp".to_string(),
+            action: HotkeyAction::Brightness("light -A 5".to_string()),
+        },
+        HotkeyConfig {
+            modifiers: String::new(),
+            key: "XF86_MonBrightnessDown".to_string(),
+            action: HotkeyAction::Brightness("light -U 5".to_string()),
+        },
+    ];
+    hotkeys.extend(
+        // switch to tag
+        (1..=9)
+            .map(|x| HotkeyConfig {
+                modifiers: "MOD".to_string(),
+                key: x.to_string(),
+                action: HotkeyAction::SwitchTag(x),
+            })
+            // move window to tag
+            .chain((1..=9).map(|x| HotkeyConfig {
+                modifiers: "MOD|SHIFT".to_string(),
+                key: x.to_string(),
+                action: HotkeyAction::MoveWindow(x),
+            })),
+    );
+    hotkeys
+}
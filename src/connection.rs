@@ -1,10 +1,16 @@
 //!
 //! This module extends `x11rb`'s `Connection` trait to interact with the manager state, provide more complicated actions, and manage atoms.
-use std::process::Command;
-use std::process::exit;
+use core::time::Duration;
+use std::process::{Child, Command};
+use std::thread;
 
+use x11rb::properties::{AspectRatio, WmHints, WmSizeHints};
+use x11rb::protocol::randr::{self, ConnectionExt as _, NotifyMask};
 use x11rb::protocol::render::Color;
+use x11rb::protocol::screensaver::ConnectionExt as _;
+use x11rb::protocol::shape::{self, ConnectionExt as _, SK, SO};
 use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::protocol::xproto::MapState;
 use x11rb::protocol::xproto::Pixmap;
 use x11rb::protocol::xproto::Rectangle;
 use x11rb::protocol::xproto::StackMode;
@@ -13,13 +19,15 @@ use x11rb::{
     COPY_DEPTH_FROM_PARENT, CURRENT_TIME,
     connection::Connection,
     cursor,
-    errors::{ReplyError, ReplyOrIdError},
+    errors::ReplyError,
     protocol::{
         ErrorKind,
         xproto::{
-            AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConfigureRequestEvent,
-            ConfigureWindowAux, CreateGCAux, CreateWindowAux, EventMask, Gcontext, GrabMode,
-            ImageFormat, InputFocus, PropMode, Screen, SetMode, Window, WindowClass,
+            Atom, AtomEnum, CONFIGURE_NOTIFY_EVENT, ChangeGCAux, ChangeWindowAttributesAux,
+            ClientMessageEvent, ClipOrdering, Colormap, ColormapAlloc, ConfigWindow,
+            ConfigureNotifyEvent, ConfigureRequestEvent, ConfigureWindowAux, CreateGCAux,
+            CreateWindowAux, EventMask, Gcontext, GrabMode, GrabStatus, Gravity, ImageFormat,
+            InputFocus, PropMode, Screen, SetMode, VisualClass, Visualid, Window, WindowClass,
         },
     },
     resource_manager,
@@ -27,27 +35,42 @@ use x11rb::{
 
 use crate::atoms::Atoms;
 use crate::{
-    config::Config,
+    config::{BASE_DPI, Config},
+    error::HematiteError,
     keys::KeyHandler,
-    state::{WindowGroup, WindowState},
+    state::{BypassCompositor, Layer, SizeHints, Struts, WindowGroup, WindowState},
 };
 
-/// A shorthand for `Result<(),ReplyOrIdError`.
+/// The environment variable holding the id of a spawned command, used to find the tag it was spawned from.
+pub const SPAWN_ID_VAR: &str = "HEMATITE_SPAWN_ID";
+/// The flag of `_MOTIF_WM_HINTS` telling that the decorations field is set.
+const MOTIF_HINTS_DECORATIONS: u32 = 1 << 1;
+
+/// A shorthand for `Result<(), HematiteError>`.
 ///
-/// The `ReplyOrIdError` is the main error that is used when handling the X11 connection, so many functions return this type to be able to use the `?` syntax and bubble the error.
-pub type Res = Result<(), ReplyOrIdError>;
+/// The `HematiteError` is the main error that is used throughout the manager, so many functions return this type to be able to use the `?` syntax and bubble the error.
+pub type Res = Result<(), HematiteError>;
 /// An integer handle to an X11 resource.
 ///
 /// The resource may be a window, pixmap, colormap, graphics context, etc. It is preferred to use the resource's unique type (e.g. `GContext` for gcs) instead.
 pub type Id = u32;
 /// Contains the ids of all allocated colors.
 ///
-/// Currently only a main and secondary color is defined.
+/// Borders and the bar have their own colors, and each tag has a focused border and highlight color.
+#[derive(Clone)]
 pub struct Colors {
-    /// The main color defines the background color, predominantly used in the status bar.
-    pub main: Id,
-    /// The secondary color defines the text color used in the status bar and the border color of windows.
-    pub secondary: Id,
+    /// The border color of unfocused windows.
+    pub unfocused: Id,
+    /// The border color of windows demanding attention.
+    pub urgent: Id,
+    /// The background color of the bar and frame windows.
+    pub bar_background: Id,
+    /// The text color of the bar.
+    pub bar_foreground: Id,
+    /// The focused border color of each tag.
+    pub focused: Vec<Id>,
+    /// The highlight color of each tag, used for the active tag's square on the bar.
+    pub tags: Vec<Id>,
 }
 
 /// Defines all the ways the connection interacts with state. Usually a `WindowState` reference is passed as a shorthand for its coordinates and size.
@@ -61,6 +84,14 @@ pub trait ConnectionStateExt {
     /// # Errors
     /// Returns an error if the window does not exist.
     fn unmap(&self, window: &WindowState) -> Res;
+    /// Hides a window by unmapping its frame window, leaving the window itself mapped.
+    /// # Errors
+    /// Returns an error if the frame window does not exist.
+    fn hide(&self, window: &WindowState) -> Res;
+    /// Shows a window hidden with `hide` by mapping its frame window.
+    /// # Errors
+    /// Returns an error if the frame window does not exist.
+    fn show(&self, window: &WindowState) -> Res;
     /// Creates a frame window and reparents the window into it. Also adds `EventMask`s to the windows.
     /// # Errors
     /// Returns an error if the window does not exist.
@@ -81,9 +112,13 @@ pub trait ConnectionStateExt {
     /// # Errors
     /// Returns an error if the window does not exist or if it goes beyond the bounds of the screen.
     fn config_window_from_state(&self, window: &WindowState) -> Res;
+    /// Tells a client where its window is on the screen with a synthetic `ConfigureNotifyEvent`, as per ICCCM, such as when its frame moved or a configure request wasn't granted.
+    /// # Errors
+    /// Returns an error if the window does not exist.
+    fn send_configure_notify(&self, window: &WindowState) -> Res;
     /// Sets the window's size to be the entire screen and lets it know it's in fullscreen mode.
     ///
-    /// Fullscreen windows are in the `Floating` group to avoid having them accidentally tiled.
+    /// Fullscreen windows are in the `Floating` group to avoid having them accidentally tiled. If configured, the compositor is asked to bypass the window until it leaves fullscreen.
     /// # Errors
     /// Returns an error if the window does not exist or if the window can't be resized.
     fn set_fullscreen(&self, window: &WindowState) -> Res;
@@ -92,14 +127,19 @@ pub trait ConnectionStateExt {
     /// # Errors
     /// Returns an error if the window does not exist.
     fn remove_fullscreen(&self, window: &WindowState) -> Res;
+    /// Raises the windows in order, so that the last window ends up on top.
+    ///
+    /// # Errors
+    /// Returns an error if a window does not exist.
+    fn restack(&self, windows: &[Window]) -> Res;
     /// Creates a pixmap (basically an off screen window to draw to) from its state.
     /// # Errors
     /// Returns an error if the window does not exist.
     fn create_pixmap_from_win(&self, pixmap: Pixmap, window: &WindowState) -> Res;
-    /// Sets the currently focused window's border to be visible and gives it the input focus.
+    /// Sets the currently focused window's border to be visible with the specified color and gives it the input focus.
     /// # Errors
     /// Returns an error if the window or its frame window does not exist.
-    fn set_focus_window(&self, windows: &[WindowState], focus: &WindowState) -> Res;
+    fn set_focus_window(&self, windows: &[WindowState], focus: &WindowState, color: Id) -> Res;
     /// Copies a window or pixmap's contents into another window.
     ///
     /// Only the second window's state needs to be known in order to fill the entire window. It is assumed that both windows are the same size.
@@ -110,6 +150,20 @@ pub trait ConnectionStateExt {
     /// # Errors
     /// Returns an error if the event specifies the wrong parameters.
     fn handle_config(&self, event: ConfigureRequestEvent, window: &mut WindowState) -> Res;
+    /// Gives the frame window the shape of its client, plus its title bar, if the client set its own shape (e.g. `xeyes`). Otherwise the frame is made rectangular again.
+    ///
+    /// Does nothing if the X11 server doesn't support the shape extension.
+    /// # Errors
+    /// Returns an error if the window or its frame window does not exist.
+    fn apply_shape(&self, window: &WindowState) -> Res;
+    /// Sets the opacity (0.0-1.0) a compositor should draw the window's frame with. Opaque frames have the property removed.
+    /// # Errors
+    /// Returns an error if the window does not exist.
+    fn set_opacity(&self, window: &WindowState, opacity: f32) -> Res;
+    /// Sets the background of the window's frame to the bar background color, such as after switching themes, and redraws it.
+    /// # Errors
+    /// Returns an error if the window does not exist.
+    fn update_frame_colors(&self, window: &WindowState) -> Res;
 }
 
 /// Defines the more abstract directions you can give to the X11 server, like drawing to a pixmap or killing the focused window.
@@ -119,23 +173,57 @@ pub trait ConnectionActionExt {
     /// Returns 1 if the root window has the input focus.
     /// # Errors
     /// Returns an error if no window focus is assigned.
-    fn get_focus(&self) -> Result<u32, ReplyOrIdError>;
+    fn get_focus(&self) -> Result<u32, HematiteError>;
     /// Gives the input focus to the root window.
     /// # Errors
     /// Returns an error if the root window does not exist.
     fn set_focus_to_root(&self) -> Res;
-    /// Kills the window which has the input focus.
+    /// Asks the window which has the input focus to close itself using the `WM_DELETE_WINDOW` protocol.
     /// # Errors
     /// Returns an error if no focus window exists.
     fn kill_focus(&self, focus: Id) -> Res;
+    /// Sends a `_NET_WM_PING` message to the window, which responsive clients send back to the root window.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn ping(&self, window: Window) -> Res;
+    /// Forcefully closes the connection of the client owning the window, destroying all of its windows.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn kill_client(&self, window: Window) -> Res;
     /// Gets the UTF-8 name of the window (if it exists).
     /// # Errors
     /// Returns an error if the window doesn't exist.
-    fn get_window_name(&self, window: Window) -> Result<String, ReplyOrIdError>;
-    /// Creates a graphics context with a background and foreground color.
+    fn get_window_name(&self, window: Window) -> Result<String, HematiteError>;
+    /// Gets the size (width, height) of a window.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_window_size(&self, window: Window) -> Result<(u16, u16), HematiteError>;
+    /// Whether the window should be left alone instead of being managed: override-redirect windows (e.g. menus and tooltips) and input-only windows.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn is_unmanaged(&self, window: Window) -> Result<bool, HematiteError>;
+    /// Maps a window that isn't managed, without a frame.
     /// # Errors
-    /// Returns an error if the colors dont exist.
-    fn create_gc(&self, gc: Id, color_background: Id, color_foreground: Id) -> Res;
+    /// Returns an error if the window doesn't exist.
+    fn map_unmanaged(&self, window: Window) -> Res;
+    /// Configures a window that isn't managed exactly as a `ConfigureRequestEvent` asks, such as a client sizing its window before mapping it.
+    /// # Errors
+    /// Returns an error if the window doesn't exist or the event specifies the wrong parameters.
+    fn configure_unmanaged(&self, event: ConfigureRequestEvent) -> Res;
+    /// Gets the mapped top-level windows that aren't override-redirect, such as the clients of a previous window manager.
+    /// # Errors
+    /// Returns an error if the connection is faulty.
+    fn get_top_level_windows(&self) -> Result<Vec<Window>, HematiteError>;
+    /// Creates a graphics context with a background and foreground color, for drawing to drawables of the same depth as the specified one.
+    /// # Errors
+    /// Returns an error if the colors or drawable dont exist.
+    fn create_gc(&self, gc: Id, drawable: Id, color_background: Id, color_foreground: Id) -> Res;
+    /// Whether windows are created with a 32-bit ARGB visual, allowing a compositor to draw them translucent.
+    fn has_argb_visual(&self) -> bool;
+    /// Listens to pointer button presses and the pointer leaving the bar, on top of the events selected when it was added.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn select_bar_events(&self, window: Window) -> Res;
     /// Draws to a pixmap (offscreen window).
     ///
     /// The graphics context does not provide any information and is used as a dummy.
@@ -162,21 +250,83 @@ pub trait ConnectionActionExt {
     /// Generates a unique id that can be used to identify any X11 resource.
     /// # Errors
     /// Returns an error if no ids are available.
-    fn generate_id(&self) -> Result<u32, ReplyOrIdError>;
+    fn generate_id(&self) -> Result<u32, HematiteError>;
     /// Grabs keys defined in configuration so that the event handler can later detect when they are pressed.
     /// # Errors
     /// Returns an error if the hotkeys are incorrect.
     fn grab_keys(&self, handler: &KeyHandler) -> Res;
+    /// Grabs the pointer so that its motion and button releases are reported to the manager.
+    ///
+    /// Returns false if the pointer couldn't be grabbed (e.g. another client has grabbed it).
+    /// # Errors
+    /// Returns an error if the connection is faulty.
+    fn grab_pointer(&self) -> Result<bool, HematiteError>;
+    /// Releases a pointer grab.
+    /// # Errors
+    /// Returns an error if the connection is faulty.
+    fn ungrab_pointer(&self) -> Res;
+    /// Grabs the keyboard so that every key press is reported to the manager, e.g. while a menu is open.
+    ///
+    /// Returns false if the keyboard couldn't be grabbed (e.g. another client has grabbed it).
+    /// # Errors
+    /// Returns an error if the connection is faulty.
+    fn grab_keyboard(&self) -> Result<bool, HematiteError>;
+    /// Releases a keyboard grab.
+    /// # Errors
+    /// Returns an error if the connection is faulty.
+    fn ungrab_keyboard(&self) -> Res;
     /// Gets the current screen's width and height in pixels.
     fn get_screen_geometry(&self) -> (u16, u16);
     /// Gets the root window's id.
     fn get_root(&self) -> u32;
+    /// Gets the ids of the managed colors.
+    fn colors(&self) -> &Colors;
+    /// Allocates the colors of the config, such as after switching themes, and uses them from now on.
+    /// # Errors
+    /// Returns an error if a color couldn't be allocated.
+    fn set_colors(&mut self, config: &Config) -> Res;
+    /// Changes the background and foreground colors of a graphics context.
+    /// # Errors
+    /// Returns an error if the graphics context or colors don't exist.
+    fn change_gc(&self, gc: Gcontext, color_background: Id, color_foreground: Id) -> Res;
     /// Adds a "heartbeat" window.
     ///
     /// Heartbeat windows act as a check that an EWMH compliant window manager is running. They do not have to be mapped and only exist to verify EWMH compliance.
     /// # Errors
     /// Returns an error if the heartbeat window couldn't be created.
     fn add_heartbeat_window(&self) -> Res;
+    /// Creates an invisible window from its state that reports the pointer entering it, used to reveal the bar when the pointer touches the edge of the screen.
+    ///
+    /// The window and its frame window are expected to be the same. It is left unmapped.
+    /// # Errors
+    /// Returns an error if the window couldn't be created.
+    fn create_edge_window(&self, window: &WindowState) -> Res;
+    /// Creates a popup window from its state, which is shown over other windows without being managed.
+    ///
+    /// The window and its frame window are expected to be the same. It is left unmapped.
+    /// # Errors
+    /// Returns an error if the window couldn't be created.
+    fn create_popup_window(&self, window: &WindowState) -> Res;
+    /// Moves and resizes a popup window to its state, then raises and maps it.
+    /// # Errors
+    /// Returns an error if the window does not exist.
+    fn show_popup(&self, window: &WindowState) -> Res;
+    /// Takes a snapshot of a window's contents, returning its width, height and BGRA data.
+    ///
+    /// Only the contents of shown windows can be read, and the parts covered by other windows may hold the contents of those windows instead.
+    /// # Errors
+    /// Returns an error if the window does not exist, isn't shown, or doesn't use 32 bits per pixel.
+    fn get_window_image(&self, window: Window) -> Result<(u16, u16, Vec<u8>), HematiteError>;
+    /// Gets the time since the last keyboard or pointer input.
+    /// # Errors
+    /// Returns an error if the screen saver extension isn't available.
+    fn get_idle_time(&self) -> Result<Duration, HematiteError>;
+    /// Gets the names of the connected and enabled outputs (e.g. `DP-1`), with the primary output first.
+    ///
+    /// Returns no outputs if the X11 server doesn't support the `RandR` extension.
+    /// # Errors
+    /// May return an error if the connection is faulty.
+    fn get_outputs(&self) -> Result<Vec<String>, HematiteError>;
     /// Draws a rectangle to a pixmap.
     ///
     /// The specified graphics context determines its color.
@@ -187,54 +337,128 @@ pub trait ConnectionActionExt {
 
 /// Defines the methods used to change specific atoms and their data.
 pub trait ConnectionAtomExt {
+    /// Gets the ids of the atoms used by the manager.
+    fn atoms(&self) -> &Atoms;
+    /// Gets the name of an atom.
+    /// # Errors
+    /// Returns an error if there is no atom with that id.
+    fn get_atom_name(&self, atom: Atom) -> Result<String, HematiteError>;
     /// Tells the window the actions it's allowed to perform.
     ///
-    /// Currently only the fullscreen action is supported.
+    /// Currently only the fullscreen, maximize, above and below actions are supported.
     /// # Errors
     /// Returns an error if the window doesn't exist.
     fn net_add_allowed_actions(&self, window: Window) -> Res;
     /// Tells the window the size of its surrounding border.
     /// # Errors
     /// Returns an error if the window doesn't exist.
-    fn net_add_frame_extents(&self, window: Window) -> Res;
+    fn net_add_frame_extents(&self, window: Window, border: u32) -> Res;
     /// Tells the window it is active and displayed.
     /// # Errors
     /// Returns an error if the window doesn't exist.
     fn wm_activate_window(&self, window: Window) -> Res;
+    /// Tells the window it is withdrawn, so it is free to be mapped again or destroyed.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn wm_withdraw_window(&self, window: Window) -> Res;
     /// Tells the window that it has the input focus.
     /// # Errors
     /// Returns an error if the window doesn't exist.
     fn net_set_active_window(&self, window: Window) -> Res;
-    /// Tells the window that it is in fullscreen mode.
+    /// Tells the window its state (fullscreen, maximized, above, below or sticky) based on its group, layer and stickiness.
     /// # Errors
     /// Returns an error if the window doesn't exist.
-    fn net_set_state_fullscreen(&self, window: Window) -> Res;
+    fn net_update_state(&self, window: &WindowState) -> Res;
     /// Tells windows what the currently active tag is.
     /// # Errors
     /// Returns an error if properties can't be changed.
     fn net_update_active_desktop(&self, tag: u32) -> Res;
+    /// Tells windows whether the desktop is being shown.
+    /// # Errors
+    /// Returns an error if properties can't be changed.
+    fn net_update_showing_desktop(&self, showing: bool) -> Res;
+    /// Tells windows the area (x, y, width, height) they can be placed in, for every tag.
+    /// # Errors
+    /// Returns an error if properties can't be changed.
+    fn net_update_workarea(&self, area: (i16, i16, u16, u16)) -> Res;
     /// Tells the window what tag it's in.
     /// # Errors
     /// Returns an error if the window doesn't exist.
     fn net_update_window_desktop(&self, window: Window, tag: u32) -> Res;
-    /// Updates a list of which windows are managed.
+    /// Updates a list of which windows are managed, in the order they were mapped.
     /// # Errors
     /// Returns an error if the windows are incorrect.
     fn net_update_client_list(&self, windows: &[Window]) -> Res;
+    /// Updates a list of which windows are managed, from bottom to top of the stacking order.
+    /// # Errors
+    /// Returns an error if the windows are incorrect.
+    fn net_update_client_list_stacking(&self, windows: &[Window]) -> Res;
     /// Gets the icon data of the window.
     /// # Errors
     /// Returns an error if the window doesn't exist.
-    fn get_icon(&self, window: Window) -> Result<Vec<u8>, ReplyOrIdError>;
+    fn get_icon(&self, window: Window) -> Result<Vec<u8>, HematiteError>;
     /// Gets the window hints and determines if the specified window wants to be floating or not.
     ///
     /// Floating logic is determined by checking the min and max widths and heights. If they are the same, then the window is floating and receives its requested width and height in the middle of the screen.
     /// # Errors
     /// Returns an error if the window doesn't exist.
-    fn should_be_floating(&self, window: Window) -> Result<(u16, u16, bool), ReplyOrIdError>;
+    fn should_be_floating(&self, window: Window) -> Result<(u16, u16, bool), HematiteError>;
+    /// Gets the size hints (`WM_NORMAL_HINTS`) of the window.
+    ///
+    /// Returns empty hints if the window has none.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_size_hints(&self, window: Window) -> Result<SizeHints, HematiteError>;
+    /// Gets the group leader of the window from `WM_HINTS`.
+    ///
+    /// Returns `None` if the window isn't part of a group.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_group_leader(&self, window: Window) -> Result<Option<Window>, HematiteError>;
+    /// Whether the window wants decorations, read from `_MOTIF_WM_HINTS`.
+    ///
+    /// Windows without the hint, or whose hint doesn't mention decorations, are decorated.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn wants_decorations(&self, window: Window) -> Result<bool, HematiteError>;
+    /// Gets the protocols (e.g. `WM_DELETE_WINDOW`) the window supports.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_protocols(&self, window: Window) -> Result<Vec<Atom>, HematiteError>;
     /// Sets the window class of the window.
     /// # Errors
     /// Returns an error if the window doesn't exist.
     fn set_class(&self, class:&str, window: Window) -> Res;
+    /// Gets the class of the window (the second string of `WM_CLASS`, e.g. `firefox`).
+    ///
+    /// Returns an empty string if the window has no class.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_window_class(&self, window: Window) -> Result<String, HematiteError>;
+    /// Gets the role (`WM_WINDOW_ROLE`) of the window.
+    ///
+    /// Returns an empty string if the window has no role.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_window_role(&self, window: Window) -> Result<String, HematiteError>;
+    /// Gets the process id (`_NET_WM_PID`) of the client owning the window.
+    ///
+    /// Returns `None` if the window has no process id.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_window_pid(&self, window: Window) -> Result<Option<u32>, HematiteError>;
+    /// Gets the X server time of the last user interaction with the window (`_NET_WM_USER_TIME`). A time of 0 means the window doesn't want to be focused when it opens.
+    ///
+    /// Returns `None` if the window has no user time.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_user_time(&self, window: Window) -> Result<Option<u32>, HematiteError>;
+    /// Gets the space a dock window (`_NET_WM_WINDOW_TYPE_DOCK`) reserves at the edges of the screen.
+    ///
+    /// Returns `None` if the window isn't a dock. Docks that don't reserve space reserve nothing.
+    /// # Errors
+    /// Returns an error if the window doesn't exist.
+    fn get_dock_struts(&self, window: Window) -> Result<Option<Struts>, HematiteError>;
 }
 
 /// An implementation of the Connection traits, with additional information like config, screen and atom list.
@@ -246,20 +470,34 @@ pub struct ConnectionHandler<'a, C: Connection> {
     /// The screen's id.
     screen_num: usize,
     /// A helper to manage atoms.
-    pub atoms: Atoms<'a, C>,
+    pub atoms: Atoms,
     /// A config for additional information.
     config: Config,
     /// All the ids of the managed colors.
     pub colors: Colors,
+    /// The depth of the bar and frame windows.
+    depth: u8,
+    /// The visual of the bar and frame windows.
+    visual: Visualid,
+    /// The colormap of the bar and frame windows.
+    colormap: Colormap,
+    /// Whether the X11 server supports the shape extension.
+    shape: bool,
+    /// Whether the X11 server supports version 1.3 of the `RandR` extension.
+    randr: bool,
+    /// The heartbeat window, which also receives the ticks waking the event loop.
+    pub heartbeat: Window,
 }
 
 impl<'a, C: Connection> ConnectionHandler<'a, C> {
     /// Creates a new handler.
     ///
     /// Allocates the specified colors, grabs the specified keys, sets the default cursor and adds a heartbeat window.
+    ///
+    /// A 32-bit ARGB visual is used for the bar and frame windows if the screen has one, otherwise colors are opaque.
     /// # Errors
     /// May return an error if the connection is faulty.
-    pub fn new(conn: &'a C, screen_num: usize, config: &Config) -> Result<Self, ReplyOrIdError> {
+    pub fn new(conn: &'a C, screen_num: usize, config: &Config) -> Result<Self, HematiteError> {
         let screen = &conn.setup().roots[screen_num];
         become_window_manager(conn, screen.root)?;
 
@@ -267,8 +505,29 @@ impl<'a, C: Connection> ConnectionHandler<'a, C> {
 
         let atoms = Atoms::new(conn, screen)?;
 
-        let main_color = get_color_id(conn, screen, config.main_color)?;
-        let secondary_color = get_color_id(conn, screen, config.secondary_color)?;
+        let (depth, visual, colormap) = if let Some(visual) = find_argb_visual(screen) {
+            log::info!("using argb visual {visual}");
+            let colormap = conn.generate_id()?;
+            conn.create_colormap(ColormapAlloc::NONE, colormap, screen.root, visual)?;
+            (32, visual, colormap)
+        } else {
+            log::info!("no argb visual found, colors will be opaque");
+            (
+                screen.root_depth,
+                screen.root_visual,
+                screen.default_colormap,
+            )
+        };
+        let argb = depth == 32;
+        let shape = conn
+            .extension_information(shape::X11_EXTENSION_NAME)?
+            .is_some();
+        let randr = has_randr(conn)?;
+        if randr {
+            conn.randr_select_input(screen.root, NotifyMask::SCREEN_CHANGE)?;
+        }
+
+        let colors = allocate_colors(conn, screen, config, argb)?;
 
         let handler = ConnectionHandler {
             conn,
@@ -276,10 +535,13 @@ impl<'a, C: Connection> ConnectionHandler<'a, C> {
             screen_num,
             atoms,
             config: config.clone(),
-            colors: Colors {
-                main: main_color,
-                secondary: secondary_color,
-            },
+            colors,
+            depth,
+            visual,
+            colormap,
+            shape,
+            randr,
+            heartbeat: conn.generate_id()?,
         };
 
         handler.grab_keys(&KeyHandler::new(conn, config)?)?;
@@ -287,6 +549,25 @@ impl<'a, C: Connection> ConnectionHandler<'a, C> {
         handler.add_heartbeat_window()?;
         Ok(handler)
     }
+
+    /// Gets the geometry (x, y, width, height) a client is given inside its frame, along with the height of the title bar above it.
+    fn get_client_geometry(&self, window: &WindowState) -> ((i16, i16, u16, u16), u16) {
+        let title = window.title_height(self.config.title_bars, self.config.title_height);
+        let border = self.get_border_size(window) as u16;
+        (
+            window.client_geometry(self.config.increments, border, title),
+            title,
+        )
+    }
+
+    /// Gets the border size of a window, which is zero for windows that asked for no decorations.
+    const fn get_border_size(&self, window: &WindowState) -> u32 {
+        if window.decorated {
+            self.config.border_size
+        } else {
+            0
+        }
+    }
 }
 
 impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
@@ -304,6 +585,18 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
         Ok(())
     }
 
+    fn hide(&self, window: &WindowState) -> Res {
+        log::trace!("hiding {}", window.window);
+        self.conn.unmap_window(window.frame_window)?;
+        Ok(())
+    }
+
+    fn show(&self, window: &WindowState) -> Res {
+        log::trace!("showing {}", window.window);
+        self.conn.map_window(window.frame_window)?;
+        Ok(())
+    }
+
     fn handle_config(&self, event: ConfigureRequestEvent, window: &mut WindowState) -> Res {
         log::trace!(
             "EVENT CONFIG w {} x {} y {} w {} self.bar.height {}",
@@ -313,14 +606,38 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
             event.width,
             event.height
         );
-        let aux = ConfigureWindowAux::from_configure_request(&event);
+        // the client is the only child of its frame, so stacking is done on the frame instead
+        let aux = ConfigureWindowAux {
+            sibling: None,
+            stack_mode: None,
+            ..ConfigureWindowAux::from_configure_request(&event)
+        };
         self.conn.configure_window(event.window, &aux)?;
 
+        // fields missing from the value mask hold the client's geometry inside its frame, not a request
         if window.group == WindowGroup::Floating {
-            window.x = event.x;
-            window.y = event.y;
-            window.width = event.width;
-            window.height = event.height;
+            let mask = event.value_mask;
+            if mask.contains(ConfigWindow::X) {
+                window.x = event.x;
+            }
+            if mask.contains(ConfigWindow::Y) {
+                window.y = event.y;
+            }
+            if mask.contains(ConfigWindow::WIDTH) || mask.contains(ConfigWindow::HEIGHT) {
+                let title = window.title_height(self.config.title_bars, self.config.title_height);
+                let width = if mask.contains(ConfigWindow::WIDTH) {
+                    event.width
+                } else {
+                    window.width
+                };
+                let height = if mask.contains(ConfigWindow::HEIGHT) {
+                    event.height
+                } else {
+                    window.height.saturating_sub(title)
+                };
+                (window.width, window.height) = window.hints.fit_aspect(width, height);
+                window.height += title;
+            }
         }
 
         self.config_window_from_state(window)?;
@@ -331,7 +648,7 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
     fn add_window(&self, window: &WindowState) -> Res {
         log::trace!("creating frame of {}", window.window);
         self.conn.create_window(
-            COPY_DEPTH_FROM_PARENT,
+            self.depth,
             window.frame_window,
             self.screen.root,
             window.x,
@@ -340,38 +657,127 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
             window.height,
             0,
             WindowClass::INPUT_OUTPUT,
-            0,
+            self.visual,
             &CreateWindowAux::new()
+                .colormap(self.colormap)
                 .event_mask(
                     EventMask::KEY_PRESS
                         | EventMask::SUBSTRUCTURE_NOTIFY
+                        | EventMask::SUBSTRUCTURE_REDIRECT
                         | EventMask::ENTER_WINDOW
-                        | EventMask::PROPERTY_CHANGE,
+                        | EventMask::PROPERTY_CHANGE
+                        | EventMask::BUTTON_PRESS
+                        | EventMask::EXPOSURE,
                 )
-                .background_pixel(self.colors.main)
-                .border_pixel(self.colors.secondary),
+                .background_pixel(self.colors.bar_background)
+                .border_pixel(self.colors.unfocused),
         )?;
 
+        // clients resizing their window inside its frame are redirected, so the frame can follow
         self.conn.change_window_attributes(
             window.window,
             &ChangeWindowAttributesAux::new().event_mask(
                 EventMask::KEY_PRESS
                     | EventMask::SUBSTRUCTURE_NOTIFY
                     | EventMask::ENTER_WINDOW
-                    | EventMask::PROPERTY_CHANGE,
+                    | EventMask::PROPERTY_CHANGE
+                    | EventMask::RESIZE_REDIRECT,
             ),
         )?;
+        if self.shape {
+            self.conn.shape_select_input(window.window, true)?;
+        }
 
         self.net_add_allowed_actions(window.window)?;
-        self.net_add_frame_extents(window.window)?;
+        self.net_add_frame_extents(window.window, self.get_border_size(window))?;
         self.wm_activate_window(window.window)?;
 
         self.conn.grab_server()?;
         self.conn.change_save_set(SetMode::INSERT, window.window)?;
         self.conn
             .reparent_window(window.window, window.frame_window, 0, 0)?;
+        self.apply_shape(window)?;
         self.map(window)?;
+        // clear the frame so nothing is left of what was on the screen below it before the client draws
+        self.conn
+            .clear_area(false, window.frame_window, 0, 0, 0, 0)?;
         self.conn.ungrab_server()?;
+        // tell the client where it ended up, as it may draw before it's configured
+        self.send_configure_notify(window)
+    }
+
+    fn apply_shape(&self, window: &WindowState) -> Res {
+        if !self.shape {
+            return Ok(());
+        }
+        if !self
+            .conn
+            .shape_query_extents(window.window)?
+            .reply()?
+            .bounding_shaped
+        {
+            self.conn.shape_mask(
+                SO::SET,
+                SK::BOUNDING,
+                window.frame_window,
+                0,
+                0,
+                x11rb::NONE,
+            )?;
+            return Ok(());
+        }
+
+        log::trace!("shaping frame of {}", window.window);
+        let title = window.title_height(self.config.title_bars, self.config.title_height);
+        self.conn.shape_combine(
+            SO::SET,
+            SK::BOUNDING,
+            SK::BOUNDING,
+            window.frame_window,
+            0,
+            title as i16,
+            window.window,
+        )?;
+        if title > 0 {
+            self.conn.shape_rectangles(
+                SO::UNION,
+                SK::BOUNDING,
+                ClipOrdering::UNSORTED,
+                window.frame_window,
+                0,
+                0,
+                &[Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: window.width,
+                    height: title,
+                }],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn set_opacity(&self, window: &WindowState, opacity: f32) -> Res {
+        if opacity >= 1.0 {
+            self.conn
+                .delete_property(window.frame_window, self.atoms.net_wm_window_opacity)?;
+            return Ok(());
+        }
+        self.atoms.change_cardinal_prop(
+            self.conn,
+            window.frame_window,
+            self.atoms.net_wm_window_opacity,
+            &[(opacity.clamp(0.0, 1.0) * u32::MAX as f32) as u32],
+        )
+    }
+
+    fn update_frame_colors(&self, window: &WindowState) -> Res {
+        self.conn.change_window_attributes(
+            window.frame_window,
+            &ChangeWindowAttributesAux::new().background_pixel(self.colors.bar_background),
+        )?;
+        self.conn
+            .clear_area(true, window.frame_window, 0, 0, 0, 0)?;
         Ok(())
     }
 
@@ -385,30 +791,40 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
         Ok(())
     }
 
-    fn set_focus_window(&self, windows: &[WindowState], window: &WindowState) -> Res {
+    fn set_focus_window(&self, windows: &[WindowState], window: &WindowState, color: Id) -> Res {
         log::trace!("setting focus to: {:?}", window.window);
         self.conn
             .set_input_focus(InputFocus::PARENT, window.window, CURRENT_TIME)?;
 
         //set borders
+        let single_tiled = windows.iter().filter(|w| w.is_tiled()).count() == 1;
         windows.iter().try_for_each(|w| {
             if w.group == WindowGroup::Fullscreen {
                 return Ok(());
             }
+            let border_size = if self.config.smart_borders && single_tiled && w.is_tiled() {
+                0
+            } else {
+                self.get_border_size(w)
+            };
             self.conn.configure_window(
                 w.frame_window,
-                &ConfigureWindowAux::new().border_width(self.config.border_size),
+                &ConfigureWindowAux::new().border_width(border_size),
             )?;
             self.conn.change_window_attributes(
                 w.frame_window,
-                &ChangeWindowAttributesAux::new().border_pixel(self.colors.main),
+                &ChangeWindowAttributesAux::new().border_pixel(if w.urgent {
+                    self.colors.urgent
+                } else {
+                    self.colors.unfocused
+                }),
             )?;
-            Ok::<(), ReplyOrIdError>(())
+            Ok::<(), HematiteError>(())
         })?;
 
         self.conn.change_window_attributes(
             window.frame_window,
-            &ChangeWindowAttributesAux::new().border_pixel(self.colors.secondary),
+            &ChangeWindowAttributesAux::new().border_pixel(color),
         )?;
 
         self.net_set_active_window(window.window)?;
@@ -418,6 +834,7 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
 
     fn config_window_from_state(&self, window: &WindowState) -> Res {
         log::trace!("configuring window {} from state", window.window);
+        let ((_, _, width, height), title) = self.get_client_geometry(window);
         self.conn
             .configure_window(
                 window.frame_window,
@@ -437,34 +854,62 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
                 window.window,
                 &ConfigureWindowAux {
                     x: Some(0),
-                    y: Some(0),
-                    width: Some(u32::from(window.width)),
-                    height: Some(u32::from(window.height)),
+                    y: Some(i32::from(title)),
+                    width: Some(u32::from(width)),
+                    height: Some(u32::from(height)),
                     border_width: None,
                     sibling: None,
                     stack_mode: None,
                 },
             )?
             .check()?;
+        self.send_configure_notify(window)
+    }
 
+    fn send_configure_notify(&self, window: &WindowState) -> Res {
+        let ((x, y, width, height), _) = self.get_client_geometry(window);
+        self.conn.send_event(
+            false,
+            window.window,
+            EventMask::STRUCTURE_NOTIFY,
+            ConfigureNotifyEvent {
+                response_type: CONFIGURE_NOTIFY_EVENT,
+                sequence: 0,
+                event: window.window,
+                window: window.window,
+                above_sibling: x11rb::NONE,
+                x,
+                y,
+                width,
+                height,
+                border_width: 0,
+                override_redirect: false,
+            },
+        )?;
         Ok(())
     }
 
     fn set_fullscreen(&self, window: &WindowState) -> Res {
         log::trace!("setting window to fullscreen {}", window.window);
-        self.net_set_state_fullscreen(window.window)?;
+        self.net_update_state(window)?;
+        if self.config.bypass_compositor == BypassCompositor::Fullscreen {
+            self.atoms.change_cardinal_prop(
+                self.conn,
+                window.window,
+                self.atoms.net_wm_bypass_compositor,
+                &[1],
+            )?;
+        }
         self.conn.configure_window(
             window.frame_window,
-            &ConfigureWindowAux::new()
-                .border_width(0)
-                .stack_mode(StackMode::ABOVE),
+            &ConfigureWindowAux::new().border_width(0),
         )?;
         Ok(())
     }
 
     fn create_pixmap_from_win(&self, pixmap: Pixmap, window: &WindowState) -> Res {
         self.conn.create_pixmap(
-            self.screen.root_depth,
+            self.depth,
             pixmap,
             window.window,
             window.width,
@@ -475,7 +920,7 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
 
     fn create_window(&self, window: &WindowState) -> Res {
         self.conn.create_window(
-            COPY_DEPTH_FROM_PARENT,
+            self.depth,
             window.window,
             self.screen.root,
             0,
@@ -484,21 +929,29 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
             window.height,
             0,
             WindowClass::INPUT_OUTPUT,
-            0,
-            &CreateWindowAux::new(),
+            self.visual,
+            &CreateWindowAux::new()
+                .colormap(self.colormap)
+                .background_pixel(self.colors.bar_background)
+                .border_pixel(self.colors.unfocused),
         )?;
+
+        // without an argb visual, the compositor can only make the whole window translucent
+        let alpha = self.config.bar_background_color.alpha;
+        if !self.has_argb_visual() && alpha < u16::MAX {
+            self.atoms.change_cardinal_prop(
+                self.conn,
+                window.window,
+                self.atoms.net_wm_window_opacity,
+                &[u32::from(alpha) * 65537],
+            )?;
+        }
         Ok(())
     }
 
     fn clear_window(&self, window: &WindowState) -> Res {
-        self.conn.clear_area(
-            false,
-            window.window,
-            window.x,
-            window.y,
-            window.width,
-            window.height,
-        )?;
+        self.conn
+            .clear_area(false, window.window, 0, 0, window.width, window.height)?;
         Ok(())
     }
 
@@ -518,17 +971,26 @@ impl<C: Connection> ConnectionStateExt for ConnectionHandler<'_, C> {
     }
 
     fn remove_fullscreen(&self, window: &WindowState) -> Res {
-        self.atoms
-            .remove_atom_prop(window.window, self.atoms.net_wm_state)?;
+        self.net_update_state(window)?;
+        if self.config.bypass_compositor == BypassCompositor::Fullscreen {
+            self.conn
+                .delete_property(window.window, self.atoms.net_wm_bypass_compositor)?;
+        }
         self.conn.configure_window(
             window.frame_window,
-            &ConfigureWindowAux::new()
-                .stack_mode(StackMode::BELOW)
-                .border_width(self.config.border_size),
+            &ConfigureWindowAux::new().border_width(self.get_border_size(window)),
         )?;
         Ok(())
     }
 
+    fn restack(&self, windows: &[Window]) -> Res {
+        windows.iter().try_for_each(|w| {
+            self.conn
+                .configure_window(*w, &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE))?;
+            Ok(())
+        })
+    }
+
 }
 
 impl<C: Connection> ConnectionActionExt for ConnectionHandler<'_, C> {
@@ -547,7 +1009,7 @@ impl<C: Connection> ConnectionActionExt for ConnectionHandler<'_, C> {
         })?;
         Ok(())
     }
-    fn get_focus(&self) -> Result<u32, ReplyOrIdError> {
+    fn get_focus(&self) -> Result<u32, HematiteError> {
         Ok(self.conn.get_input_focus()?.reply()?.focus)
     }
     fn draw_to_pixmap(
@@ -571,7 +1033,7 @@ impl<C: Connection> ConnectionActionExt for ConnectionHandler<'_, C> {
                 x,
                 y,
                 0,
-                self.screen.root_depth,
+                self.depth,
                 data,
             )?
             .check()
@@ -597,7 +1059,7 @@ impl<C: Connection> ConnectionActionExt for ConnectionHandler<'_, C> {
     }
 
     fn add_heartbeat_window(&self) -> Res {
-        let proof_window_id = self.conn.generate_id()?;
+        let proof_window_id = self.heartbeat;
 
         self.conn.create_window(
             COPY_DEPTH_FROM_PARENT,
@@ -614,74 +1076,261 @@ impl<C: Connection> ConnectionActionExt for ConnectionHandler<'_, C> {
         )?;
 
         self.atoms.change_window_prop(
+            self.conn,
             self.screen.root,
             self.atoms.net_supporting_wm_check,
             &[proof_window_id],
         )?;
         self.atoms.change_window_prop(
+            self.conn,
             proof_window_id,
             self.atoms.net_supporting_wm_check,
             &[proof_window_id],
         )?;
-        self.atoms
-            .change_string_prop(proof_window_id, self.atoms.net_wm_name, "hematite")?;
+        self.atoms.change_string_prop(
+            self.conn,
+            proof_window_id,
+            self.atoms.net_wm_name,
+            "hematite",
+        )?;
         Ok(())
     }
 
-    fn get_window_name(&self, window: Window) -> Result<String, ReplyOrIdError> {
-        log::trace!("getting window name of {window}");
-
-        let result = String::from_utf8(
-            self.conn
-                .get_property(
-                    false,
-                    window,
-                    self.atoms.net_wm_name,
-                    self.atoms.utf8_string,
-                    0,
-                    100,
-                )?
-                .reply()?
-                .value,
-        )
-        .unwrap_or_default();
-
-        if result.is_empty() {
-            let result = String::from_utf8(
-                self.conn
-                    .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 100)?
-                    .reply()?
-                    .value,
-            )
-            .unwrap_or_default();
-            Ok(result)
-        } else {
-            Ok(result)
-        }
-    }
-
-    fn create_gc(&self, gc: Id, color_background: Id, color_foreground: Id) -> Res {
-        self.conn.create_gc(
-            gc,
+    fn create_edge_window(&self, window: &WindowState) -> Res {
+        self.conn.create_window(
+            COPY_DEPTH_FROM_PARENT,
+            window.window,
             self.screen.root,
-            &CreateGCAux::new()
-                .graphics_exposures(0)
-                .background(color_background)
-                .foreground(color_foreground),
+            window.x,
+            window.y,
+            window.width,
+            window.height,
+            0,
+            WindowClass::INPUT_ONLY,
+            0,
+            &CreateWindowAux::new()
+                .override_redirect(1)
+                .event_mask(EventMask::ENTER_WINDOW),
+        )?;
+        Ok(())
+    }
+
+    fn create_popup_window(&self, window: &WindowState) -> Res {
+        self.conn.create_window(
+            self.depth,
+            window.window,
+            self.screen.root,
+            window.x,
+            window.y,
+            window.width,
+            window.height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            self.visual,
+            &CreateWindowAux::new()
+                .colormap(self.colormap)
+                .override_redirect(1)
+                .background_pixel(self.colors.bar_background)
+                .border_pixel(self.colors.unfocused),
+        )?;
+        Ok(())
+    }
+
+    fn show_popup(&self, window: &WindowState) -> Res {
+        self.conn.configure_window(
+            window.window,
+            &ConfigureWindowAux::new()
+                .x(i32::from(window.x))
+                .y(i32::from(window.y))
+                .width(u32::from(window.width))
+                .height(u32::from(window.height))
+                .stack_mode(StackMode::ABOVE),
+        )?;
+        self.conn.map_window(window.window)?;
+        Ok(())
+    }
+
+    fn get_window_image(&self, window: Window) -> Result<(u16, u16, Vec<u8>), HematiteError> {
+        let geometry = self.conn.get_geometry(window)?.reply()?;
+        let mut data = self
+            .conn
+            .get_image(
+                ImageFormat::Z_PIXMAP,
+                window,
+                0,
+                0,
+                geometry.width,
+                geometry.height,
+                u32::MAX,
+            )?
+            .reply()?
+            .data;
+        if data.len() != usize::from(geometry.width) * usize::from(geometry.height) * 4 {
+            return Err(HematiteError::Render(format!(
+                "window {window} doesn't use 32 bits per pixel"
+            )));
+        }
+        // the alpha of windows without an argb visual is undefined
+        data.chunks_exact_mut(4).for_each(|p| p[3] = u8::MAX);
+        Ok((geometry.width, geometry.height, data))
+    }
+
+    fn get_idle_time(&self) -> Result<Duration, HematiteError> {
+        let info = self
+            .conn
+            .screensaver_query_info(self.screen.root)?
+            .reply()?;
+        Ok(Duration::from_millis(u64::from(info.ms_since_user_input)))
+    }
+
+    fn get_outputs(&self) -> Result<Vec<String>, HematiteError> {
+        if !self.randr {
+            return Ok(Vec::new());
+        }
+        let resources = self
+            .conn
+            .randr_get_screen_resources_current(self.screen.root)?
+            .reply()?;
+        let primary = self
+            .conn
+            .randr_get_output_primary(self.screen.root)?
+            .reply()?
+            .output;
+        let mut outputs = Vec::new();
+        for &output in &resources.outputs {
+            let info = self
+                .conn
+                .randr_get_output_info(output, resources.config_timestamp)?
+                .reply()?;
+            if info.connection != randr::Connection::CONNECTED || info.crtc == x11rb::NONE {
+                continue;
+            }
+            let name = String::from_utf8_lossy(&info.name).into_owned();
+            if output == primary {
+                outputs.insert(0, name);
+            } else {
+                outputs.push(name);
+            }
+        }
+        Ok(outputs)
+    }
+
+    fn get_window_name(&self, window: Window) -> Result<String, HematiteError> {
+        log::trace!("getting window name of {window}");
+
+        let result = String::from_utf8(
+            self.conn
+                .get_property(
+                    false,
+                    window,
+                    self.atoms.net_wm_name,
+                    self.atoms.utf8_string,
+                    0,
+                    100,
+                )?
+                .reply()?
+                .value,
+        )
+        .unwrap_or_default();
+
+        if result.is_empty() {
+            let result = String::from_utf8(
+                self.conn
+                    .get_property(false, window, AtomEnum::WM_NAME, AtomEnum::STRING, 0, 100)?
+                    .reply()?
+                    .value,
+            )
+            .unwrap_or_default();
+            Ok(result)
+        } else {
+            Ok(result)
+        }
+    }
+
+    fn get_window_size(&self, window: Window) -> Result<(u16, u16), HematiteError> {
+        let geometry = self.conn.get_geometry(window)?.reply()?;
+        Ok((geometry.width, geometry.height))
+    }
+
+    fn is_unmanaged(&self, window: Window) -> Result<bool, HematiteError> {
+        let attributes = self.conn.get_window_attributes(window)?.reply()?;
+        Ok(attributes.override_redirect || attributes.class == WindowClass::INPUT_ONLY)
+    }
+
+    fn map_unmanaged(&self, window: Window) -> Res {
+        self.conn.map_window(window)?;
+        Ok(())
+    }
+
+    fn configure_unmanaged(&self, event: ConfigureRequestEvent) -> Res {
+        self.conn.configure_window(
+            event.window,
+            &ConfigureWindowAux::from_configure_request(&event),
+        )?;
+        Ok(())
+    }
+
+    fn get_top_level_windows(&self) -> Result<Vec<Window>, HematiteError> {
+        let tree = self.conn.query_tree(self.screen.root)?.reply()?;
+        Ok(tree
+            .children
+            .into_iter()
+            .filter(|w| {
+                self.conn
+                    .get_window_attributes(*w)
+                    .ok()
+                    .and_then(|c| c.reply().ok())
+                    .is_some_and(|a| a.map_state == MapState::VIEWABLE && !a.override_redirect)
+            })
+            .collect())
+    }
+
+    fn create_gc(&self, gc: Id, drawable: Id, color_background: Id, color_foreground: Id) -> Res {
+        self.conn.create_gc(
+            gc,
+            drawable,
+            &CreateGCAux::new()
+                .graphics_exposures(0)
+                .background(color_background)
+                .foreground(color_foreground),
+        )?;
+        Ok(())
+    }
+
+    fn has_argb_visual(&self) -> bool {
+        self.depth == 32
+    }
+
+    fn select_bar_events(&self, window: Window) -> Res {
+        self.conn.change_window_attributes(
+            window,
+            &ChangeWindowAttributesAux::new().event_mask(
+                EventMask::KEY_PRESS
+                    | EventMask::SUBSTRUCTURE_NOTIFY
+                    | EventMask::ENTER_WINDOW
+                    | EventMask::LEAVE_WINDOW
+                    | EventMask::POINTER_MOTION
+                    | EventMask::PROPERTY_CHANGE
+                    | EventMask::BUTTON_PRESS,
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn set_focus_to_root(&self) -> Result<(), HematiteError> {
+        log::trace!("setting focus to root");
+        self.conn
+            .set_input_focus(InputFocus::NONE, 1_u32, CURRENT_TIME)?;
+
+        self.atoms.change_window_prop(
+            self.conn,
+            self.screen.root,
+            self.atoms.net_active_window,
+            &[1],
         )?;
         Ok(())
     }
 
-    fn set_focus_to_root(&self) -> Result<(), ReplyOrIdError> {
-        log::trace!("setting focus to root");
-        self.conn
-            .set_input_focus(InputFocus::NONE, 1_u32, CURRENT_TIME)?;
-
-        self.atoms
-            .change_window_prop(self.screen.root, self.atoms.net_active_window, &[1])?;
-        Ok(())
-    }
-
     fn kill_focus(&self, focus: Id) -> Res {
         log::trace!("killing focus window {focus}");
         self.conn.send_event(
@@ -698,8 +1347,79 @@ impl<C: Connection> ConnectionActionExt for ConnectionHandler<'_, C> {
         Ok(())
     }
 
-    fn generate_id(&self) -> Result<u32, ReplyOrIdError> {
-        self.conn.generate_id()
+    fn ping(&self, window: Window) -> Res {
+        log::trace!("pinging window {window}");
+        self.conn.send_event(
+            false,
+            window,
+            EventMask::NO_EVENT,
+            ClientMessageEvent::new(
+                32,
+                window,
+                self.atoms.wm_protocols,
+                [self.atoms.net_wm_ping, CURRENT_TIME, window, 0, 0],
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn kill_client(&self, window: Window) -> Res {
+        log::debug!("forcefully killing client of window {window}");
+        self.conn.kill_client(window)?;
+        Ok(())
+    }
+
+    fn generate_id(&self) -> Result<u32, HematiteError> {
+        Ok(self.conn.generate_id()?)
+    }
+
+    fn grab_pointer(&self) -> Result<bool, HematiteError> {
+        let status = self
+            .conn
+            .grab_pointer(
+                false,
+                self.screen.root,
+                EventMask::POINTER_MOTION | EventMask::BUTTON_RELEASE,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                x11rb::NONE,
+                CURRENT_TIME,
+            )?
+            .reply()?
+            .status;
+        if status != GrabStatus::SUCCESS {
+            log::error!("couldn't grab pointer: {status:?}");
+        }
+        Ok(status == GrabStatus::SUCCESS)
+    }
+
+    fn ungrab_pointer(&self) -> Res {
+        self.conn.ungrab_pointer(CURRENT_TIME)?;
+        Ok(())
+    }
+
+    fn grab_keyboard(&self) -> Result<bool, HematiteError> {
+        let status = self
+            .conn
+            .grab_keyboard(
+                false,
+                self.screen.root,
+                CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .reply()?
+            .status;
+        if status != GrabStatus::SUCCESS {
+            log::error!("couldn't grab keyboard: {status:?}");
+        }
+        Ok(status == GrabStatus::SUCCESS)
+    }
+
+    fn ungrab_keyboard(&self) -> Res {
+        self.conn.ungrab_keyboard(CURRENT_TIME)?;
+        Ok(())
     }
 
     fn get_screen_geometry(&self) -> (u16, u16) {
@@ -710,6 +1430,26 @@ impl<C: Connection> ConnectionActionExt for ConnectionHandler<'_, C> {
         self.screen.root
     }
 
+    fn colors(&self) -> &Colors {
+        &self.colors
+    }
+
+    fn set_colors(&mut self, config: &Config) -> Res {
+        self.colors = allocate_colors(self.conn, self.screen, config, self.depth == 32)?;
+        self.config = config.clone();
+        Ok(())
+    }
+
+    fn change_gc(&self, gc: Gcontext, color_background: Id, color_foreground: Id) -> Res {
+        self.conn.change_gc(
+            gc,
+            &ChangeGCAux::new()
+                .background(color_background)
+                .foreground(color_foreground),
+        )?;
+        Ok(())
+    }
+
     fn fill_rectangle(&self, pixmap: Pixmap, gc: Gcontext, rect: Rectangle) -> Res {
         self.conn
             .poly_fill_rectangle(pixmap, gc, &[rect])?
@@ -719,48 +1459,185 @@ impl<C: Connection> ConnectionActionExt for ConnectionHandler<'_, C> {
 }
 
 impl<C: Connection> ConnectionAtomExt for ConnectionHandler<'_, C> {
+    fn atoms(&self) -> &Atoms {
+        &self.atoms
+    }
+
+    fn get_atom_name(&self, atom: Atom) -> Result<String, HematiteError> {
+        self.atoms.get_atom_name(self.conn, atom)
+    }
+
     fn set_class(&self, class:&str, window: Window) -> Res {
-        self.atoms.change_string_prop(window, self.atoms.wm_class, class)?;
+        self.atoms
+            .change_string_prop(self.conn, window, self.atoms.wm_class, class)?;
         Ok(())
     }
 
+    fn get_window_class(&self, window: Window) -> Result<String, HematiteError> {
+        let class =
+            self.atoms
+                .get_property(self.conn, window, self.atoms.wm_class, AtomEnum::STRING)?;
+        let mut parts = class.split(|b| *b == 0);
+        let instance = parts.next().unwrap_or_default();
+        let class = parts.next().filter(|c| !c.is_empty()).unwrap_or(instance);
+        Ok(String::from_utf8_lossy(class).into_owned())
+    }
+
+    fn get_window_role(&self, window: Window) -> Result<String, HematiteError> {
+        let role = self.atoms.get_property(
+            self.conn,
+            window,
+            self.atoms.wm_window_role,
+            AtomEnum::STRING,
+        )?;
+        Ok(String::from_utf8_lossy(&role).into_owned())
+    }
+
+    fn get_window_pid(&self, window: Window) -> Result<Option<u32>, HematiteError> {
+        let pid = self.atoms.get_property(
+            self.conn,
+            window,
+            self.atoms.net_wm_pid,
+            AtomEnum::CARDINAL,
+        )?;
+        Ok(pid
+            .get(..4)
+            .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]])))
+    }
+
+    fn get_user_time(&self, window: Window) -> Result<Option<u32>, HematiteError> {
+        let time = self.atoms.get_property(
+            self.conn,
+            window,
+            self.atoms.net_wm_user_time,
+            AtomEnum::CARDINAL,
+        )?;
+        Ok(time
+            .get(..4)
+            .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]])))
+    }
+
+    fn get_dock_struts(&self, window: Window) -> Result<Option<Struts>, HematiteError> {
+        let cardinals = |data: Vec<u8>| -> Vec<u32> {
+            data.chunks_exact(4)
+                .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        };
+        let types = cardinals(self.atoms.get_property(
+            self.conn,
+            window,
+            self.atoms.net_wm_window_type,
+            AtomEnum::ATOM,
+        )?);
+        if !types.contains(&self.atoms.net_wm_window_type_dock) {
+            return Ok(None);
+        }
+        let mut struts = cardinals(self.atoms.get_property(
+            self.conn,
+            window,
+            self.atoms.net_wm_strut_partial,
+            AtomEnum::CARDINAL,
+        )?);
+        if struts.len() < 4 {
+            struts = cardinals(self.atoms.get_property(
+                self.conn,
+                window,
+                self.atoms.net_wm_strut,
+                AtomEnum::CARDINAL,
+            )?);
+        }
+        let edge = |i: usize| {
+            struts
+                .get(i)
+                .map_or(0, |s| u16::try_from(*s).unwrap_or(u16::MAX))
+        };
+        Ok(Some(Struts {
+            left: edge(0),
+            right: edge(1),
+            top: edge(2),
+            bottom: edge(3),
+        }))
+    }
+
     fn net_update_client_list(&self, windows: &[Window]) -> Res {
-        self.atoms
-            .change_window_prop(self.screen.root, self.atoms.net_client_list, windows)?;
+        self.atoms.change_window_prop(
+            self.conn,
+            self.screen.root,
+            self.atoms.net_client_list,
+            windows,
+        )?;
+        Ok(())
+    }
+
+    fn net_update_client_list_stacking(&self, windows: &[Window]) -> Res {
+        self.atoms.change_window_prop(
+            self.conn,
+            self.screen.root,
+            self.atoms.net_client_list_stacking,
+            windows,
+        )?;
         Ok(())
     }
 
     fn net_update_active_desktop(&self, tag: u32) -> Res {
-        self.atoms
-            .change_window_prop(self.screen.root, self.atoms.net_current_desktop, &[tag])?;
+        self.atoms.change_window_prop(
+            self.conn,
+            self.screen.root,
+            self.atoms.net_current_desktop,
+            &[tag],
+        )?;
+        Ok(())
+    }
+
+    fn net_update_showing_desktop(&self, showing: bool) -> Res {
+        self.atoms.change_cardinal_prop(
+            self.conn,
+            self.screen.root,
+            self.atoms.net_showing_desktop,
+            &[u32::from(showing)],
+        )?;
+        Ok(())
+    }
+
+    fn net_update_workarea(&self, area: (i16, i16, u16, u16)) -> Res {
+        let (x, y, width, height) = area;
+        self.atoms.change_cardinal_prop(
+            self.conn,
+            self.screen.root,
+            self.atoms.net_workarea,
+            &[x as u32, y as u32, u32::from(width), u32::from(height)].repeat(9),
+        )?;
         Ok(())
     }
 
     fn net_update_window_desktop(&self, window: Window, tag: u32) -> Res {
         self.atoms
-            .change_window_prop(window, self.atoms.net_wm_desktop, &[tag])?;
+            .change_window_prop(self.conn, window, self.atoms.net_wm_desktop, &[tag])?;
         Ok(())
     }
 
     fn net_add_allowed_actions(&self, window: Window) -> Res {
         self.atoms.change_atom_prop(
+            self.conn,
             window,
             self.atoms.net_wm_allowed_actions,
-            &[self.atoms.net_wm_action_fullscreen],
+            &[
+                self.atoms.net_wm_action_fullscreen,
+                self.atoms.net_wm_action_maximize_vert,
+                self.atoms.net_wm_action_maximize_horz,
+                self.atoms.net_wm_action_above,
+                self.atoms.net_wm_action_below,
+            ],
         )?;
         Ok(())
     }
 
-    fn net_add_frame_extents(&self, window: Window) -> Res {
+    fn net_add_frame_extents(&self, window: Window, border: u32) -> Res {
         self.atoms.change_cardinal_prop(
+            self.conn,
             window,
             self.atoms.net_frame_extents,
-            &[
-                self.config.border_size,
-                self.config.border_size,
-                self.config.border_size,
-                self.config.border_size,
-            ],
+            &[border, border, border, border],
         )?;
         Ok(())
     }
@@ -776,35 +1653,80 @@ impl<C: Connection> ConnectionAtomExt for ConnectionHandler<'_, C> {
         Ok(())
     }
 
-    fn net_set_active_window(&self, window: Window) -> Res {
-        self.atoms
-            .change_window_prop(self.screen.root, self.atoms.net_active_window, &[window])?;
+    fn wm_withdraw_window(&self, window: Window) -> Res {
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            self.atoms.wm_state,
+            self.atoms.wm_state,
+            &[0, 0],
+        )?;
         Ok(())
     }
 
-    fn net_set_state_fullscreen(&self, window: Window) -> Res {
-        self.atoms.change_atom_prop(
-            window,
-            self.atoms.net_wm_state,
-            &[self.atoms.net_wm_state_fullscreen],
+    fn net_set_active_window(&self, window: Window) -> Res {
+        self.atoms.change_window_prop(
+            self.conn,
+            self.screen.root,
+            self.atoms.net_active_window,
+            &[window],
         )?;
         Ok(())
     }
 
-    fn get_icon(&self, window: Window) -> Result<Vec<u8>, ReplyOrIdError> {
+    fn net_update_state(&self, window: &WindowState) -> Res {
+        let mut states = match window.group {
+            WindowGroup::Fullscreen => vec![self.atoms.net_wm_state_fullscreen],
+            WindowGroup::Maximized => vec![
+                self.atoms.net_wm_state_maximized_vert,
+                self.atoms.net_wm_state_maximized_horz,
+            ],
+            _ => Vec::new(),
+        };
+        match window.layer {
+            Layer::Above => states.push(self.atoms.net_wm_state_above),
+            Layer::Below => states.push(self.atoms.net_wm_state_below),
+            Layer::Normal => (),
+        }
+        if window.sticky {
+            states.push(self.atoms.net_wm_state_sticky);
+        }
+        if window.urgent {
+            states.push(self.atoms.net_wm_state_demands_attention);
+        }
         self.atoms
-            .get_property(window, self.atoms.net_wm_icon, AtomEnum::CARDINAL)
+            .change_atom_prop(self.conn, window.window, self.atoms.net_wm_state, &states)?;
+        Ok(())
+    }
+
+    fn get_icon(&self, window: Window) -> Result<Vec<u8>, HematiteError> {
+        self.atoms.get_property(
+            self.conn,
+            window,
+            self.atoms.net_wm_icon,
+            AtomEnum::CARDINAL,
+        )
     }
 
-    fn should_be_floating(&self, window: Window) -> Result<(u16, u16, bool), ReplyOrIdError> {
+    fn get_protocols(&self, window: Window) -> Result<Vec<Atom>, HematiteError> {
+        Ok(self
+            .atoms
+            .get_property(self.conn, window, self.atoms.wm_protocols, AtomEnum::ATOM)?
+            .chunks_exact(4)
+            .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+
+    fn should_be_floating(&self, window: Window) -> Result<(u16, u16, bool), HematiteError> {
         unsafe {
             let hints_data = self.atoms.get_property(
+                self.conn,
                 window,
                 AtomEnum::WM_NORMAL_HINTS.into(),
                 AtomEnum::WM_SIZE_HINTS,
             )?;
             let hints = hints_data.align_to::<u32>().1;
-            if hints.len() == 0 {
+            if hints.is_empty() {
                 return Ok((10, 10, false));
             }
             let width = hints[5];
@@ -816,19 +1738,192 @@ impl<C: Connection> ConnectionAtomExt for ConnectionHandler<'_, C> {
             }
         }
     }
+
+    fn get_size_hints(&self, window: Window) -> Result<SizeHints, HematiteError> {
+        let Some(hints) = WmSizeHints::get_normal_hints(self.conn, window)?.reply()? else {
+            return Ok(SizeHints::default());
+        };
+        let size = |size: Option<(i32, i32)>| {
+            size.map_or((0, 0), |(w, h)| {
+                (
+                    u16::try_from(w).unwrap_or_default(),
+                    u16::try_from(h).unwrap_or_default(),
+                )
+            })
+        };
+        let ratio = |aspect: AspectRatio| {
+            if aspect.numerator > 0 && aspect.denominator > 0 {
+                f64::from(aspect.numerator) / f64::from(aspect.denominator)
+            } else {
+                0.0
+            }
+        };
+        // the requested position is the window's own, as the position fields of the hints are obsolete as per ICCCM
+        let position = if hints.position.is_some() {
+            let geometry = self.conn.get_geometry(window)?.reply()?;
+            Some((geometry.x, geometry.y))
+        } else {
+            None
+        };
+        // the minimum size is used as the base size if there is none, as per ICCCM
+        Ok(SizeHints {
+            base: size(hints.base_size.or(hints.min_size)),
+            increment: size(hints.size_increment),
+            aspect: hints
+                .aspect
+                .map_or((0.0, 0.0), |(min, max)| (ratio(min), ratio(max))),
+            position,
+            gravity: hints.win_gravity.unwrap_or(Gravity::NORTH_WEST),
+        })
+    }
+
+    fn wants_decorations(&self, window: Window) -> Result<bool, HematiteError> {
+        let hints = self
+            .atoms
+            .get_property(self.conn, window, self.atoms.motif_wm_hints, AtomEnum::ANY)?
+            .chunks_exact(4)
+            .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<u32>>();
+        // the first field holds flags telling which fields are set, with the decorations being the third field
+        Ok(match hints[..] {
+            [flags, _, decorations, ..] if flags & MOTIF_HINTS_DECORATIONS != 0 => decorations != 0,
+            _ => true,
+        })
+    }
+
+    fn get_group_leader(&self, window: Window) -> Result<Option<Window>, HematiteError> {
+        Ok(WmHints::get(self.conn, window)?
+            .reply()?
+            .and_then(|h| h.window_group)
+            .filter(|&w| w != x11rb::NONE))
+    }
 }
 
-/// Spawns a shell command with the specified arguments.
+/// Spawns a shell command in a new session, detached from the manager.
+///
+/// The command is started with `setsid -f`, which forks again so the command is reparented away from the manager. This way it keeps running if the manager exits, and never becomes a zombie. Falls back to running the command directly if `setsid` isn't available.
 ///
 /// May log an error if there was an issue with spawning a command.
 pub fn spawn_command(command: &str) {
-    match Command::new("sh").arg("-c").arg(command).spawn() {
-        Ok(_) => (),
-        Err(e) => log::error!("error when spawning command {e:?}"),
+    spawn_command_with(command, &[], None);
+}
+
+/// Spawns a shell command like `spawn_command`, with additional environment variables and a working directory.
+///
+/// May log an error if there was an issue with spawning a command.
+pub fn spawn_command_with(command: &str, envs: &[(String, String)], cwd: Option<&str>) {
+    let build = |program: &str, args: &[&str]| {
+        let mut c = Command::new(program);
+        c.args(args).envs(envs.iter().map(|(k, v)| (k, v)));
+        if let Some(cwd) = cwd {
+            c.current_dir(cwd);
+        }
+        c
+    };
+    let child = match build("setsid", &["-f", "sh", "-c", command]).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            log::error!("error when spawning setsid {e:?}, spawning command directly");
+            match build("sh", &["-c", command]).spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    log::error!("error when spawning command {e:?}");
+                    return;
+                }
+            }
+        }
+    };
+    reap(child);
+}
+
+/// Runs a shell command and waits for it to finish, for commands whose effect is needed before going on (e.g. changing the volume before reading it). It should only be called off the event loop.
+///
+/// May log an error if the command couldn't be run.
+pub fn run_command(command: &str) {
+    if let Err(e) = Command::new("sh").args(["-c", command]).status() {
+        log::error!("error when running command {command}: {e:?}");
+    }
+}
+
+/// Expands a leading `~` to the home directory, and `$VAR` or `${VAR}` to the value of the environment variable.
+///
+/// Unset variables expand to an empty string.
+#[must_use]
+pub fn expand_vars(text: &str) -> String {
+    let text = match text.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{rest}", std::env::var("HOME").unwrap_or_default())
+        }
+        _ => text.to_owned(),
+    };
+
+    let mut expanded = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        let braced = chars.next_if_eq(&'{').is_some();
+        let mut name = String::new();
+        while let Some(n) = chars.next_if(|n| n.is_ascii_alphanumeric() || *n == '_') {
+            name.push(n);
+        }
+        let closed = braced && chars.next_if_eq(&'}').is_some();
+        if name.is_empty() || braced != closed {
+            // not a variable, so it's kept as is
+            expanded.push('$');
+            if braced {
+                expanded.push('{');
+            }
+            expanded.push_str(&name);
+            if closed {
+                expanded.push('}');
+            }
+            continue;
+        }
+        expanded.push_str(&std::env::var(&name).unwrap_or_default());
     }
+    expanded
 }
 
-/// Sets the event mask of the root window, and exits if another window manager is running.
+/// Gets the spawn id a process was started with by `spawn_command_with`, read from its environment.
+///
+/// Returns `None` if the process wasn't spawned with an id or its environment can't be read.
+#[must_use]
+pub fn get_spawn_id(pid: u32) -> Option<u64> {
+    let environ = std::fs::read(format!("/proc/{pid}/environ")).ok()?;
+    environ
+        .split(|b| *b == 0)
+        .find_map(|v| v.strip_prefix(SPAWN_ID_VAR.as_bytes())?.strip_prefix(b"="))
+        .and_then(|id| std::str::from_utf8(id).ok()?.parse().ok())
+}
+
+/// Waits for a child process in a separate thread, so that it doesn't become a zombie once it exits.
+pub fn reap(mut child: Child) {
+    thread::spawn(move || {
+        if let Err(e) = child.wait() {
+            log::error!("error when waiting for child process {e:?}");
+        }
+    });
+}
+
+/// Gets the shell command running a command inside a terminal emulator.
+///
+/// The terminal is expected to accept a command to run with the `-e` flag. If the command is empty, only the terminal is spawned.
+#[must_use]
+pub fn terminal_command(terminal: &str, command: &str) -> String {
+    if command.is_empty() {
+        terminal.to_owned()
+    } else {
+        format!("{terminal} -e sh -c '{}'", command.replace('\'', "'\\''"))
+    }
+}
+
+/// Sets the event mask of the root window.
+///
+/// # Errors
+/// Returns an error if another window manager is running.
 fn become_window_manager<C: Connection>(conn: &C, root: u32) -> Res {
     let change = ChangeWindowAttributesAux::default().event_mask(
         EventMask::SUBSTRUCTURE_REDIRECT
@@ -840,8 +1935,7 @@ fn become_window_manager<C: Connection>(conn: &C, root: u32) -> Res {
 
     if let Err(ReplyError::X11Error(ref error)) = result {
         if error.error_kind == ErrorKind::Access {
-            log::error!("another wm is running");
-            exit(1);
+            return Err(HematiteError::AnotherWindowManager);
         }
     } else {
         log::info!("became window manager successfully");
@@ -849,14 +1943,147 @@ fn become_window_manager<C: Connection>(conn: &C, root: u32) -> Res {
     Ok(())
 }
 
-/// Gets a pixel id from the specified RGB color.
+/// Ignores the error of a request made for a client window that has since been destroyed, so one dying client can't abort an action on every window.
+///
+/// # Errors
+/// Returns any other error.
+pub fn tolerate_vanished(result: Res) -> Res {
+    match result {
+        Err(error) if error.is_vanished_window() => {
+            log::debug!("ignoring error for vanished window: {error}");
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// Gets the DPI of a screen, to scale sizes with.
+///
+/// The `Xft.dpi` resource is used if set, as it's what desktop environments and toolkits scale with. Otherwise the DPI is computed from the width and physical width of the primary output (or the first enabled output) if the X11 server supports `RandR`, and from the screen's size if not. Outputs not reporting a physical size count as 96 DPI.
+/// # Errors
+/// May return an error if the connection is faulty.
+pub fn get_dpi<C: Connection>(conn: &C, screen_num: usize) -> Result<f32, HematiteError> {
+    let screen = &conn.setup().roots[screen_num];
+    if let Some(dpi) = resource_manager::new_from_default(conn)?
+        .get_string("Xft.dpi", "")
+        .and_then(|dpi| dpi.parse::<f32>().ok())
+        .filter(|dpi| *dpi > 0.0)
+    {
+        log::debug!("using xft dpi {dpi}");
+        return Ok(dpi);
+    }
+    let (width, mm_width) = if has_randr(conn)? {
+        output_size(conn, screen.root)?.unwrap_or((0, 0))
+    } else {
+        (
+            screen.width_in_pixels,
+            u32::from(screen.width_in_millimeters),
+        )
+    };
+    if mm_width == 0 {
+        return Ok(BASE_DPI);
+    }
+    Ok(f32::from(width) * 25.4 / mm_width as f32)
+}
+
+/// Gets the width in pixels and in millimeters of the primary output, or of the first enabled output if there's no primary output.
+fn output_size<C: Connection>(conn: &C, root: Window) -> Result<Option<(u16, u32)>, HematiteError> {
+    let resources = conn.randr_get_screen_resources_current(root)?.reply()?;
+    let primary = conn.randr_get_output_primary(root)?.reply()?.output;
+    let outputs = resources
+        .outputs
+        .iter()
+        .filter(|&&o| o == primary)
+        .chain(resources.outputs.iter().filter(|&&o| o != primary));
+    for &output in outputs {
+        let info = conn
+            .randr_get_output_info(output, resources.config_timestamp)?
+            .reply()?;
+        if info.crtc == x11rb::NONE {
+            continue;
+        }
+        let crtc = conn
+            .randr_get_crtc_info(info.crtc, resources.config_timestamp)?
+            .reply()?;
+        return Ok(Some((crtc.width, info.mm_width)));
+    }
+    Ok(None)
+}
+
+/// Sends an empty client message to the heartbeat window of a manager, waking its event loop so it ticks even while no other events arrive.
+///
+/// The message is delivered to the connection that created the window, so it has to be sent from another connection. The manager is expected to filter it out of its events.
+/// # Errors
+/// Returns an error if the heartbeat window no longer exists, e.g. because the manager's connection was closed.
+pub fn send_tick<C: Connection>(conn: &C, heartbeat: Window) -> Res {
+    let event = ClientMessageEvent::new(32, heartbeat, AtomEnum::NONE, [0_u32; 5]);
+    conn.send_event(false, heartbeat, EventMask::NO_EVENT, event)?
+        .check()?;
+    Ok(())
+}
+
+/// Checks whether the X11 server supports version 1.3 of the `RandR` extension, which is needed to list outputs.
+fn has_randr<C: Connection>(conn: &C) -> Result<bool, HematiteError> {
+    Ok(conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_some()
+        && conn.randr_query_version(1, 3)?.reply()?.minor_version >= 3)
+}
+
+/// Allocates the border and bar colors of the config, along with the focused border and highlight color of each tag.
+fn allocate_colors<C: Connection>(
+    conn: &C,
+    screen: &Screen,
+    config: &Config,
+    argb: bool,
+) -> Result<Colors, HematiteError> {
+    let tag_colors = |default: Color| {
+        config
+            .tag_colors
+            .iter()
+            .map(|c| get_color_id(conn, screen, c.unwrap_or(default), argb))
+            .collect::<Result<Vec<Id>, HematiteError>>()
+    };
+    Ok(Colors {
+        unfocused: get_color_id(conn, screen, config.unfocused_border_color, argb)?,
+        urgent: get_color_id(conn, screen, config.urgent_border_color, argb)?,
+        bar_background: get_color_id(conn, screen, config.bar_background_color, argb)?,
+        bar_foreground: get_color_id(conn, screen, config.bar_foreground_color, argb)?,
+        focused: tag_colors(config.focused_border_color)?,
+        tags: tag_colors(config.tag_highlight_color)?,
+    })
+}
+
+/// Gets a pixel id from the specified color.
+///
+/// With an ARGB visual the pixel is the premultiplied color itself, otherwise the color is allocated opaque from the default colormap.
 fn get_color_id<C: Connection>(
     conn: &C,
     screen: &Screen,
     color: Color,
-) -> Result<Id, ReplyOrIdError> {
+    argb: bool,
+) -> Result<Id, HematiteError> {
+    if argb {
+        let alpha = u32::from(color.alpha >> 8);
+        let premultiply = |c: u16| u32::from(c >> 8) * alpha / 255;
+        return Ok(alpha << 24
+            | premultiply(color.red) << 16
+            | premultiply(color.green) << 8
+            | premultiply(color.blue));
+    }
     Ok(conn
         .alloc_color(screen.default_colormap, color.red, color.green, color.blue)?
         .reply()?
         .pixel)
 }
+
+/// Finds a 32-bit `TrueColor` visual of the screen, which has an alpha channel.
+fn find_argb_visual(screen: &Screen) -> Option<Visualid> {
+    screen
+        .allowed_depths
+        .iter()
+        .filter(|d| d.depth == 32)
+        .flat_map(|d| &d.visuals)
+        .find(|v| v.class == VisualClass::TRUE_COLOR)
+        .map(|v| v.visual_id)
+}
@@ -0,0 +1,120 @@
+//!
+//! This module provides the crate's error type.
+//!
+//! Errors are told apart by where they come from (the X11 connection, the config, rendering or the manager's state), and can carry the window or action that failed, which makes the logs far more useful than a bare X11 error.
+use x11rb::{
+    errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError},
+    protocol::{ErrorKind, xproto::Window},
+};
+
+/// The errors the manager can run into.
+#[derive(Debug, thiserror::Error)]
+pub enum HematiteError {
+    /// The X11 server couldn't be connected to.
+    #[error("couldn't connect to the X11 server: {0}")]
+    Connect(#[from] ConnectError),
+    /// A request to the X11 server failed.
+    #[error("X11 error: {0}")]
+    Connection(#[from] ReplyOrIdError),
+    /// Another window manager is already running on the screen.
+    #[error("another window manager is running")]
+    AnotherWindowManager,
+    /// The config is invalid.
+    #[error("config error: {0}")]
+    Config(String),
+    /// Something couldn't be drawn, e.g. because the font is missing.
+    #[error("render error: {0}")]
+    Render(String),
+    /// The manager's state doesn't allow the action.
+    #[error("state error: {0}")]
+    State(String),
+    /// An action on a window failed.
+    #[error("{action} window {window} failed: {source}")]
+    Window {
+        /// The window the action was done on.
+        window: Window,
+        /// What was done, e.g. `mapping`.
+        action: &'static str,
+        /// The error the action ran into.
+        source: Box<HematiteError>,
+    },
+    /// An action failed.
+    #[error("{action} failed: {source}")]
+    Action {
+        /// What was done, e.g. the hotkey action.
+        action: String,
+        /// The error the action ran into.
+        source: Box<HematiteError>,
+    },
+}
+
+impl HematiteError {
+    /// Returns true if the error only means a client window was destroyed before the request about it reached the X11 server (`BadWindow` or `BadDrawable`).
+    ///
+    /// Windows often disappear between an event and the manager's reply to it, so these errors are expected and harmless.
+    #[must_use]
+    pub fn is_vanished_window(&self) -> bool {
+        match self {
+            Self::Connection(ReplyOrIdError::X11Error(error)) => {
+                matches!(error.error_kind, ErrorKind::Window | ErrorKind::Drawable)
+            }
+            Self::Window { source, .. } | Self::Action { source, .. } => {
+                source.is_vanished_window()
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if the error means the connection to the X11 server couldn't be made or was lost, e.g. because the server exited.
+    #[must_use]
+    pub fn is_connection_lost(&self) -> bool {
+        match self {
+            Self::Connect(_) | Self::Connection(ReplyOrIdError::ConnectionError(_)) => true,
+            Self::Window { source, .. } | Self::Action { source, .. } => {
+                source.is_connection_lost()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<ConnectionError> for HematiteError {
+    fn from(error: ConnectionError) -> Self {
+        Self::Connection(error.into())
+    }
+}
+
+impl From<ReplyError> for HematiteError {
+    fn from(error: ReplyError) -> Self {
+        Self::Connection(error.into())
+    }
+}
+
+/// Adds context to the errors of a result.
+pub trait Context<T> {
+    /// Tells which window the failed action was done on.
+    /// # Errors
+    /// Returns the error wrapped with the window and action, if any.
+    fn window_context(self, window: Window, action: &'static str) -> Result<T, HematiteError>;
+    /// Tells which action failed.
+    /// # Errors
+    /// Returns the error wrapped with the action, if any.
+    fn action_context(self, action: impl FnOnce() -> String) -> Result<T, HematiteError>;
+}
+
+impl<T, E: Into<HematiteError>> Context<T> for Result<T, E> {
+    fn window_context(self, window: Window, action: &'static str) -> Result<T, HematiteError> {
+        self.map_err(|e| HematiteError::Window {
+            window,
+            action,
+            source: Box::new(e.into()),
+        })
+    }
+
+    fn action_context(self, action: impl FnOnce() -> String) -> Result<T, HematiteError> {
+        self.map_err(|e| HematiteError::Action {
+            action: action(),
+            source: Box::new(e.into()),
+        })
+    }
+}
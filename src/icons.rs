@@ -0,0 +1,107 @@
+//!
+//! This module finds icons for windows that don't publish one themselves (through `_NET_WM_ICON`), such as terminal emulators and older applications.
+//!
+//! The window's class is matched against the desktop entries of installed applications to find the name of its icon, which is then looked up in the icon theme directories following the freedesktop icon theme specification. Only PNG icons are supported.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use xdg::BaseDirectories;
+
+use crate::render::Image;
+
+/// The icon themes searched, in order. Every theme inherits from `hicolor`, so applications install their icons there.
+const THEMES: [&str; 1] = ["hicolor"];
+/// The icon sizes searched, in order of preference. Sizes close to the bar's text are preferred, as they need the least scaling.
+const SIZES: [&str; 7] = [
+    "32x32", "48x48", "24x24", "64x64", "16x16", "128x128", "256x256",
+];
+
+/// Finds the icon of an application by the class of its window, as a BGRA image.
+///
+/// If no desktop entry matches the class, the class itself is tried as the icon's name.
+#[must_use]
+pub fn find_icon(class: &str) -> Option<Image> {
+    if class.is_empty() {
+        return None;
+    }
+    let dirs = BaseDirectories::new();
+    let name = find_icon_name(&dirs, class).unwrap_or_else(|| class.to_lowercase());
+    let path = find_icon_path(&dirs, &name)?;
+    log::debug!("using icon {} for class {class}", path.display());
+    load_icon(&path)
+}
+
+/// Finds the name of an application's icon in its desktop entry.
+///
+/// Entries match if their file name or their `StartupWMClass` key is the class, ignoring case.
+fn find_icon_name(dirs: &BaseDirectories, class: &str) -> Option<String> {
+    dirs.list_data_files("applications")
+        .iter()
+        .filter(|path| path.extension().is_some_and(|e| e == "desktop"))
+        .find_map(|path| {
+            let entry = fs::read_to_string(path).ok()?;
+            let name_matches = path
+                .file_stem()
+                .is_some_and(|s| s.to_string_lossy().eq_ignore_ascii_case(class));
+            let class_matches = get_entry_key(&entry, "StartupWMClass")
+                .is_some_and(|c| c.eq_ignore_ascii_case(class));
+            if name_matches || class_matches {
+                get_entry_key(&entry, "Icon").map(str::to_owned)
+            } else {
+                None
+            }
+        })
+}
+
+/// Gets the value of a key in the main group (`[Desktop Entry]`) of a desktop entry.
+fn get_entry_key<'a>(entry: &'a str, key: &str) -> Option<&'a str> {
+    entry
+        .lines()
+        .skip_while(|l| l.trim() != "[Desktop Entry]")
+        .skip(1)
+        .take_while(|l| !l.starts_with('['))
+        .find_map(|l| {
+            let (k, v) = l.split_once('=')?;
+            (k.trim() == key).then(|| v.trim())
+        })
+}
+
+/// Finds the file of an icon by its name, which may also be an absolute path.
+///
+/// The icon themes are searched first, followed by the legacy `pixmaps` directories.
+fn find_icon_path(dirs: &BaseDirectories, name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+    if path.is_absolute() {
+        return path.exists().then(|| path.to_owned());
+    }
+    THEMES
+        .iter()
+        .flat_map(|theme| {
+            SIZES
+                .iter()
+                .map(move |size| format!("icons/{theme}/{size}/apps/{name}.png"))
+        })
+        .chain([format!("icons/{name}.png"), format!("pixmaps/{name}.png")])
+        .find_map(|file| dirs.find_data_file(file))
+}
+
+/// Loads an icon file into a BGRA image.
+fn load_icon(path: &Path) -> Option<Image> {
+    let image = image::open(path)
+        .inspect_err(|e| log::error!("could not load icon {}: {e}", path.display()))
+        .ok()?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+    let data = image
+        .into_raw()
+        .chunks_exact(4)
+        .flat_map(|p| [p[2], p[1], p[0], p[3]])
+        .collect();
+    Some(Image {
+        width,
+        height,
+        data,
+    })
+}
@@ -0,0 +1,90 @@
+//!
+//! This module runs commands when the user goes idle, such as dimming the screen and locking the session.
+//!
+//! The idle time is the time since the last keyboard or pointer input, as reported by the X11 screen saver extension. It is checked on every tick, so commands run within a second of their threshold. Fullscreen windows (e.g. a playing video) can keep the user from going idle.
+use core::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, connection::spawn_command, session::SessionHandler};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Which windows keep the user from going idle.
+pub enum IdleInhibit {
+    /// The user goes idle regardless of the windows shown.
+    Never,
+    /// The user doesn't go idle while the active tag has a fullscreen window.
+    #[default]
+    Fullscreen,
+}
+
+/// A helper that runs the idle commands as the idle time crosses their thresholds.
+pub struct IdleHandler {
+    /// The idle time after which the dim command is run. Dimming is disabled if zero.
+    dim_after: Duration,
+    /// The idle time after which the session is locked. Locking is disabled if zero.
+    lock_after: Duration,
+    /// The command run when the dim threshold is crossed.
+    dim_command: String,
+    /// The command run on input after the dim command was run.
+    undim_command: String,
+    /// The command locking the session.
+    lock_command: String,
+    /// Whether the dim command was run, and the user hasn't come back since.
+    dimmed: bool,
+    /// Whether the session was locked, and the user hasn't come back since.
+    locked: bool,
+}
+
+impl IdleHandler {
+    /// Creates a new handler from the idle thresholds and commands in the config.
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        Self {
+            dim_after: Duration::from_secs(config.idle_dim_after),
+            lock_after: Duration::from_secs(config.idle_lock_after),
+            dim_command: config.idle_dim_command.clone(),
+            undim_command: config.idle_undim_command.clone(),
+            lock_command: config.lock_command.clone(),
+            dimmed: false,
+            locked: false,
+        }
+    }
+
+    /// Whether any idle command is configured, and the idle time needs to be checked.
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        !self.dim_after.is_zero() || (!self.lock_after.is_zero() && !self.lock_command.is_empty())
+    }
+
+    /// Runs the commands whose threshold the idle time crossed since the last update.
+    ///
+    /// The dim command is run once the dim threshold is crossed, and the undim command once there's input again. The session is locked once the lock threshold is crossed, and may be locked again after the next input.
+    pub fn update(&mut self, idle: Duration, session: &SessionHandler) {
+        if !self.dim_after.is_zero() && idle >= self.dim_after && !self.dimmed {
+            log::info!("idle for {}s, dimming", idle.as_secs());
+            self.dimmed = true;
+            if !self.dim_command.is_empty() {
+                spawn_command(&self.dim_command);
+            }
+        } else if idle < self.dim_after && self.dimmed {
+            log::info!("no longer idle, undimming");
+            self.dimmed = false;
+            if !self.undim_command.is_empty() {
+                spawn_command(&self.undim_command);
+            }
+        }
+
+        if self.lock_after.is_zero() || self.lock_command.is_empty() {
+            return;
+        }
+        if idle >= self.lock_after && !self.locked {
+            log::info!("idle for {}s, locking session", idle.as_secs());
+            self.locked = true;
+            session.lock(&self.lock_command);
+        } else if idle < self.lock_after {
+            self.locked = false;
+        }
+    }
+}
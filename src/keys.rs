@@ -6,24 +6,29 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use x11rb::{
     connection::Connection,
-    errors::ReplyOrIdError,
     protocol::xproto::{ConnectionExt as _, KeyButMask, KeyPressEvent, ModMask},
 };
 use xkeysym::{KeyCode, Keysym};
 
-use crate::config::Config;
+use crate::{config::Config, error::HematiteError};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 /// The possible actions a hotkey could activate.
 pub enum HotkeyAction {
     /// Spawns the specified command.
     Spawn(String),
+    /// Spawns the specified command inside the configured terminal. If the command is empty, only the terminal is opened.
+    SpawnInTerminal(String),
+    /// Spawns a command with additional environment variables and a working directory.
+    SpawnWith(SpawnOptions),
     /// Closes the currently focused window (if it exists).
     ExitFocusedWindow,
     /// Switches the active tag to the specified one.
     SwitchTag(usize),
     /// Moves the currently focused window to the specified tag.
     MoveWindow(usize),
-    /// Changes the ratio between the `Master` and `Stack` groups by the specified amount.
+    /// Moves the currently focused window to the specified tag, then switches to that tag, keeping the window focused.
+    MoveWindowAndFollow(usize),
+    /// Changes the ratio between the `Master` and `Stack` groups of the active tag by the specified amount.
     ChangeRatio(f32),
     /// Changes the window focus by the specified change.
     NextFocus(i16),
@@ -31,6 +36,54 @@ pub enum HotkeyAction {
     NextTag(i16),
     /// Swaps the focused window with the `Master` window.
     SwapMaster,
+    /// Promotes the focused window to `Master`, pushing the old `Master` window to the top of the stack. If the `Master` window is focused, the top of the stack is promoted instead.
+    Zoom,
+    /// Rotates the tiled windows of the active tag by the specified change, keeping the focus at the same position on the screen.
+    RotateStack(i16),
+    /// Hides or shows all windows of the active tag.
+    ToggleShowDesktop,
+    /// Maximizes the focused window to the work area, or restores it if it's already maximized.
+    ToggleMaximize,
+    /// Keeps the focused window above other windows, or puts it back in its usual stacking layer.
+    ToggleAlwaysOnTop,
+    /// Grows (or shrinks, if negative) the inner and outer gaps by the specified amount.
+    AdjustGaps(i16),
+    /// Changes the opacity of the focused window by the specified amount, for use with a compositor.
+    AdjustOpacity(f32),
+    /// Hides the bar, letting windows take up its area, or shows it again.
+    ToggleBar,
+    /// Switches the active tag between the tiled and tabbed layouts.
+    ToggleTabbed,
+    /// Pauses or resumes notifications (do not disturb), using `dunstctl`.
+    ToggleDnd,
+    /// Opens the launcher, listing the installed applications.
+    Launcher,
+    /// Opens the window finder, listing the windows of every tag.
+    FindWindow,
+    /// Switches the colors, and the font if the theme has one, to those of the specified named theme.
+    SetTheme(String),
+    /// Runs the specified command changing the volume, then shows the new volume on the OSD.
+    Volume(String),
+    /// Runs the specified command changing the screen brightness, then shows the new brightness on the OSD.
+    Brightness(String),
+    /// Changes the screen brightness by the specified amount in percent by writing to sysfs directly, then shows the new brightness on the OSD.
+    #[cfg(feature = "native-backlight")]
+    ChangeBrightness(i32),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A command to spawn, along with its environment and working directory.
+///
+/// `~` and `$VAR` are expanded in the values of environment variables and in the working directory.
+pub struct SpawnOptions {
+    /// The command, run with `sh -c`.
+    pub command: String,
+    /// Environment variables added to the command's environment.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// The working directory of the command. Uses the manager's working directory if not set.
+    #[serde(default)]
+    pub cwd: Option<String>,
 }
 
 #[derive(Debug)]
@@ -52,8 +105,12 @@ pub struct Hotkey {
 pub struct KeyHandler {
     /// A list of monitored hotkeys.
     pub hotkeys: Vec<Hotkey>,
+    /// The hotkeys that couldn't be resolved to a key and were skipped, along with why.
+    pub skipped: Vec<String>,
     /// A map of keysyms and their respective keycodes. 
     _sym_code: HashMap<Keysym, KeyCode>,
+    /// The keyboard mapping (minimum keycode, keysyms per keycode and keysyms), used to look up the keysyms of arbitrary key presses.
+    mapping: (u8, u8, Vec<u32>),
 }
 
 impl KeyHandler {
@@ -63,12 +120,11 @@ impl KeyHandler {
     /// 
     /// The hotkeys defined in the config file are grabbed and stored.
     /// 
+    /// Hotkeys whose key is unknown or not on the keyboard are skipped.
+    ///
     /// # Errors
-    /// May return an error if the hotkeys are invalid.
-    /// 
-    /// # Panics
-    /// 
-    pub fn new(conn: &impl Connection, config: &Config) -> Result<Self, ReplyOrIdError> {
+    /// May return an error if the keyboard mapping can't be read.
+    pub fn new(conn: &impl Connection, config: &Config) -> Result<Self, HematiteError> {
         //get min-max code
         let min = conn.setup().min_keycode;
         let max = conn.setup().max_keycode;
@@ -78,69 +134,74 @@ impl KeyHandler {
             .get_keyboard_mapping(min, max - min + 1)?
             .reply()?;
 
+        Ok(Self::from_mapping(
+            min,
+            max,
+            mapping.keysyms_per_keycode,
+            &mapping.keysyms,
+            config,
+        ))
+    }
+
+    /// Creates a new handler from a keyboard mapping, as returned by the X11 server for the keycodes `min` to `max`.
+    ///
+    /// This is useful when the mapping doesn't come from an X11 server, e.g. in a mock connection.
+    ///
+    /// Hotkeys whose key is unknown or isn't in the mapping are skipped, and listed in `skipped`.
+    #[must_use]
+    pub fn from_mapping(
+        min: u8,
+        max: u8,
+        keysyms_per_keycode: u8,
+        keysyms: &[u32],
+        config: &Config,
+    ) -> Self {
         //get sym-code pairings
         let sym_code: HashMap<Keysym, KeyCode> = (min..=max)
             .filter_map(|x| {
-                xkeysym::keysym(
-                    x.into(),
-                    0,
-                    min.into(),
-                    mapping.keysyms_per_keycode,
-                    mapping.keysyms.as_slice(),
-                )
-                .map(|s| (s, KeyCode::new(x.into())))
+                xkeysym::keysym(x.into(), 0, min.into(), keysyms_per_keycode, keysyms)
+                    .map(|s| (s, KeyCode::new(x.into())))
             })
             .collect();
 
         //get config hotkeys
+        let mut skipped = Vec::new();
         let hotkeys: Vec<Hotkey> = config
             .hotkeys
             .iter()
             .cloned()
-            .map(|c| {
+            .filter_map(|c| {
                 let modi = c
                     .modifiers
                     .split('|')
-                    .map(|m| match m {
-                        "CONTROL" => KeyButMask::CONTROL,
-                        "SHIFT" => KeyButMask::SHIFT,
-                        "MOD" => KeyButMask::MOD4,
-                        _ => KeyButMask::default(),
-                    })
+                    .map(|m| parse_modifier(m).unwrap_or_default())
                     .fold(KeyButMask::default(), |acc, m| acc | m);
 
-                let sym = match c.key.as_str() {
-                    "XK_Return" => Keysym::Return,
-                    "XF86_MonBrightnessUp" => Keysym::XF86_MonBrightnessUp,
-                    "XF86_MonBrightnessDown" => Keysym::XF86_MonBrightnessDown,
-                    "XF86_AudioRaiseVolume" => Keysym::XF86_AudioRaiseVolume,
-                    "XF86_AudioLowerVolume" => Keysym::XF86_AudioLowerVolume,
-                    "XF86_AudioMute" => Keysym::XF86_AudioMute,
-                    "XK_Left" => Keysym::Left,
-                    "XK_Right" => Keysym::Right,
-                    c => {
-                        let ch = c.chars().next().unwrap_or_else(|| {
-                            log::error!("BAD KEYSYM {c}");
-                            char::default()
-                        });
-                        Keysym::from_char(ch)
-                    }
+                let Some(sym) = parse_keysym(&c.key) else {
+                    skipped.push(format!("{}+{}: unknown keysym", c.modifiers, c.key));
+                    return None;
+                };
+                let Some(code) = sym_code.get(&sym) else {
+                    skipped.push(format!("{}+{}: not on the keyboard", c.modifiers, c.key));
+                    return None;
                 };
 
-                Hotkey {
+                Some(Hotkey {
                     _sym: sym,
-                    code: *sym_code.get(&sym).expect("expected sym to have code"),
+                    code: *code,
                     mask: modi,
                     modifier: ModMask::from(modi.bits()),
                     action: c.action,
-                }
+                })
             })
             .collect();
 
-        Ok(Self {
+        Self {
             _sym_code: sym_code,
             hotkeys,
-        })
+            skipped,
+            mapping: (min, keysyms_per_keycode, keysyms.to_vec()),
+        }
     }
 
     /// Gets a hotkey based on its mask and code.
@@ -150,6 +211,21 @@ impl KeyHandler {
             .find(|h| mask == h.mask && code_raw == h.code.raw())
     }
 
+    /// Gets the keysym of a `KeyPressEvent`, taking shift into account, e.g. to type into a menu.
+    #[must_use]
+    pub fn get_keysym(&self, event: KeyPressEvent) -> Option<Keysym> {
+        let (min, keysyms_per_keycode, keysyms) = &self.mapping;
+        let column = u8::from(event.state.contains(KeyButMask::SHIFT));
+        xkeysym::keysym(
+            event.detail.into(),
+            column,
+            (*min).into(),
+            *keysyms_per_keycode,
+            keysyms,
+        )
+        .filter(|s| s.raw() != 0)
+    }
+
     /// Gets the hotkey and its associated action based on a `KeyPressEvent`.
     #[must_use] 
     pub fn get_action(&self, event: KeyPressEvent) -> Option<HotkeyAction> {
@@ -157,3 +233,38 @@ impl KeyHandler {
             .map(|h| h.action.clone())
     }
 }
+
+/// Parses a hotkey's key, either one of the supported keysym names (e.g. `XK_Return`) or a single character (e.g. `q`).
+///
+/// Returns `None` if the key is empty or an unknown name.
+#[must_use]
+pub fn parse_keysym(key: &str) -> Option<Keysym> {
+    match key {
+        "XK_Return" => Some(Keysym::Return),
+        "XF86_MonBrightnessUp" => Some(Keysym::XF86_MonBrightnessUp),
+        "XF86_MonBrightnessDown" => Some(Keysym::XF86_MonBrightnessDown),
+        "XF86_AudioRaiseVolume" => Some(Keysym::XF86_AudioRaiseVolume),
+        "XF86_AudioLowerVolume" => Some(Keysym::XF86_AudioLowerVolume),
+        "XF86_AudioMute" => Some(Keysym::XF86_AudioMute),
+        "XK_Left" => Some(Keysym::Left),
+        "XK_Right" => Some(Keysym::Right),
+        key => {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => Some(Keysym::from_char(ch)),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Parses one of a hotkey's modifiers (`CONTROL`, `SHIFT` or `MOD`), returning `None` if it's unknown.
+#[must_use]
+pub fn parse_modifier(modifier: &str) -> Option<KeyButMask> {
+    match modifier {
+        "CONTROL" => Some(KeyButMask::CONTROL),
+        "SHIFT" => Some(KeyButMask::SHIFT),
+        "MOD" => Some(KeyButMask::MOD4),
+        _ => None,
+    }
+}
@@ -0,0 +1,113 @@
+//! A small, fast, opinionated X11 tiling window manager.
+//!
+//! Hematite provides the essential parts of a modern window manager while maintaining a light, easily configurable codebase.
+//!
+//! The code is organized in separate files, each being their own module:
+//! - `connection`: Traits and a struct implementing those traits, wrapping `x11rb`'s Connection trait, providing extra features
+//! - `state`: Struct holding the state of windows and desktops
+//! - `events`: Parsing events and handling them
+//! - `config`: User configuration and hotkey definitions
+//! - `error`: The error type, telling connection, config, render and state errors apart
+//! - `bar`: Status bar rendering
+//! - `blocks`: Built-in status blocks for the bar
+//! - `battery`: Warning about a low battery
+//! - `markup`: Colors in the status text
+//! - `icons`: Fallback icons for windows without one
+//! - `mock`: A connection implementing the connection traits without an X11 server, recording the calls made to it
+//! - `rules`: Matching windows to decide how they are placed
+//! - `schema`: Describing the config file as a JSON schema
+//! - `places`: Remembering where floating windows were left
+//! - `restore`: Saving the session and restoring it on the next start
+//! - `osd`: Briefly showing what a hotkey changed
+//! - `menu`: A keyboard-driven menu, used by the application launcher
+//! - `preview`: Previewing windows when hovering the bar
+//! - `session`: Locking the session before sleep
+//! - `idle`: Dimming the screen and locking the session when the user goes idle
+//! - `status`: Streaming the status to external bars
+//! - `validate`: Reporting and dropping invalid entries of the config file
+//!
+//! The flow of the program is:
+//! setup -> main event loop -> event catching -> event handling -> back to main loop.
+//!
+//! The `hematite` binary is a thin wrapper around this library, which can be used to build custom binaries on top of the core window manager logic, such as ones with a different bar or extra actions.
+//!
+//! See the `manager` module for the core logic implementation. Everything else is some kind of helper that abstracts away the various properties of the program.
+
+#![warn(clippy::correctness)]
+#![warn(clippy::suspicious)]
+#![warn(clippy::complexity)]
+#![warn(clippy::perf)]
+#![warn(clippy::style)]
+// #![warn(clippy::nursery)]
+#![warn(clippy::pedantic)]
+#![warn(clippy::missing_docs_in_private_items)]
+#![allow(clippy::cast_sign_loss, reason = "")]
+#![allow(clippy::cast_possible_truncation, reason = "")]
+#![allow(clippy::cast_possible_wrap, reason = "")]
+#![allow(clippy::cast_precision_loss, reason = "")]
+#![allow(clippy::collapsible_if, reason = "clippy is weird")]
+#![allow(
+    clippy::too_many_arguments,
+    reason = "function would have too much indirection"
+)]
+#![allow(
+    clippy::too_many_lines,
+    reason = "function is generating a config file"
+)]
+#![allow(
+    clippy::question_mark_used,
+    reason = "no additional error handling required"
+)]
+#![allow(clippy::implicit_return, reason = "")]
+#![allow(clippy::separated_literal_suffix, reason = "")]
+/// Atom handling.
+pub mod atoms;
+/// Status bar display.
+pub mod bar;
+/// Battery warnings.
+pub mod battery;
+/// Built-in status blocks.
+pub mod blocks;
+/// Config file parsing.
+pub mod config;
+/// Connection to the X11 server.
+pub mod connection;
+/// The crate's error type.
+pub mod error;
+/// Fallback window icons.
+pub mod icons;
+/// Idle detection.
+pub mod idle;
+/// Keypress handling.
+pub mod keys;
+/// Event handling and core logic.
+pub mod manager;
+/// Status text markup.
+pub mod markup;
+/// Keyboard-driven menus.
+pub mod menu;
+/// A mock connection for headless testing.
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+/// On-screen display popups.
+pub mod osd;
+/// Remembered floating window geometry.
+pub mod places;
+/// Window previews on the bar.
+pub mod preview;
+/// Font and image rendering.
+pub mod render;
+/// Session saving and restoring.
+pub mod restore;
+/// Window rules.
+pub mod rules;
+/// JSON schema generation for the config file.
+pub mod schema;
+/// Session locking and sleep inhibition.
+pub mod session;
+/// State management of windows and desktops.
+pub mod state;
+/// Status socket for external bars.
+pub mod status;
+/// Config file validation.
+pub mod validate;
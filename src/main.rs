@@ -1,133 +1,260 @@
-//! A small, fast, opinionated X11 tiling window manager.
+//! The `hematite` window manager binary.
 //!
-//! Hematite provides the essential parts of a modern window manager while maintaining a light, easily configurable codebase.
-//!
-//! The code is organized in separate files, each being their own module:
-//! - `connection`: Traits and a struct implementing those traits, wrapping `x11rb`'s Connection trait, providing extra features
-//! - `state`: Struct holding the state of windows and desktops
-//! - `events`: Parsing events and handling them
-//! - `config`: User configuration and hotkey definitions
-//! - `bar`: Status bar rendering
-//!
-//! The flow of the program is:
-//! setup -> main event loop -> event catching -> event handling -> back to main loop.
-//!
-//! See the `manager` module for the core logic implementation. Everything else is some kind of helper that abstracts away the various properties of the program.
+//! Sets up the connection, the state and the helpers from the `hematite` library, then runs the main event loop.
 
 // Xephyr -br -ac -noreset -screen 800x600 :1
 // DISPLAY=:1 RUST_BACKTRACE=1 RUST_LOG=debug hematite
-#![warn(clippy::correctness)]
-#![warn(clippy::suspicious)]
-#![warn(clippy::complexity)]
-#![warn(clippy::perf)]
-#![warn(clippy::style)]
-// #![warn(clippy::nursery)]
 #![warn(clippy::pedantic)]
 #![warn(clippy::missing_docs_in_private_items)]
-#![allow(clippy::cast_sign_loss, reason = "")]
 #![allow(clippy::cast_possible_truncation, reason = "")]
-#![allow(clippy::cast_possible_wrap, reason = "")]
-#![allow(clippy::cast_precision_loss, reason = "")]
-#![allow(clippy::collapsible_if, reason = "clippy is weird")]
-#![allow(
-    clippy::too_many_arguments,
-    reason = "function would have too much indirection"
-)]
-#![allow(
-    clippy::too_many_lines,
-    reason = "function is generating a config file"
-)]
-#![allow(
-    clippy::question_mark_used,
-    reason = "no additional error handling required"
-)]
-#![allow(clippy::implicit_return, reason = "")]
-#![allow(clippy::separated_literal_suffix, reason = "")]
-/// Atom handling.
-pub mod atoms;
-/// Status bar display.
-pub mod bar;
-/// Config file parsing.
-pub mod config;
-/// Connection to the X11 server.
-pub mod connection;
-/// Keypress handling.
-pub mod keys;
-/// Event handling and core logic.
-pub mod manager;
-/// Font and image rendering.
-pub mod render;
-/// State management of windows and desktops.
-pub mod state;
-use crate::{
+use core::time::Duration;
+use hematite::{
     bar::BarPainter,
+    battery::BatteryMonitor,
+    blocks::{BlockBar, BlockHandler},
     config::{Config, ConfigDeserialized},
-    connection::ConnectionHandler,
+    connection::{self, ConnectionHandler},
+    error::HematiteError,
+    idle::IdleHandler,
     keys::KeyHandler,
     manager::EventHandler,
+    menu::Menu,
+    osd::Osd,
+    places::Places,
+    preview::Preview,
+    restore::Restore,
+    session::SessionHandler,
     state::{StateHandler, TilingInfo},
+    status::StatusServer,
+};
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+};
+use x11rb::{
+    connection::Connection as _,
+    protocol::{Event, xproto::Window},
+    rust_connection::RustConnection,
 };
-use core::error::Error;
-use core::time::Duration;
-use std::{sync::mpsc, thread};
-use x11rb::{connection::Connection as _, errors::ReplyOrIdError};
 
-/// This function handles various handle initializations and starts the main event loop.
+/// The time to wait before connecting again after losing the X11 server.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Sets up logging and the config, then runs the window manager.
 ///
-/// A new thread is spawned to send a tick every second to update the status bar. This helps update the window name text and the status text, which may update frequently.
+/// With `--print-default-config` or `--print-schema`, the default config or a JSON schema of the config is printed instead.
 ///
-/// # Errors
-/// May return and exit if a connection to the X11 can't be made or the connection is dropped.
+/// If the connection to the X11 server is lost, connecting again is tried up to the configured number of times. Otherwise, or once every attempt failed, the manager exits cleanly: block commands are reaped, the status socket is removed and the logs are flushed.
 ///
-/// Event handling errors are simply logged.
-pub fn main() -> Result<(), Box<dyn Error>> {
+/// # Errors
+/// Returns any error that isn't a lost connection, including failing to connect at startup.
+pub fn main() -> Result<(), HematiteError> {
+    match std::env::args().nth(1).as_deref() {
+        Some("--print-default-config") => {
+            print!("{}", ConfigDeserialized::default_toml()?);
+            return Ok(());
+        }
+        Some("--print-schema") => {
+            print!("{}", ConfigDeserialized::schema()?);
+            return Ok(());
+        }
+        _ => (),
+    }
+
     env_logger::Builder::from_default_env()
         .target(env_logger::Target::Stdout)
         .init();
 
-    let (conn, screen_num) = x11rb::connect(None)?;
     let config = Config::from(ConfigDeserialized::new());
+    let session = SessionHandler::new(&config);
+    // outside the reconnect loop, so reconnecting doesn't start them again. Windows they open before hematite manages the screen are adopted
+    for command in &config.autostart {
+        log::info!("autostarting {command}");
+        connection::spawn_command(command);
+    }
+    // is `None` until the first connection is made, as failing to connect at startup is an error
+    let mut attempts = None;
+
+    let result = loop {
+        match (run(&config, &session, &mut attempts), attempts) {
+            (Err(error), Some(n))
+                if error.is_connection_lost() && n < config.reconnect_attempts =>
+            {
+                attempts = Some(n + 1);
+                log::warn!(
+                    "{error}, connecting again ({}/{})",
+                    n + 1,
+                    config.reconnect_attempts
+                );
+                thread::sleep(RECONNECT_DELAY);
+            }
+            (Err(error), Some(_)) if error.is_connection_lost() => {
+                log::info!("{error}, exiting");
+                break Ok(());
+            }
+            (result, _) => break result,
+        }
+    };
+
+    log::logger().flush();
+    result
+}
+
+/// This function handles various handle initializations and starts the main event loop.
+///
+/// The number of reconnection attempts is reset once connected.
+///
+/// Pending events are handled in batches, with property, configure and expose notifications handled after the other events so hotkeys stay responsive even when clients flood the manager with them. The bar is redrawn once a batch is handled.
+///
+/// A new thread is spawned to send a tick every second from a connection of its own, which wakes the event loop even while no other events arrive. Ticks update the status bar, which helps update the window name text and the status text, as they may update frequently. Idle windows are also parked on each tick.
+///
+/// # Errors
+/// Returns if a connection to the X11 can't be made or the connection is dropped. Every helper is dropped on return, which reaps the commands of status blocks.
+///
+/// Event handling errors are simply logged.
+fn run(
+    config: &Config,
+    session: &SessionHandler,
+    attempts: &mut Option<u32>,
+) -> Result<(), HematiteError> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    *attempts = Some(0);
+    let mut config = config.clone();
+    config.scale_to_dpi(connection::get_dpi(&conn, screen_num)?);
     let conn_handler = ConnectionHandler::new(&conn, screen_num, &config)?;
     let bar = BarPainter::new(&conn_handler, &conn_handler.colors, &config)?;
+    let preview = Preview::new(&conn_handler, &conn_handler.colors, &config)?;
+    let osd = Osd::new(&conn_handler, &conn_handler.colors, &config)?;
+    let menu = Menu::new(&conn_handler, &conn_handler.colors, &config)?;
+    let secondary_bars = if config.blocks.iter().any(|b| b.bar == BlockBar::Secondary) {
+        vec![BarPainter::new_secondary(
+            &conn_handler,
+            &conn_handler.colors,
+            &config,
+        )?]
+    } else {
+        Vec::new()
+    };
 
     let mut event_handler = EventHandler {
         state: StateHandler::new(TilingInfo {
-            gap: config.spacing as u16,
+            inner_gap: config.inner_spacing as u16,
+            outer_gap: config.outer_spacing as u16,
             ratio: config.ratio,
             max_width: conn_handler.screen.width_in_pixels,
             max_height: conn_handler.screen.height_in_pixels,
             bar_height: bar.bar.height,
+            bar_position: config.bar_position,
+            border: config.border_size as u16,
+            smart_gaps: config.smart_gaps,
+            smart_borders: config.smart_borders,
+            snap_distance: config.snap_distance,
         }),
         conn: conn_handler,
         key: KeyHandler::new(&conn, &config)?,
         bar,
+        secondary_bars,
+        session: session.clone(),
+        idle: IdleHandler::new(&config),
+        status: StatusServer::new(),
+        blocks: BlockHandler::new(&config),
+        battery: BatteryMonitor::new(&config),
+        places: Places::new(),
+        restore: Restore::new(),
+        preview,
+        osd,
+        menu,
+        config,
+        opacities: HashMap::new(),
+        closing: HashMap::new(),
+        pings: HashMap::new(),
+        pending_unmaps: HashMap::new(),
+        spawns: HashMap::new(),
+        spawn_count: 0,
+        focused: None,
+        user_time: 0,
     };
 
-    let (tx, rx) = mpsc::channel();
+    event_handler.reserve_secondary_bars();
+    event_handler.apply_monitor_config(true)?;
+    event_handler.restore_session()?;
+    event_handler.report_skipped_hotkeys()?;
+    event_handler.exec_always();
 
-    thread::spawn(move || -> Result<(), ReplyOrIdError> {
+    // ticks come in as events from a connection of their own, so they wake the loop while the user is idle
+    let heartbeat = event_handler.conn.heartbeat;
+    // helpers can also ask for a tick right away, e.g. once a level changed by a hotkey was read
+    let (waker, wake_requests) = mpsc::channel();
+    event_handler.blocks.set_waker(waker);
+    thread::spawn(move || {
+        let tick_conn = match x11rb::connect(None) {
+            Ok((tick_conn, _)) => tick_conn,
+            Err(error) => {
+                log::error!("couldn't connect for ticks: {error}");
+                return;
+            }
+        };
         loop {
-            if let Err(error) = tx.send(1_i32) {
-                log::error!("channel error: {error}");
+            if let Err(RecvTimeoutError::Disconnected) =
+                wake_requests.recv_timeout(Duration::from_secs(1))
+            {
+                break;
+            }
+            if let Err(error) = connection::send_tick(&tick_conn, heartbeat) {
+                log::debug!("stopped ticking: {error}");
                 break;
             }
-            thread::sleep(Duration::from_secs(1));
         }
-        Ok(())
     });
 
+    let result = handle_events(&conn, &mut event_handler, heartbeat);
+    // saved once more on exit, as the last tick may have been a second ago
+    event_handler.save_session();
+    result
+}
+
+/// Handles events until the connection is lost, ticking whenever a tick arrives at the heartbeat window.
+///
+/// # Errors
+/// Returns if the connection is dropped.
+fn handle_events(
+    conn: &RustConnection,
+    event_handler: &mut EventHandler<ConnectionHandler<'_, RustConnection>>,
+    heartbeat: Window,
+) -> Result<(), HematiteError> {
     loop {
-        if rx.try_recv().is_ok() {
-            event_handler.draw_bar();
-        }
         conn.flush()?;
-        let mut potential_event = Some(conn.wait_for_event()?);
+        let mut events = vec![conn.wait_for_event()?];
+        while let Some(event) = conn.poll_for_event().unwrap_or_default() {
+            events.push(event);
+        }
 
-        while let Some(event) = potential_event {
-            if let Err(error) = event_handler.handle_event(&event) {
+        let ticks = events.len();
+        events.retain(|e| !matches!(e, Event::ClientMessage(m) if m.window == heartbeat));
+        if events.len() < ticks {
+            event_handler.tick();
+        }
+
+        // key presses only skip floods that don't change focus, so hotkeys still act on the window focused before them
+        let (floods, others): (Vec<Event>, Vec<Event>) = events.into_iter().partition(|e| {
+            matches!(
+                e,
+                Event::PropertyNotify(_) | Event::ConfigureNotify(_) | Event::Expose(_)
+            )
+        });
+
+        for event in &others {
+            if let Err(error) = event_handler.handle_event(event) {
+                log::error!("{error}");
+            }
+        }
+        conn.flush()?;
+        for event in &floods {
+            if let Err(error) = event_handler.handle_event(event) {
                 log::error!("{error}");
             }
-            potential_event = conn.poll_for_event().unwrap_or_default();
         }
+        event_handler.draw_bar_if_dirty();
     }
 }
@@ -3,79 +3,184 @@
 //!
 //! This module is basically just for the `EventHandler` struct.
 
-use x11rb::{
-    connection::Connection,
-    protocol::{
-        Event,
-        xproto::{
-            ClientMessageEvent, ConfigureRequestEvent, EnterNotifyEvent, KeyPressEvent,
-            MapRequestEvent, UnmapNotifyEvent,
-        },
+use core::time::Duration;
+use std::{collections::HashMap, iter, time::Instant};
+
+use x11rb::protocol::{
+    Event,
+    shape::{NotifyEvent, SK},
+    xproto::{
+        ButtonPressEvent, ButtonReleaseEvent, ClientMessageEvent, ConfigWindow,
+        ConfigureRequestEvent, EnterNotifyEvent, ExposeEvent, KeyPressEvent, LeaveNotifyEvent,
+        MAP_REQUEST_EVENT, MapRequestEvent, MotionNotifyEvent, NotifyMode, ResizeRequestEvent,
+        UnmapNotifyEvent, Window,
     },
 };
 
 use crate::{
-    bar::BarPainter,
+    bar::{BarPainter, BarVisibility},
+    battery::{BatteryMonitor, BatteryWarning},
+    blocks::{BlockBar, BlockHandler, BlockKind, Level},
+    config::{Config, MIN_OPACITY, MonitorConfig},
     connection::{
-        ConnectionActionExt as _, ConnectionAtomExt as _, ConnectionHandler,
-        ConnectionStateExt as _, Res,
+        ConnectionActionExt, ConnectionAtomExt, ConnectionStateExt, Res, tolerate_vanished,
     },
+    error::Context as _,
+    idle::{IdleHandler, IdleInhibit},
     keys::{HotkeyAction, KeyHandler},
-    state::{StateHandler, WindowGroup, WindowState},
+    menu::{Menu, MenuItem, MenuResult},
+    osd::Osd,
+    places::Places,
+    preview::Preview,
+    restore::Restore,
+    rules,
+    session::SessionHandler,
+    state::{
+        Drag, DragDirection, FocusStealing, Layer, Layout, StateHandler, Struts, WindowGroup,
+        WindowState,
+    },
+    status::StatusServer,
 };
 
+/// The time a client has to answer a ping or close itself before it can be killed.
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+/// The time a spawned command has to open its first window on the tag it was spawned from.
+const SPAWN_TIMEOUT: Duration = Duration::from_mins(2);
+
 /// The main struct handling events.
 /// This struct employs all the other handlers and uses their apis to change the state or do something with X11, handling all the required events for a window manager.
-pub struct EventHandler<'connection, C: Connection> {
+pub struct EventHandler<C: ConnectionStateExt + ConnectionActionExt + ConnectionAtomExt> {
     /// A struct to manage the bar.
     pub bar: BarPainter,
+    /// The bars showing only the blocks assigned to them, each reserving its edge of the screen like a dock.
+    pub secondary_bars: Vec<BarPainter>,
     /// A struct to manage X11 related actions.
-    pub conn: ConnectionHandler<'connection, C>,
+    pub conn: C,
     /// An api to help with keypresses.
     pub key: KeyHandler,
     /// A struct to change the state of windows.
     pub state: StateHandler,
+    /// The user configuration.
+    pub config: Config,
+    /// A helper to track whether the session is locked.
+    pub session: SessionHandler,
+    /// A socket streaming the status to external bars.
+    pub status: StatusServer,
+    /// The built-in status blocks of the bar.
+    pub blocks: BlockHandler,
+    /// The monitor warning about a low battery.
+    pub battery: BatteryMonitor,
+    /// The last floating geometry of each application.
+    pub places: Places,
+    /// The helper running commands when the user goes idle.
+    pub idle: IdleHandler,
+    /// The saved session, restoring windows to their tags of the last session.
+    pub restore: Restore,
+    /// The popup previewing windows when hovering the bar.
+    pub preview: Preview,
+    /// The popup briefly showing what a hotkey changed.
+    pub osd: Osd,
+    /// The keyboard-driven menu, such as the launcher.
+    pub menu: Menu,
+    /// The opacity of each window set with hotkeys. Other windows are opaque while focused.
+    pub opacities: HashMap<Window, f32>,
+    /// Windows that were asked to close, and when they were asked.
+    ///
+    /// Asking a window to close a second time before the timeout kills its client.
+    pub closing: HashMap<Window, Instant>,
+    /// Windows that were pinged but haven't answered yet, and when they were pinged.
+    ///
+    /// Clients not answering before the timeout are killed.
+    pub pings: HashMap<Window, Instant>,
+    /// The number of unmaps of each client window requested by the manager itself (e.g. when switching tags) that haven't been notified yet.
+    ///
+    /// Only other unmaps mean the client withdrew its window.
+    pub pending_unmaps: HashMap<Window, usize>,
+    /// Commands spawned from hotkeys that haven't opened a window yet, by spawn id, with the tag they were spawned from and when.
+    pub spawns: HashMap<u64, (usize, Instant)>,
+    /// The number of commands spawned from hotkeys, used as the next spawn id.
+    pub spawn_count: u64,
+    /// The focused window as of the last refresh, used to tell when the focus changes.
+    pub focused: Option<Window>,
+    /// The X server time of the last key or button press the manager got, compared with the user time of new windows to keep them from stealing the focus.
+    pub user_time: u32,
 }
 
-impl<C: Connection> EventHandler<'_, C> {
+impl<C: ConnectionStateExt + ConnectionActionExt + ConnectionAtomExt> EventHandler<C> {
     /// Handles X11 events related to managing windows.
     ///
-    /// Currently, only mapping, unmapping, keypresses, entering a window, configure requests and messages are handled.
+    /// Currently, only mapping, unmapping, keypresses, entering a window, configure requests, messages and pointer drags are handled.
+    ///
+    /// Errors caused by a client window vanishing before the manager got to it are ignored.
     ///
     /// # Errors
-    /// Any inappropriate call to the X11 server will be bubbled up by this function.
+    /// Any other inappropriate call to the X11 server will be bubbled up by this function, along with the window or hotkey it was made for.
     pub fn handle_event(&mut self, event: &Event) -> Res {
-        match event {
-            Event::MapRequest(event) => {
-                self.handle_map_request(*event)?;
-            }
-            Event::UnmapNotify(event) => {
-                self.handle_unmap_notify(*event)?;
+        let result = match event {
+            Event::MapRequest(event) => self
+                .handle_map_request(*event)
+                .window_context(event.window, "mapping"),
+            Event::UnmapNotify(event) => self
+                .handle_unmap_notify(*event)
+                .window_context(event.window, "unmapping"),
+            Event::DestroyNotify(event) => {
+                self.bar.cache.forget(event.window);
+                self.preview.forget(event.window);
+                self.restore.forget(event.window);
+                self.opacities.remove(&event.window);
+                Ok(())
             }
-            Event::KeyPress(event) => {
-                self.handle_keypress(*event)?;
-            }
-            Event::EnterNotify(event) => {
-                self.handle_enter(*event)?;
-            }
-            Event::ConfigureRequest(event) => {
-                self.handle_config(*event)?;
-            }
-            Event::ClientMessage(event) => {
-                self.handle_client_message(*event)?;
-            }
-            _ => (),
-        }
-        Ok(())
+            Event::KeyPress(event) => self.handle_keypress(*event).action_context(|| {
+                self.key
+                    .get_action(*event)
+                    .map_or_else(String::new, |a| format!("hotkey {a:?}"))
+            }),
+            Event::EnterNotify(event) => self.handle_enter(*event),
+            Event::LeaveNotify(event) => self.handle_leave(*event),
+            Event::ConfigureRequest(event) => self
+                .handle_config(*event)
+                .window_context(event.window, "configuring"),
+            Event::ResizeRequest(event) => self
+                .handle_resize_request(*event)
+                .window_context(event.window, "resizing"),
+            Event::ClientMessage(event) => self
+                .handle_client_message(*event)
+                .window_context(event.window, "handling a message for"),
+            Event::MotionNotify(event) => self.handle_motion(*event),
+            Event::ButtonPress(event) => self.handle_button_press(*event),
+            Event::ButtonRelease(event) => self.handle_button_release(*event),
+            Event::Expose(event) if event.count == 0 => self.handle_expose(*event),
+            Event::ShapeNotify(event) if event.shape_kind == SK::BOUNDING => self
+                .handle_shape_notify(*event)
+                .window_context(event.affected_window, "shaping"),
+            Event::RandrScreenChangeNotify(_) => self.apply_monitor_config(false),
+            _ => Ok(()),
+        };
+        tolerate_vanished(result)
     }
 
     /// Handles a `MapRequestEvent`.
     ///
     /// Only maps unmapped windows. Adds the window (including frame) using a connection and adds the window to the state. Also refreshes the display.
+    ///
+    /// Override-redirect and input-only windows (e.g. menus, tooltips and drag icons) are mapped as is, without being managed. So are docks (e.g. panels and trays), whose reserved space is left out of the work area until they're unmapped.
     fn handle_map_request(&mut self, event: MapRequestEvent) -> Res {
         if self.state.get_window_state(event.window).is_some() {
             return Ok(());
         }
+        if self.conn.is_unmanaged(event.window)? {
+            log::trace!("mapping unmanaged window {}", event.window);
+            return self.conn.map_unmanaged(event.window);
+        }
+        if let Some(struts) = self.conn.get_dock_struts(event.window)? {
+            log::debug!("mapping dock {} reserving {struts:?}", event.window);
+            // docks aren't reparented, so adopting one doesn't unmap it
+            self.pending_unmaps.remove(&event.window);
+            self.state.add_dock(event.window, struts);
+            self.conn.map_unmanaged(event.window)?;
+            self.update_workarea()?;
+            return self.refresh();
+        }
 
         log::trace!(
             "EVENT MAP window {} parent {} response {}",
@@ -85,10 +190,13 @@ impl<C: Connection> EventHandler<'_, C> {
         );
 
         let (width, height, should_be_floating) = self.conn.should_be_floating(event.window)?;
+        let hints = self.conn.get_size_hints(event.window).unwrap_or_default();
+        let leader = self.conn.get_group_leader(event.window).unwrap_or_default();
+        let decorated = self.conn.wants_decorations(event.window).unwrap_or(true);
 
         let screen = self.conn.get_screen_geometry();
 
-        let window = if should_be_floating {
+        let mut window = if should_be_floating {
             WindowState {
                 window: event.window,
                 frame_window: self.conn.generate_id()?,
@@ -97,6 +205,14 @@ impl<C: Connection> EventHandler<'_, C> {
                 width,
                 height,
                 group: WindowGroup::Floating,
+                layer: Layer::Normal,
+                sticky: false,
+                urgent: false,
+                decorated,
+                last_focus: Instant::now(),
+                hints,
+                restore: None,
+                leader,
             }
         } else {
             WindowState {
@@ -107,21 +223,170 @@ impl<C: Connection> EventHandler<'_, C> {
                 width,
                 height,
                 group: WindowGroup::Stack,
+                layer: Layer::Normal,
+                sticky: false,
+                urgent: false,
+                decorated,
+                last_focus: Instant::now(),
+                hints,
+                restore: None,
+                leader,
             }
         };
 
+        let title = window.title_height(self.config.title_bars, self.config.title_height);
+        window.height += title;
+        // floating windows are placed by the configured policy unless they asked for a position
+        if window.group == WindowGroup::Floating {
+            let border = if decorated {
+                self.config.border_size as u16
+            } else {
+                0
+            };
+            (window.x, window.y) = hints.get_frame_position(border, title).unwrap_or_else(|| {
+                self.state
+                    .get_floating_position(self.config.placement, window.width, window.height)
+            });
+        }
+
+        let class = self.conn.get_window_class(event.window).unwrap_or_default();
+        self.restore.track(event.window, &class);
+        let restored = self
+            .restore
+            .take(&class)
+            .filter(|r| r.tag < self.state.tags.len());
+        if let Some((x, y, width, height)) = restored.as_ref().and_then(|r| r.floating) {
+            log::trace!("placing window of {class} at its geometry of the last session");
+            window.group = WindowGroup::Floating;
+            (window.x, window.y, window.width, window.height) = (x, y, width, height);
+        } else if window.group == WindowGroup::Floating
+            && let Some((x, y, width, height)) = self.places.get(&class)
+        {
+            log::trace!("placing window of {class} at its remembered geometry");
+            (window.x, window.y, window.width, window.height) = (x, y, width, height);
+        }
+
+        if self.config.pip_enabled && self.is_picture_in_picture(event.window) {
+            self.place_picture_in_picture(&mut window)?;
+        }
+
         log::trace!("new window = {window:?}");
 
+        // the first window of a spawned command opens on the tag it was spawned from, windows of the last session open on their saved tag, and windows joining a group open on the group's tag, unless a rule says otherwise
+        let spawn_tag = self.take_spawn_tag(event.window);
+        let assigned = rules::find_assign_rule(&self.config.assign, &class)
+            .map(|r| (r.tag - 1, r.switch))
+            .or_else(|| spawn_tag.map(|tag| (tag, false)))
+            .or_else(|| restored.map(|r| (r.tag, false)))
+            .or_else(|| self.state.find_group_tag(&window).map(|tag| (tag, false)))
+            .filter(|(tag, _)| *tag != self.state.active_tag && !window.sticky);
+        if let Some((tag, false)) = assigned {
+            log::trace!("opening window of {class} on tag {tag} in the background");
+            self.conn.add_window(&window)?;
+            self.unmap_client(&window)?;
+            self.conn
+                .net_update_window_desktop(window.window, tag as u32)?;
+            self.state.add_window_to_tag(window, tag);
+            self.update_opacity(Some(window.window))?;
+            self.run_hook(&self.config.hooks.window_open, Some(window.window));
+            return self.refresh();
+        }
+        if let Some((tag, true)) = assigned {
+            log::trace!("switching to tag {tag} for window of {class}");
+            self.change_active_tag(tag)?;
+        }
+
+        self.set_showing_desktop(false)?;
+
+        let previous_focus = self.state.get_focus();
+        let steals_focus = previous_focus.is_some_and(|f| self.steals_focus(window.window, f));
+        if steals_focus {
+            log::debug!(
+                "opening {} unfocused to keep it from stealing the focus",
+                window.window
+            );
+            window.urgent = true;
+        }
+
         self.conn.add_window(&window)?;
+        if window.sticky || window.urgent {
+            self.conn.net_update_state(&window)?;
+        }
+        if window.sticky {
+            self.conn
+                .net_update_window_desktop(window.window, u32::MAX)?;
+        }
         self.state.add_window(window);
+        if steals_focus {
+            self.state.tags[self.state.active_tag].focus = previous_focus;
+        }
+        self.update_opacity(Some(window.window))?;
+        self.run_hook(&self.config.hooks.window_open, Some(window.window));
         self.refresh()
     }
 
+    /// Whether focusing a new window would steal the focus, if focus stealing is prevented.
+    ///
+    /// It would if the window asked not to be focused with a user time of 0, or if the user interacted with the manager or the focused window since they last interacted with the new window (e.g. typing while a slow application was starting). Windows without a user time are focused.
+    fn steals_focus(&self, window: Window, focus: Window) -> bool {
+        if self.config.focus_stealing == FocusStealing::Allow {
+            return false;
+        }
+        // X server times wrap around, so later times are less than half the range ahead
+        let is_later =
+            |time: u32, than: u32| time != than && time.wrapping_sub(than) < u32::MAX / 2;
+        let user_time = match self.conn.get_user_time(focus).unwrap_or_default() {
+            Some(time) if is_later(time, self.user_time) => time,
+            _ => self.user_time,
+        };
+        match self.conn.get_user_time(window).unwrap_or_default() {
+            Some(0) => true,
+            Some(time) => is_later(user_time, time),
+            None => false,
+        }
+    }
+
+    /// Whether a window is a browser's picture-in-picture window.
+    fn is_picture_in_picture(&self, window: Window) -> bool {
+        let class = self.conn.get_window_class(window).unwrap_or_default();
+        let role = self.conn.get_window_role(window).unwrap_or_default();
+        let title = self.conn.get_window_name(window).unwrap_or_default();
+        rules::is_picture_in_picture(&class, &role, &title)
+    }
+
+    /// Makes a picture-in-picture window floating, sticky and always on top, snapping it to the configured corner.
+    ///
+    /// The window is shrunk to the configured maximum size, keeping its aspect ratio.
+    fn place_picture_in_picture(&self, window: &mut WindowState) -> Res {
+        log::trace!("placing picture-in-picture window {}", window.window);
+        let (width, height) = self.conn.get_window_size(window.window)?;
+        (window.width, window.height) = rules::cap_size(
+            width,
+            height,
+            self.config.pip_max_width,
+            self.config.pip_max_height,
+        );
+        (window.x, window.y) =
+            self.state
+                .get_corner_position(self.config.pip_corner, window.width, window.height);
+        window.group = WindowGroup::Floating;
+        window.layer = Layer::Above;
+        window.sticky = true;
+        Ok(())
+    }
+
     /// Handles an `UnmapNotifyEvent`.
     ///
-    /// Only unmaps existing windows. Destroys the window and frame and removes it from the state. Also refreshes the display.
+    /// Only unmaps existing windows, on any tag. Destroys the window and frame and removes it from the state. Also refreshes the display.
+    ///
+    /// Unmapped frame windows are ignored, as only the manager unmaps them. Unmaps requested by the manager itself (e.g. when switching tags) are ignored as well, so only clients withdrawing their windows stop them from being managed.
     fn handle_unmap_notify(&mut self, event: UnmapNotifyEvent) -> Res {
-        let Some(window) = self.state.get_window_state(event.window) else {
+        if self.state.remove_dock(event.window) {
+            log::debug!("dock {} was unmapped", event.window);
+            self.update_workarea()?;
+            return self.refresh();
+        }
+        let Some((tag, window)) = self.state.find_window(event.window) else {
             return Ok(());
         };
         log::trace!(
@@ -132,22 +397,34 @@ impl<C: Connection> EventHandler<'_, C> {
             event.response_type
         );
 
+        // clients withdrawing an unmapped window send a synthetic event instead, as per ICCCM
+        let synthetic = event.response_type & 0x80 != 0;
+        if !synthetic && let Some(pending) = self.pending_unmaps.get_mut(&event.window) {
+            *pending -= 1;
+            if *pending == 0 {
+                self.pending_unmaps.remove(&event.window);
+            }
+            log::trace!(
+                "ignoring unmap of {} requested by the manager",
+                event.window
+            );
+            return Ok(());
+        }
+
+        log::trace!("window {} was withdrawn", event.window);
+        self.run_hook(&self.config.hooks.window_close, Some(event.window));
+        self.pending_unmaps.remove(&event.window);
         self.conn.destroy_frame_window(window)?;
-        self.conn.net_update_client_list(
-            &self.state.tags[self.state.active_tag]
-                .windows
-                .iter()
-                .map(|w| w.window)
-                .collect::<Vec<u32>>(),
-        )?;
+        self.conn.wm_withdraw_window(event.window)?;
 
-        self.bar.cache.icons.remove(&window.window);
-        self.bar.cache.names.remove(&window.window);
-        self.state
-            .get_mut_active_tag_windows()
-            .retain(|w| w.window != event.window);
+        self.closing.remove(&window.window);
+        self.pings.remove(&window.window);
+        self.bar.cache.forget(window.window);
+        self.state.remove_window(event.window);
 
-        self.state.set_tag_focus_to_master();
+        if tag == self.state.active_tag {
+            self.state.set_tag_focus_to_master();
+        }
         self.refresh()
     }
 
@@ -155,6 +432,10 @@ impl<C: Connection> EventHandler<'_, C> {
     ///
     /// Only parses keys with valid hotkey actions. The parsed action is also handled. Also refreshes the display.
     fn handle_keypress(&mut self, event: KeyPressEvent) -> Res {
+        self.user_time = event.time;
+        if self.menu.is_open() {
+            return self.handle_menu_key(event);
+        }
         let Some(action) = self.key.get_action(event) else {
             return Ok(());
         };
@@ -171,31 +452,151 @@ impl<C: Connection> EventHandler<'_, C> {
                 self.change_active_tag(n - 1)?;
             }
             HotkeyAction::MoveWindow(n) => {
-                self.move_window(n - 1)?;
+                self.move_window(self.conn.get_focus()?, n - 1)?;
+            }
+            HotkeyAction::MoveWindowAndFollow(n) => {
+                let Some(window) = self
+                    .state
+                    .get_window_state(self.conn.get_focus()?)
+                    .map(|w| w.window)
+                else {
+                    return Ok(());
+                };
+                if self.state.active_tag != n - 1 {
+                    self.move_window(window, n - 1)?;
+                    self.state.tags[n - 1].focus = Some(window);
+                    self.change_active_tag(n - 1)?;
+                }
             }
             HotkeyAction::Spawn(command) => {
-                crate::connection::spawn_command(&command);
+                self.spawn_on_active_tag(&command, Vec::new(), None);
+            }
+            HotkeyAction::SpawnInTerminal(command) => {
+                let command = crate::connection::terminal_command(&self.config.terminal, &command);
+                self.spawn_on_active_tag(&command, Vec::new(), None);
+            }
+            HotkeyAction::SpawnWith(options) => {
+                let envs = options
+                    .env
+                    .iter()
+                    .map(|(k, v)| (k.clone(), crate::connection::expand_vars(v)))
+                    .collect();
+                let cwd = options.cwd.as_deref().map(crate::connection::expand_vars);
+                self.spawn_on_active_tag(&options.command, envs, cwd.as_deref());
             }
             HotkeyAction::ExitFocusedWindow => {
                 let Some(focus) = self.state.get_focus() else {
                     return Ok(());
                 };
-                self.conn.kill_focus(focus)?;
+                self.close_window(focus)?;
             }
             HotkeyAction::ChangeRatio(change) => {
-                self.state.tiling.ratio = (self.state.tiling.ratio + change).clamp(0.15, 0.85);
+                let tag = &mut self.state.tags[self.state.active_tag];
+                tag.ratio = (tag.ratio + change).clamp(0.15, 0.85);
             }
             HotkeyAction::NextFocus(change) => {
-                self.state.switch_focus_next(change);
+                self.state
+                    .switch_focus_next(change, self.config.focus_cycle);
             }
             HotkeyAction::NextTag(change) => {
-                self.change_active_tag(
-                    (self.state.active_tag as i16 + change).rem_euclid(9) as usize
-                )?;
+                self.change_active_tag(self.state.get_next_tag(change, self.config.tag_cycle))?;
             }
             HotkeyAction::SwapMaster => {
                 self.state.swap_master();
             }
+            HotkeyAction::Zoom => {
+                self.state.zoom();
+            }
+            HotkeyAction::RotateStack(change) => {
+                self.state.rotate_stack(change);
+            }
+            HotkeyAction::ToggleShowDesktop => {
+                self.set_showing_desktop(!self.state.showing_desktop)?;
+            }
+            HotkeyAction::ToggleMaximize => {
+                let Some(focus) = self.state.get_focus() else {
+                    return Ok(());
+                };
+                let maximized = self
+                    .state
+                    .get_window_state(focus)
+                    .is_some_and(|w| w.group == WindowGroup::Maximized);
+                self.set_maximized(focus, !maximized)?;
+            }
+            HotkeyAction::AdjustGaps(change) => {
+                let tiling = &mut self.state.tiling;
+                tiling.inner_gap = (tiling.inner_gap as i16 + change).clamp(0, 1000) as u16;
+                tiling.outer_gap = (tiling.outer_gap as i16 + change).clamp(0, 1000) as u16;
+                self.update_workarea()?;
+            }
+            HotkeyAction::AdjustOpacity(change) => {
+                let Some(focus) = self.state.get_focus() else {
+                    return Ok(());
+                };
+                let opacity = self.opacities.get(&focus).copied().unwrap_or(1.0);
+                let opacity = (opacity + change).clamp(MIN_OPACITY, 1.0);
+                self.opacities.insert(focus, opacity);
+                self.update_opacity(Some(focus))?;
+                let percent = (opacity * 100.0).round() as u32;
+                self.osd.show_level(&self.conn, "opacity", percent)?;
+            }
+            HotkeyAction::ToggleBar => {
+                let visibility = if self.bar.visibility == BarVisibility::Shown {
+                    BarVisibility::Hidden
+                } else {
+                    BarVisibility::Shown
+                };
+                self.set_bar_visibility(visibility)?;
+            }
+            HotkeyAction::ToggleTabbed => {
+                let tag = &mut self.state.tags[self.state.active_tag];
+                tag.layout = if tag.layout == Layout::Tabbed {
+                    Layout::Tile
+                } else {
+                    Layout::Tabbed
+                };
+                let name = tag.layout.name();
+                self.osd.show_text(&self.conn, name)?;
+            }
+            HotkeyAction::ToggleDnd => {
+                self.blocks
+                    .change_level("dunstctl set-paused toggle", &BlockKind::Notifications);
+            }
+            HotkeyAction::Launcher => {
+                let items = crate::menu::desktop_entries(&self.config.terminal);
+                self.menu.open(&self.conn, items)?;
+            }
+            HotkeyAction::FindWindow => {
+                let items = self.window_entries();
+                self.menu.open(&self.conn, items)?;
+            }
+            HotkeyAction::SetTheme(name) => self.set_theme(&name)?,
+            HotkeyAction::Volume(command) => {
+                self.blocks.change_level(&command, &BlockKind::Volume);
+            }
+            HotkeyAction::Brightness(command) => {
+                self.blocks.change_level(&command, &BlockKind::Backlight);
+            }
+            #[cfg(feature = "native-backlight")]
+            HotkeyAction::ChangeBrightness(change) => {
+                match crate::blocks::change_brightness(change) {
+                    Ok(brightness) => {
+                        self.bar.dirty |= self.blocks.refresh(&BlockKind::Backlight);
+                        self.osd.show_level(&self.conn, "brightness", brightness)?;
+                    }
+                    Err(error) => log::error!("couldn't change the brightness: {error}"),
+                }
+            }
+            HotkeyAction::ToggleAlwaysOnTop => {
+                let Some(focus) = self.state.get_focus() else {
+                    return Ok(());
+                };
+                let above = self
+                    .state
+                    .get_window_state(focus)
+                    .is_some_and(|w| w.layer == Layer::Above);
+                self.set_layer(focus, if above { Layer::Normal } else { Layer::Above })?;
+            }
         }
         self.refresh()?;
         Ok(())
@@ -204,7 +605,13 @@ impl<C: Connection> EventHandler<'_, C> {
     /// Handles an `EnterNotfiyEvent`.
     ///
     /// Handles enters from window to window and window to root. Also refreshes the display.
+    ///
+    /// Entering the edge of the screen reveals the bar in autohide mode.
     fn handle_enter(&mut self, event: EnterNotifyEvent) -> Res {
+        if event.event == self.bar.edge.window {
+            return self.set_bar_revealed(true);
+        }
+
         log::trace!(
             "event enter child {} detail {:?} event {}",
             event.child,
@@ -222,9 +629,25 @@ impl<C: Connection> EventHandler<'_, C> {
         Ok(())
     }
 
+    /// Handles a `LeaveNotifyEvent`.
+    ///
+    /// The pointer leaving the bar hides the window preview, and hides the bar again in autohide mode.
+    fn handle_leave(&mut self, event: LeaveNotifyEvent) -> Res {
+        if event.event == self.bar.bar.window {
+            self.preview.hide(&self.conn)?;
+        }
+        if event.event != self.bar.bar.window
+            || event.mode != NotifyMode::NORMAL
+            || self.bar.visibility != BarVisibility::Autohide
+        {
+            return Ok(());
+        }
+        self.set_bar_revealed(false)
+    }
+
     /// Handles a `ConfigureRequestEvent`.
     ///
-    /// Only configures the window if it exists in the state.
+    /// Managed windows are only moved and resized if they're floating, other windows are told where they stay with a synthetic `ConfigureNotifyEvent`. Asking to be raised or lowered restacks their frame within its stacking layer. Windows that aren't managed (e.g. clients sizing their windows before mapping them) get exactly what they asked for, as per ICCCM.
     fn handle_config(&mut self, event: ConfigureRequestEvent) -> Res {
         log::trace!(
             "event config window {} x {} y {} w {} h {}",
@@ -234,32 +657,168 @@ impl<C: Connection> EventHandler<'_, C> {
             event.width,
             event.height
         );
-        if let Some(state) = self.state.get_mut_window_state(event.window)
-            && state.group == WindowGroup::Floating
-        {
+        let Some(state) = self.state.get_mut_window_state(event.window) else {
+            return self.conn.configure_unmanaged(event);
+        };
+        let geometry = [
+            ConfigWindow::X,
+            ConfigWindow::Y,
+            ConfigWindow::WIDTH,
+            ConfigWindow::HEIGHT,
+        ];
+        let moves = geometry.into_iter().any(|f| event.value_mask.contains(f));
+        if state.group == WindowGroup::Floating && moves {
             self.conn.handle_config(event, state)?;
+        } else {
+            self.conn.send_configure_notify(state)?;
+        }
+        if event.value_mask.contains(ConfigWindow::STACK_MODE) {
+            let sibling = event
+                .value_mask
+                .contains(ConfigWindow::SIBLING)
+                .then_some(event.sibling);
+            self.state
+                .restack_window(event.window, sibling, event.stack_mode);
+            self.restack()?;
         }
         Ok(())
     }
 
+    /// Handles a `ResizeRequestEvent`, sent when a managed client resizes its own window inside its frame.
+    ///
+    /// Floating windows get the size they asked for, with their frame resized along with them. Other windows are kept at the size their frame gives them.
+    fn handle_resize_request(&mut self, event: ResizeRequestEvent) -> Res {
+        log::trace!(
+            "event resize window {} w {} h {}",
+            event.window,
+            event.width,
+            event.height
+        );
+        let (title_bars, title_height) = (self.config.title_bars, self.config.title_height);
+        let Some(window) = self.state.get_mut_window_state(event.window) else {
+            return Ok(());
+        };
+        if window.group == WindowGroup::Floating {
+            (window.width, window.height) = window.hints.fit_aspect(event.width, event.height);
+            window.height += window.title_height(title_bars, title_height);
+        }
+        self.conn.config_window_from_state(window)
+    }
+
     /// Handles a `ClientMessageEvent`.
     ///
     /// A client message is made up of a window and message data, usually containing atoms, meant to change the appearance or behaviour of a window.
     ///
-    /// Currently only the fullscreen request message is handled.
+    /// Currently ping replies, close requests, move/resize requests and fullscreen requests are handled.
     fn handle_client_message(&mut self, event: ClientMessageEvent) -> Res {
         let data = event.data.as_data32();
 
         log::trace!("got client data {data:?}");
+        if event.type_ == self.conn.atoms().wm_protocols && data[0] == self.conn.atoms().net_wm_ping
+        {
+            log::trace!("got ping reply from {}", data[2]);
+            self.pings.remove(&data[2]);
+            return Ok(());
+        }
+        if event.type_ == self.conn.atoms().net_close_window {
+            log::trace!("got close request for {}", event.window);
+            if self.state.get_window_state(event.window).is_some() {
+                self.close_window(event.window)?;
+            }
+            return Ok(());
+        }
+        if event.type_ == self.conn.atoms().net_showing_desktop {
+            self.set_showing_desktop(data[0] != 0)?;
+            return self.refresh();
+        }
+        if event.type_ == self.conn.atoms().net_current_desktop {
+            log::trace!("got request to switch to tag {}", data[0]);
+            if (data[0] as usize) < self.state.tags.len() {
+                self.change_active_tag(data[0] as usize)?;
+            }
+            return self.refresh();
+        }
+        if event.type_ == self.conn.atoms().net_wm_desktop {
+            log::trace!("got request to move {} to tag {}", event.window, data[0]);
+            if (data[0] as usize) < self.state.tags.len() {
+                self.move_window(event.window, data[0] as usize)?;
+            }
+            return self.refresh();
+        }
+        if event.type_ == self.conn.atoms().net_wm_moveresize {
+            return self.start_drag(event.window, data[0] as i16, data[1] as i16, data[2]);
+        }
+        let maximize_atoms = [
+            self.conn.atoms().net_wm_state_maximized_vert,
+            self.conn.atoms().net_wm_state_maximized_horz,
+        ];
+        if event.type_ == self.conn.atoms().net_wm_state
+            && (maximize_atoms.contains(&data[1]) || maximize_atoms.contains(&data[2]))
+        {
+            let Some(state) = self.state.get_window_state(event.window) else {
+                return Ok(());
+            };
+            let maximize = match data[0] {
+                0 => false,
+                1 => true,
+                2 => state.group != WindowGroup::Maximized,
+                _ => return Ok(()),
+            };
+            self.set_maximized(state.window, maximize)?;
+            return self.refresh();
+        }
+        let layer_atoms = [
+            self.conn.atoms().net_wm_state_above,
+            self.conn.atoms().net_wm_state_below,
+        ];
+        if event.type_ == self.conn.atoms().net_wm_state
+            && (layer_atoms.contains(&data[1]) || layer_atoms.contains(&data[2]))
+        {
+            let Some(state) = self.state.get_window_state(event.window) else {
+                return Ok(());
+            };
+            let requested = if data[1] == self.conn.atoms().net_wm_state_above
+                || data[2] == self.conn.atoms().net_wm_state_above
+            {
+                Layer::Above
+            } else {
+                Layer::Below
+            };
+            let layer = match (data[0], state.layer == requested) {
+                (0 | 2, true) => Layer::Normal,
+                (0, false) => state.layer,
+                (1, _) | (2, false) => requested,
+                _ => return Ok(()),
+            };
+            self.set_layer(state.window, layer)?;
+            return self.refresh();
+        }
+        let attention = self.conn.atoms().net_wm_state_demands_attention;
+        if event.type_ == self.conn.atoms().net_wm_state
+            && (data[1] == attention || data[2] == attention)
+        {
+            let Some(state) = self.state.get_mut_window_state(event.window) else {
+                return Ok(());
+            };
+            state.urgent = match data[0] {
+                0 => false,
+                1 => true,
+                2 => !state.urgent,
+                _ => return Ok(()),
+            };
+            log::trace!("setting urgency of {} to {}", state.window, state.urgent);
+            self.conn.net_update_state(state)?;
+            return self.refresh();
+        }
         if data[1] == 0 {
             return Ok(());
         }
 
-        let Ok(event_type) = self.conn.atoms.get_atom_name(event.type_) else {
+        let Ok(event_type) = self.conn.get_atom_name(event.type_) else {
             return Ok(());
         };
 
-        let Ok(first_property) = self.conn.atoms.get_atom_name(data[1]) else {
+        let Ok(first_property) = self.conn.get_atom_name(data[1]) else {
             return Ok(());
         };
 
@@ -279,13 +838,25 @@ impl<C: Connection> EventHandler<'_, C> {
             let window = state.window;
             match data[0] {
                 0 => {
-                    log::trace!("setting group of {window} to stack");
-                    state.group = WindowGroup::Stack;
+                    if let Some((group, x, y, width, height)) = state.restore.take() {
+                        log::trace!("restoring group of {window} to {group:?}");
+                        state.group = group;
+                        if group == WindowGroup::Floating {
+                            (state.x, state.y, state.width, state.height) = (x, y, width, height);
+                        }
+                    } else {
+                        log::trace!("setting group of {window} to stack");
+                        state.group = WindowGroup::Stack;
+                    }
                     self.conn.remove_fullscreen(state)?;
                     self.refresh()?;
                 }
                 1 => {
                     log::trace!("setting group of {window} to fullscreen");
+                    if state.group != WindowGroup::Fullscreen {
+                        state.restore =
+                            Some((state.group, state.x, state.y, state.width, state.height));
+                    }
                     state.group = WindowGroup::Fullscreen;
                     self.conn.set_fullscreen(state)?;
                     self.refresh()?;
@@ -297,34 +868,650 @@ impl<C: Connection> EventHandler<'_, C> {
         Ok(())
     }
 
+    /// Maximizes a window to the work area, or puts it back into the tiling groups.
+    ///
+    /// Vertical and horizontal maximization are not tracked separately, so a window is always maximized in both directions. Nothing happens if the window is already in the requested state.
+    fn set_maximized(&mut self, window: Window, maximize: bool) -> Res {
+        let Some(state) = self.state.get_mut_window_state(window) else {
+            return Ok(());
+        };
+        if maximize == (state.group == WindowGroup::Maximized) {
+            return Ok(());
+        }
+
+        if maximize {
+            log::trace!("setting group of {window} to maximized");
+            let was_fullscreen = state.group == WindowGroup::Fullscreen;
+            state.group = WindowGroup::Maximized;
+            if was_fullscreen {
+                state.restore = None;
+                self.conn.remove_fullscreen(state)?;
+            }
+        } else {
+            log::trace!("setting group of {window} to stack");
+            state.group = WindowGroup::Stack;
+        }
+        self.conn.net_update_state(state)
+    }
+
+    /// Keeps a window above or below other windows, or puts it back in the stacking layer of its group.
+    fn set_layer(&mut self, window: Window, layer: Layer) -> Res {
+        let Some(state) = self.state.get_mut_window_state(window) else {
+            return Ok(());
+        };
+        log::trace!("setting layer of {window} to {layer:?}");
+        state.layer = layer;
+        self.conn.net_update_state(state)
+    }
+
+    /// Starts an interactive move or resize of a floating window, as requested by a `_NET_WM_MOVERESIZE` message.
+    ///
+    /// The pointer is grabbed until its button is released. Keyboard driven directions are not supported, and the cancel direction ends any drag in progress.
+    fn start_drag(&mut self, window: Window, x: i16, y: i16, direction: u32) -> Res {
+        let Some(direction) = DragDirection::from_net(direction) else {
+            log::trace!("unsupported move/resize direction {direction}");
+            return self.end_drag();
+        };
+        let Some(state) = self.state.get_window_state(window) else {
+            return Ok(());
+        };
+        if state.group != WindowGroup::Floating || self.state.drag.is_some() {
+            return Ok(());
+        }
+
+        log::trace!("starting drag {direction:?} of window {window}");
+        let drag = Drag {
+            window: state.window,
+            direction,
+            pointer: (x, y),
+            origin: (state.x, state.y, state.width, state.height),
+        };
+        if self.conn.grab_pointer()? {
+            self.state.drag = Some(drag);
+        }
+        Ok(())
+    }
+
+    /// Ends the interactive move or resize in progress, releasing the pointer.
+    ///
+    /// The dragged window's geometry is remembered for its application.
+    fn end_drag(&mut self) -> Res {
+        let Some(drag) = self.state.drag.take() else {
+            return Ok(());
+        };
+        log::trace!("ending drag");
+        self.conn.ungrab_pointer()?;
+        if let Some(w) = self.state.get_window_state(drag.window)
+            && w.group == WindowGroup::Floating
+        {
+            let class = self.conn.get_window_class(w.window).unwrap_or_default();
+            self.places.remember(&class, (w.x, w.y, w.width, w.height));
+        }
+        Ok(())
+    }
+
+    /// Handles a `MotionNotifyEvent`.
+    ///
+    /// Moves or resizes the dragged window to follow the pointer. Moved windows snap to nearby edges.
+    ///
+    /// Without a drag, moving the pointer over the bar previews the window under it.
+    fn handle_motion(&mut self, event: MotionNotifyEvent) -> Res {
+        let Some(drag) = self.state.drag else {
+            if event.event == self.bar.bar.window {
+                return self.update_preview(event.event_x);
+            }
+            return Ok(());
+        };
+        let Some(state) = self.state.get_window_state(drag.window) else {
+            return self.end_drag();
+        };
+        let (x, y, width, height) = drag.geometry(event.root_x, event.root_y, &state.hints);
+        let (x, y) = if drag.direction == DragDirection::Move {
+            self.state.snap_position(drag.window, x, y, width, height)
+        } else {
+            (x, y)
+        };
+        let Some(state) = self.state.get_mut_window_state(drag.window) else {
+            return self.end_drag();
+        };
+        state.x = x;
+        state.y = y;
+        state.width = width;
+        state.height = height;
+        self.state.validate_geometry();
+        let Some(state) = self.state.get_window_state(drag.window) else {
+            return Ok(());
+        };
+        self.conn.config_window_from_state(state)
+    }
+
+    /// Previews the window under the pointer on the bar at the specified x coordinate.
+    ///
+    /// A tag's square previews the focused window of the tag, unless it's the active tag. A taskbar entry previews its window, taking a fresh snapshot since the window is shown.
+    fn update_preview(&mut self, x: i16) -> Res {
+        let window = match self.bar.get_tag_at(x) {
+            Some(tag) if tag != self.state.active_tag => self.state.tags[tag].focus,
+            Some(_) => None,
+            None => self.bar.get_entry_window(x),
+        };
+        let Some(window) = window else {
+            return self.preview.hide(&self.conn);
+        };
+        if self.preview.shown == Some(window) {
+            return Ok(());
+        }
+        if self
+            .state
+            .get_active_tag_windows()
+            .iter()
+            .any(|w| w.window == window)
+            && let Err(error) = self.preview.snapshot(&self.conn, window)
+        {
+            log::debug!("couldn't take a snapshot of window {window}: {error}");
+        }
+        self.preview.show(&self.conn, window, x, &self.bar.bar)
+    }
+
+    /// Handles a `ButtonPressEvent`.
+    ///
+    /// Clicking a window's entry on the bar in taskbar mode focuses the window.
+    ///
+    /// Clicking a window's title bar focuses the window, or closes it if the close button was clicked.
+    fn handle_button_press(&mut self, event: ButtonPressEvent) -> Res {
+        self.user_time = event.time;
+        log::trace!("button {} pressed on {}", event.detail, event.event);
+        if let Some(state) = self.state.get_window_state(event.event)
+            && state.frame_window == event.event
+        {
+            let height = state.title_height(self.config.title_bars, self.config.title_height);
+            if event.event_y >= height as i16 {
+                return Ok(());
+            }
+            let window = state.window;
+            if BarPainter::is_on_close_button(state, event.event_x, event.event_y, height) {
+                return self.close_window(window);
+            }
+            self.state.tags[self.state.active_tag].focus = Some(window);
+            return self.refresh();
+        }
+        if event.event != self.bar.bar.window {
+            return Ok(());
+        }
+        let Some(window) = self.bar.get_entry_window(event.event_x) else {
+            return Ok(());
+        };
+        self.state.tags[self.state.active_tag].focus = Some(window);
+        self.refresh()
+    }
+
+    /// Handles a `ShapeNotifyEvent`.
+    ///
+    /// The frame window is reshaped to match the client's new shape.
+    fn handle_shape_notify(&self, event: NotifyEvent) -> Res {
+        let Some(window) = self.state.get_window_state(event.affected_window) else {
+            return Ok(());
+        };
+        if window.window != event.affected_window {
+            return Ok(());
+        }
+        self.conn.apply_shape(window)
+    }
+
+    /// Handles an `ExposeEvent`.
+    ///
+    /// Redraws the title bar of the exposed frame, if it has one.
+    fn handle_expose(&self, event: ExposeEvent) -> Res {
+        let Some(window) = self.state.get_window_state(event.window) else {
+            return Ok(());
+        };
+        if window.frame_window != event.window {
+            return Ok(());
+        }
+        self.draw_title_bar(window)
+    }
+
+    /// Draws the title bar of a window, if it has one.
+    fn draw_title_bar(&self, window: &WindowState) -> Res {
+        let height = window.title_height(self.config.title_bars, self.config.title_height);
+        if height == 0 {
+            return Ok(());
+        }
+        let title = self.conn.get_window_name(window.window).unwrap_or_default();
+        let focused_tag =
+            (self.state.get_focus() == Some(window.window)).then_some(self.state.active_tag);
+        self.bar
+            .draw_title_bar(&self.conn, window, &title, height, focused_tag)
+    }
+
+    /// Handles a `ButtonReleaseEvent`.
+    ///
+    /// Releasing the pointer button ends the drag in progress.
+    fn handle_button_release(&mut self, event: ButtonReleaseEvent) -> Res {
+        log::trace!("button {} released", event.detail);
+        self.end_drag()
+    }
+
     /// Refreshes the state and status bar.
     ///
     /// This function does a laundry list of tasks:
     /// - Sets the focus using the focus set in state
     /// - Tiles windows using state
     /// - Configures every window in a tag
-    /// - Draws the status bar
+    /// - Restacks the windows and the status bar
+    /// - Publishes the managed windows
+    /// - Marks the status bar to be redrawn once the event queue is drained
     /// - Logs the state
     fn refresh(&mut self) -> Res {
+        self.clear_focus_urgency()?;
         self.refresh_focus()?;
         self.state.refresh();
+        let focus = self.state.get_focus();
+        if focus != self.focused {
+            let unfocused = self.focused;
+            self.focused = focus;
+            tolerate_vanished(self.update_opacity(unfocused))?;
+            tolerate_vanished(self.update_opacity(focus))?;
+            self.run_hook(&self.config.hooks.focus_change, focus);
+        }
         self.config_tag()?;
-        self.draw_bar();
+        self.restack()?;
+        self.update_client_lists()?;
+        self.bar.dirty = true;
         self.state.log_state();
         Ok(())
     }
 
+    /// Sets the opacity of a window's frame: the opacity set with hotkeys while it's focused, or the opacity of its rule while it isn't, if it has one.
+    fn update_opacity(&self, window: Option<Window>) -> Res {
+        let Some((_, state)) = window.and_then(|w| self.state.find_window(w)) else {
+            return Ok(());
+        };
+        let opacity = self.opacities.get(&state.window).copied().unwrap_or(1.0);
+        let opacity = if self.focused == Some(state.window) || self.config.opacity.is_empty() {
+            opacity
+        } else {
+            let class = self.conn.get_window_class(state.window).unwrap_or_default();
+            rules::find_opacity_rule(&self.config.opacity, &class).map_or(opacity, |r| r.unfocused)
+        };
+        self.conn.set_opacity(state, opacity)
+    }
+
+    /// Clears the urgency of the focused window, as it has gotten the user's attention.
+    fn clear_focus_urgency(&mut self) -> Res {
+        let Some(focus) = self.state.get_focus() else {
+            return Ok(());
+        };
+        let Some(state) = self.state.get_mut_window_state(focus) else {
+            return Ok(());
+        };
+        if !state.urgent {
+            return Ok(());
+        }
+        state.urgent = false;
+        self.conn.net_update_state(state)
+    }
+
+    /// Restacks the frame windows of the active tag according to their stacking layers.
+    ///
+    /// The bars are placed above tiled and `Below` windows, but below every other window. In autohide mode the bar and the edge revealing it are placed above every window except `Fullscreen` windows, as the bar is shown over them.
+    fn restack(&self) -> Res {
+        let windows = self.state.get_active_stacking_order();
+        let autohide = self.bar.visibility == BarVisibility::Autohide;
+        let split = if autohide {
+            windows.partition_point(|w| w.group != WindowGroup::Fullscreen)
+        } else {
+            windows.partition_point(|w| !w.is_above_bar())
+        };
+        let frames = windows[..split]
+            .iter()
+            .map(|w| w.frame_window)
+            .chain(iter::once(self.bar.bar.frame_window))
+            .chain(self.secondary_bars.iter().map(|b| b.bar.frame_window))
+            .chain(autohide.then_some(self.bar.edge.window))
+            .chain(windows[split..].iter().map(|w| w.frame_window))
+            .collect::<Vec<Window>>();
+        self.conn.restack(&frames)
+    }
+
+    /// Sets whether the bar is shown, re-tiling windows so that they take up or free its area.
+    ///
+    /// In autohide mode, windows take up the bar's area and the bar is hidden until revealed.
+    /// # Errors
+    /// Returns an error if the bar can't be shown or hidden.
+    pub fn set_bar_visibility(&mut self, visibility: BarVisibility) -> Res {
+        self.bar.visibility = visibility;
+        self.state.tiling.bar_height = if visibility == BarVisibility::Shown {
+            self.bar.bar.height
+        } else {
+            0
+        };
+        self.set_bar_revealed(visibility == BarVisibility::Shown)?;
+        self.update_workarea()?;
+        self.refresh()
+    }
+
+    /// Applies the config section of the first connected output that has one, preferring the primary output, and refreshes.
+    ///
+    /// Parameters the section doesn't override, or all of them if no output has a section, fall back to their global values. At startup, the section's tag is also switched to. This should be called once after becoming the window manager, and again whenever the outputs change.
+    /// # Errors
+    /// Returns an error if the outputs can't be listed or the bar can't be shown or hidden.
+    pub fn apply_monitor_config(&mut self, startup: bool) -> Res {
+        let outputs = self.conn.get_outputs()?;
+        let monitor = outputs.iter().find_map(|name| {
+            self.config
+                .monitors
+                .get(name)
+                .map(|m| (name.clone(), m.clone()))
+        });
+        let monitor = if let Some((name, monitor)) = monitor {
+            log::info!("applying config of monitor {name}");
+            monitor
+        } else {
+            MonitorConfig::default()
+        };
+
+        let spacing = monitor.spacing;
+        self.state.tiling.inner_gap = monitor
+            .inner_spacing
+            .or(spacing)
+            .unwrap_or(self.config.inner_spacing) as u16;
+        self.state.tiling.outer_gap = monitor
+            .outer_spacing
+            .or(spacing)
+            .unwrap_or(self.config.outer_spacing) as u16;
+        if monitor.ratio.is_some() || !startup {
+            let ratio = monitor.ratio.unwrap_or(self.config.ratio);
+            self.state.tiling.ratio = ratio;
+            for tag in &mut self.state.tags {
+                tag.ratio = ratio;
+            }
+        }
+        if startup && let Some(tag) = monitor.tag {
+            self.change_active_tag(tag - 1)?;
+        }
+        self.set_bar_visibility(monitor.bar_visibility.unwrap_or(self.config.bar_visibility))
+    }
+
+    /// Switches the colors, and the font if the theme has one, to those of a named theme, redrawing the borders, bar and frames.
+    ///
+    /// Logs an error if no theme has the specified name.
+    /// # Errors
+    /// Returns an error if the colors can't be allocated or the font can't be loaded.
+    fn set_theme(&mut self, name: &str) -> Res {
+        let Some(theme) = self.config.themes.get(name).cloned() else {
+            log::error!("no theme named {name}");
+            return Ok(());
+        };
+        self.config.apply_theme(&theme);
+        self.conn.set_colors(&self.config)?;
+
+        let colors = self.conn.colors().clone();
+        self.bar.set_theme(&self.conn, &colors, &self.config)?;
+        self.osd.set_theme(&self.conn, &colors, &self.config)?;
+        self.menu.set_theme(&self.conn, &colors, &self.config)?;
+        self.state
+            .tags
+            .iter()
+            .flat_map(|t| &t.windows)
+            .try_for_each(|w| tolerate_vanished(self.conn.update_frame_colors(w)))?;
+        self.conn.update_frame_colors(&self.bar.bar)?;
+        for bar in &mut self.secondary_bars {
+            bar.set_theme(&self.conn, &colors, &self.config)?;
+            self.conn.update_frame_colors(&bar.bar)?;
+        }
+
+        self.osd.show_text(&self.conn, name)?;
+        self.refresh()
+    }
+
+    /// Shows or hides the bar without re-tiling windows.
+    ///
+    /// In autohide mode, the edge revealing the bar is mapped while the bar is hidden.
+    fn set_bar_revealed(&mut self, revealed: bool) -> Res {
+        if revealed {
+            self.conn.show(&self.bar.bar)?;
+            self.bar.dirty = true;
+        } else {
+            self.conn.hide(&self.bar.bar)?;
+        }
+        if self.bar.visibility == BarVisibility::Autohide && !revealed {
+            self.conn.show(&self.bar.edge)?;
+        } else {
+            self.conn.hide(&self.bar.edge)?;
+        }
+        self.restack()
+    }
+
+    /// Publishes every managed window across all tags, both in mapping and stacking order.
+    fn update_client_lists(&self) -> Res {
+        self.conn.net_update_client_list(&self.state.clients)?;
+        self.conn
+            .net_update_client_list_stacking(&self.state.get_stacking_order())
+    }
+
+    /// Handles a key press while the menu is open, typing into it and acting on the chosen item.
+    fn handle_menu_key(&mut self, event: KeyPressEvent) -> Res {
+        let Some(keysym) = self.key.get_keysym(event) else {
+            return Ok(());
+        };
+        match self.menu.handle_key(keysym) {
+            MenuResult::Continue => self.menu.draw(&self.conn),
+            MenuResult::Close => self.menu.close(&self.conn),
+            MenuResult::Choose(item) => {
+                self.menu.close(&self.conn)?;
+                match item {
+                    MenuItem::Command(command) => {
+                        self.spawn_on_active_tag(&command, Vec::new(), None);
+                        Ok(())
+                    }
+                    MenuItem::Window(window) => self.jump_to_window(window),
+                }
+            }
+        }
+    }
+
+    /// Lists the windows of every tag as window finder items, labelled with their tag, class and title.
+    fn window_entries(&self) -> Vec<(String, MenuItem)> {
+        self.state
+            .tags
+            .iter()
+            .enumerate()
+            .flat_map(|(tag, t)| t.windows.iter().map(move |w| (tag, w.window)))
+            .map(|(tag, window)| {
+                let class = self.conn.get_window_class(window).unwrap_or_default();
+                let title = self.conn.get_window_name(window).unwrap_or_default();
+                (
+                    format!("{}: {class} | {title}", tag + 1),
+                    MenuItem::Window(window),
+                )
+            })
+            .collect()
+    }
+
+    /// Switches to the tag of a window and focuses it. Does nothing if the window was closed in the meantime.
+    fn jump_to_window(&mut self, window: Window) -> Res {
+        let Some((tag, _)) = self.state.find_window(window) else {
+            return Ok(());
+        };
+        log::trace!("jumping to window {window} on tag {tag}");
+        self.change_active_tag(tag)?;
+        self.state.tags[tag].focus = Some(window);
+        self.refresh()
+    }
+
+    /// Spawns a command, remembering the active tag so that the command's first window opens on it.
+    ///
+    /// The active tag (1-9) and the focused window's id are given to the command in `HEMATITE_TAG` and `HEMATITE_WINDOW`, on top of the specified environment variables. Spawns that haven't opened a window within the timeout are forgotten.
+    fn spawn_on_active_tag(
+        &mut self,
+        command: &str,
+        mut envs: Vec<(String, String)>,
+        cwd: Option<&str>,
+    ) {
+        self.spawns.retain(|_, (_, t)| t.elapsed() < SPAWN_TIMEOUT);
+        let id = self.spawn_count;
+        self.spawn_count += 1;
+        self.spawns
+            .insert(id, (self.state.active_tag, Instant::now()));
+        envs.extend([
+            (crate::connection::SPAWN_ID_VAR.to_owned(), id.to_string()),
+            (
+                String::from("HEMATITE_TAG"),
+                (self.state.active_tag + 1).to_string(),
+            ),
+            (
+                String::from("HEMATITE_WINDOW"),
+                self.state.get_focus().unwrap_or_default().to_string(),
+            ),
+        ]);
+        crate::connection::spawn_command_with(command, &envs, cwd);
+    }
+
+    /// Runs a hook's command with details about the active tag and the window (if any) in its environment.
+    fn run_hook(&self, command: &str, window: Option<Window>) {
+        if command.is_empty() {
+            return;
+        }
+        let mut envs = vec![(
+            String::from("HEMATITE_TAG"),
+            (self.state.active_tag + 1).to_string(),
+        )];
+        if let Some(window) = window {
+            envs.extend([
+                (String::from("HEMATITE_WINDOW"), window.to_string()),
+                (
+                    String::from("HEMATITE_CLASS"),
+                    self.conn.get_window_class(window).unwrap_or_default(),
+                ),
+                (
+                    String::from("HEMATITE_TITLE"),
+                    self.conn.get_window_name(window).unwrap_or_default(),
+                ),
+            ]);
+        }
+        log::debug!("running hook {command}");
+        crate::connection::spawn_command_with(command, &envs, None);
+    }
+
+    /// Gets the tag the command owning a window was spawned from, if the window is the command's first window.
+    ///
+    /// The process is found with `_NET_WM_PID`, and its spawn id is read from its environment.
+    fn take_spawn_tag(&mut self, window: Window) -> Option<usize> {
+        let pid = self.conn.get_window_pid(window).ok()??;
+        let id = crate::connection::get_spawn_id(pid)?;
+        let (tag, spawned) = self.spawns.remove(&id)?;
+        log::trace!("window {window} belongs to a command spawned on tag {tag}");
+        (spawned.elapsed() < SPAWN_TIMEOUT).then_some(tag)
+    }
+
+    /// Gives the tags their layouts and ratios of the last session, then adopts the windows already on the screen, such as the clients of a previous window manager.
+    ///
+    /// Adopted windows are managed like newly mapped ones, so they go back to their tags of the last session.
+    ///
+    /// This should be called once, after becoming the window manager.
+    /// # Errors
+    /// Returns an error if the windows on the screen can't be listed or adopted.
+    pub fn restore_session(&mut self) -> Res {
+        for (tag, saved) in self.state.tags.iter_mut().zip(self.restore.saved_tags()) {
+            tag.layout = saved.layout;
+            tag.ratio = saved.ratio;
+        }
+        for window in self.conn.get_top_level_windows()? {
+            let bar = iter::once(&self.bar)
+                .chain(&self.secondary_bars)
+                .any(|b| b.bar.window == window);
+            if bar || self.conn.is_unmanaged(window).unwrap_or(true) {
+                continue;
+            }
+            log::trace!("adopting window {window}");
+            // reparenting a mapped window unmaps it
+            *self.pending_unmaps.entry(window).or_default() += 1;
+            tolerate_vanished(self.handle_map_request(MapRequestEvent {
+                response_type: MAP_REQUEST_EVENT,
+                sequence: 0,
+                parent: self.conn.get_root(),
+                window,
+            }))?;
+        }
+        self.refresh()
+    }
+
+    /// Logs the hotkeys that were skipped as their key couldn't be resolved, and warns about them on the OSD.
+    ///
+    /// # Errors
+    /// Returns an error if the OSD can't be shown.
+    pub fn report_skipped_hotkeys(&mut self) -> Res {
+        if self.key.skipped.is_empty() {
+            return Ok(());
+        }
+        for hotkey in &self.key.skipped {
+            log::warn!("skipped hotkey {hotkey}");
+        }
+        let count = self.key.skipped.len();
+        let text = if count == 1 {
+            String::from("1 hotkey skipped, see the log")
+        } else {
+            format!("{count} hotkeys skipped, see the log")
+        };
+        self.osd.show_warning(&self.conn, &text)
+    }
+
+    /// Runs the commands run every time hematite starts managing windows.
+    ///
+    /// This should be called after becoming the window manager, including after reconnecting.
+    pub fn exec_always(&self) {
+        for command in &self.config.exec_always {
+            log::info!("running {command}");
+            crate::connection::spawn_command(command);
+        }
+    }
+
+    /// Reserves the edges of the screen taken by the secondary bars, the same way docks reserve theirs.
+    ///
+    /// This should be called once, before windows are tiled.
+    pub fn reserve_secondary_bars(&mut self) {
+        for bar in &self.secondary_bars {
+            let struts = if bar.bar.y == 0 {
+                Struts {
+                    top: bar.bar.height,
+                    ..Struts::default()
+                }
+            } else {
+                Struts {
+                    bottom: bar.bar.height,
+                    ..Struts::default()
+                }
+            };
+            self.state.add_dock(bar.bar.window, struts);
+        }
+    }
+
+    /// Publishes the area windows can be placed in.
+    ///
+    /// This should be called whenever the bar or the gaps change.
+    /// # Errors
+    /// Returns an error if the root window's properties can't be changed.
+    pub fn update_workarea(&self) -> Res {
+        self.conn.net_update_workarea(self.state.get_workarea())
+    }
+
     /// Refreshes the displayed focus.
     ///
     /// If no window is focused the root window obtains the focus.
     fn refresh_focus(&self) -> Res {
+        if self.state.showing_desktop {
+            return self.conn.set_focus_to_root();
+        }
         match self.state.tags[self.state.active_tag].focus {
             Some(w) => {
                 let Some(window) = self.state.get_window_state(w) else {
                     return Ok(());
                 };
-                self.conn
-                    .set_focus_window(self.state.get_active_tag_windows(), window)?;
+                self.conn.set_focus_window(
+                    self.state.get_active_tag_windows(),
+                    window,
+                    self.conn.colors().focused[self.state.active_tag],
+                )?;
             }
             None => {
                 self.conn.set_focus_to_root()?;
@@ -335,34 +1522,74 @@ impl<C: Connection> EventHandler<'_, C> {
 
     /// Switches the display from one tag to another, unmapping the old tag and mapping the new.
     ///
-    /// Only switching between two different tags is permitted.
+    /// Only switching between two different tags is permitted. Sticky windows follow the switch, unless they're fullscreen.
     fn change_active_tag(&mut self, tag: usize) -> Res {
         if self.state.active_tag == tag {
             log::trace!("tried switching to already active tag");
             return Ok(());
         }
         log::trace!("changing tag to {tag}");
+        self.set_showing_desktop(false)?;
+        if let Some(focus) = self.state.get_focus()
+            && let Err(error) = self.preview.snapshot(&self.conn, focus)
+        {
+            log::debug!("couldn't take a snapshot of window {focus}: {error}");
+        }
+        self.state.move_sticky_windows(self.state.active_tag, tag);
         self.unmap_tag()?;
         self.state.active_tag = tag;
+        // the new tag is stacked before it's mapped, so a fullscreen window of the old tag never shows over it
+        self.restack()?;
         self.map_tag()?;
         self.conn.net_update_active_desktop(tag as u32)?;
+        self.osd.show_text(&self.conn, &(tag + 1).to_string())?;
+        self.run_hook(&self.config.hooks.tag_change, None);
         Ok(())
     }
 
+    /// Hides or shows the active tag's windows to show the desktop, and lets other clients know about it.
+    ///
+    /// Windows are hidden by unmapping their frames, so they stay managed while hidden.
+    fn set_showing_desktop(&mut self, showing: bool) -> Res {
+        if self.state.showing_desktop == showing {
+            return Ok(());
+        }
+        log::trace!("setting showing desktop to {showing}");
+        self.state.showing_desktop = showing;
+        self.state
+            .get_active_tag_windows()
+            .iter()
+            .try_for_each(|w| {
+                tolerate_vanished(if showing {
+                    self.conn.hide(w)
+                } else {
+                    self.conn.show(w)
+                })
+            })?;
+        self.conn.net_update_showing_desktop(showing)
+    }
+
     /// Maps a tag's windows to the display.
     fn map_tag(&self) -> Res {
         self.state
             .get_active_tag_windows()
             .iter()
-            .try_for_each(|w| self.conn.map(w))
+            .try_for_each(|w| tolerate_vanished(self.conn.map(w)))
     }
 
     /// Unmaps a tag's windows from the display.
-    fn unmap_tag(&self) -> Res {
+    fn unmap_tag(&mut self) -> Res {
         self.state
             .get_active_tag_windows()
+            .clone()
             .iter()
-            .try_for_each(|w| self.conn.unmap(w))
+            .try_for_each(|w| tolerate_vanished(self.unmap_client(w)))
+    }
+
+    /// Unmaps a window and its frame, remembering that the resulting `UnmapNotifyEvent` doesn't mean the client withdrew the window.
+    fn unmap_client(&mut self, window: &WindowState) -> Res {
+        *self.pending_unmaps.entry(window.window).or_default() += 1;
+        self.conn.unmap(window)
     }
 
     /// Configures a tag's windows with their state.
@@ -370,47 +1597,501 @@ impl<C: Connection> EventHandler<'_, C> {
         self.state
             .get_active_tag_windows()
             .iter()
-            .try_for_each(|w| self.conn.config_window_from_state(w))
+            .try_for_each(|w| tolerate_vanished(self.conn.config_window_from_state(w)))
     }
 
-    /// Moves the focused window from one tag to another.
+    /// Moves a window of the active tag to another tag, along with the other windows of its group.
     ///
     /// Only moving to a different tag is permitted.
-    fn move_window(&mut self, tag: usize) -> Res {
+    fn move_window(&mut self, window: Window, tag: usize) -> Res {
         if self.state.active_tag == tag {
             log::trace!("tried moving window to already active tag");
             return Ok(());
         }
         log::trace!("moving window to tag {tag}");
 
-        let focus_window = self.conn.get_focus()?;
-
-        let state = match self.state.get_window_state(focus_window) {
+        let state = match self.state.get_window_state(window) {
             Some(s) => *s,
             None => return Ok(()),
         };
-        self.conn.unmap(&state)?;
-
-        self.state.tags[tag].windows.push(state);
-        self.state.tags[self.state.active_tag]
-            .windows
-            .retain(|w| w.window != focus_window);
+        let moved = self
+            .state
+            .get_active_tag_windows()
+            .iter()
+            .filter(|w| w.window == state.window || w.is_grouped_with(&state))
+            .copied()
+            .collect::<Vec<WindowState>>();
+        for w in &moved {
+            tolerate_vanished(self.unmap_client(w))?;
+            self.state.tags[tag].windows.push(*w);
+            self.state.tags[self.state.active_tag]
+                .windows
+                .retain(|s| s.window != w.window);
+            self.conn.net_update_window_desktop(w.window, tag as u32)?;
+        }
         self.state.set_tag_focus_to_master();
 
-        self.conn
-            .net_update_window_desktop(focus_window, self.state.active_tag as u32)?;
+        Ok(())
+    }
+
+    /// Asks a window to close.
+    ///
+    /// If the window was already asked to close recently, its client is killed instead.
+    ///
+    /// If the client doesn't advertise the `WM_DELETE_WINDOW` protocol in `WM_PROTOCOLS`, it can't be closed gracefully, so the user is told on the OSD that closing it again will kill it.
+    ///
+    /// Clients supporting `_NET_WM_PING` are also pinged, and killed on the next tick after the timeout if they haven't answered.
+    fn close_window(&mut self, window: Window) -> Res {
+        if self
+            .closing
+            .get(&window)
+            .is_some_and(|t| t.elapsed() < KILL_TIMEOUT)
+        {
+            log::debug!("window {window} was already asked to close, killing it");
+            self.closing.remove(&window);
+            return self.conn.kill_client(window);
+        }
+
+        let protocols = self.conn.get_protocols(window)?;
+        if !protocols.contains(&self.conn.atoms().wm_delete_window) {
+            log::debug!("window {window} doesn't support deletion, waiting for confirmation");
+            self.closing.insert(window, Instant::now());
+            return self.osd.show_text(&self.conn, "close again to kill");
+        }
+
+        self.conn.kill_focus(window)?;
+        self.closing.insert(window, Instant::now());
+        if protocols.contains(&self.conn.atoms().net_wm_ping) {
+            self.conn.ping(window)?;
+            self.pings.insert(window, Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Kills the clients of windows that didn't answer a ping in time.
+    fn kill_unresponsive_windows(&mut self) -> Res {
+        let unresponsive = self
+            .pings
+            .iter()
+            .filter(|(_, t)| t.elapsed() >= KILL_TIMEOUT)
+            .map(|(w, _)| *w)
+            .collect::<Vec<Window>>();
+
+        unresponsive.into_iter().try_for_each(|w| {
+            log::debug!("window {w} didn't answer ping, killing it");
+            self.pings.remove(&w);
+            self.closing.remove(&w);
+            tolerate_vanished(self.conn.kill_client(w))
+        })
+    }
 
+    /// Handles the periodic tick sent from the main loop.
+    ///
+    /// Unresponsive windows are killed, idle windows are parked, status blocks are refreshed, stale icons are evicted, the battery is checked, idle commands are run, the session is saved if it changed and the bar is redrawn.
+    pub fn tick(&mut self) {
+        if let Err(error) = self.kill_unresponsive_windows() {
+            log::error!("{error}");
+        }
+        if let Err(error) = self.park_idle_windows() {
+            log::error!("{error}");
+        }
+        self.blocks.update();
+        self.bar.cache.evict_icons();
+        if let Err(error) = self.osd.hide_if_expired(&self.conn) {
+            log::error!("{error}");
+        }
+        if let Err(error) = self.show_levels() {
+            log::error!("{error}");
+        }
+        let warning = match self.battery.check() {
+            Some(BatteryWarning::Low(capacity)) => Some(format!("battery low {capacity}%")),
+            Some(BatteryWarning::Critical(capacity)) => {
+                Some(format!("battery critical {capacity}%"))
+            }
+            None => None,
+        };
+        if let Some(warning) = warning
+            && let Err(error) = self.osd.show_warning(&self.conn, &warning)
+        {
+            log::error!("{error}");
+        }
+        if let Err(error) = self.check_idle() {
+            log::error!("{error}");
+        }
+        self.save_session();
+        self.draw_bar();
+    }
+
+    /// Saves the tags and windows to the session file, if they changed since the last save.
+    pub fn save_session(&mut self) {
+        self.restore.update(&self.state);
+        self.restore.save();
+    }
+
+    /// Shows the levels read after the commands of the volume, brightness and do not disturb hotkeys finished.
+    fn show_levels(&mut self) -> Res {
+        for level in self.blocks.take_levels() {
+            match level {
+                Level::Volume(Some((_, true))) => self.osd.show_text(&self.conn, "muted")?,
+                Level::Volume(Some((volume, false))) => {
+                    self.osd.show_level(&self.conn, "volume", volume)?;
+                }
+                Level::Brightness(Some(brightness)) => {
+                    self.osd.show_level(&self.conn, "brightness", brightness)?;
+                }
+                Level::Notifications(Some((true, _))) => {
+                    self.osd.show_text(&self.conn, "do not disturb")?;
+                }
+                Level::Notifications(Some((false, _))) => {
+                    self.osd.show_text(&self.conn, "notifications on")?;
+                }
+                Level::Volume(None) | Level::Brightness(None) | Level::Notifications(None) => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the idle commands whose threshold the time since the last input crossed.
+    ///
+    /// If configured, the user doesn't go idle while the active tag has a fullscreen window.
+    fn check_idle(&mut self) -> Res {
+        if !self.idle.is_enabled() {
+            return Ok(());
+        }
+        let inhibited = self.config.idle_inhibit == IdleInhibit::Fullscreen
+            && self
+                .state
+                .get_active_tag_windows()
+                .iter()
+                .any(|w| w.group == WindowGroup::Fullscreen);
+        let idle = if inhibited {
+            Duration::ZERO
+        } else {
+            self.conn.get_idle_time()?
+        };
+        self.idle.update(idle, &self.session);
         Ok(())
     }
 
+    /// Moves windows that haven't been focused for the configured amount of hours to the parking tag, and sends a notification about it.
+    fn park_idle_windows(&mut self) -> Res {
+        if self.config.parking_hours == 0 {
+            return Ok(());
+        }
+        let parking_tag = self.config.parking_tag;
+        // the focused window is in use even if the focus never moved away from it
+        self.state.touch_focus();
+        let parked = self.state.park_idle_windows(
+            parking_tag,
+            Duration::from_secs(self.config.parking_hours * 3600),
+        );
+        if parked.is_empty() {
+            return Ok(());
+        }
+
+        parked.iter().try_for_each(|(tag, w)| {
+            log::debug!("parking window {} from tag {tag}", w.window);
+            if *tag == self.state.active_tag {
+                self.unmap_client(w)?;
+            }
+            if parking_tag == self.state.active_tag {
+                self.conn.map(w)?;
+            }
+            self.conn
+                .net_update_window_desktop(w.window, parking_tag as u32)
+        })?;
+
+        if !self.config.parking_notify_command.is_empty() {
+            crate::connection::spawn_command_with(
+                &self.config.parking_notify_command,
+                &[
+                    ("HEMATITE_PARKED".to_owned(), parked.len().to_string()),
+                    ("HEMATITE_TAG".to_owned(), (parking_tag + 1).to_string()),
+                ],
+                None,
+            );
+        }
+        self.refresh()
+    }
+
+    /// Draws the bar if it was marked to be redrawn.
+    pub fn draw_bar_if_dirty(&mut self) {
+        if self.bar.dirty {
+            self.draw_bar();
+        }
+    }
+
+    /// Draws the bar and publishes the status to external bars, logging any errors.
+    ///
+    /// In the tabbed layout, the tiled windows of the active tag are listed on the bar as tabs.
     pub fn draw_bar(&mut self) {
+        self.bar.dirty = false;
+        let tabbed = self.state.tags[self.state.active_tag].layout == Layout::Tabbed;
+        let windows = self
+            .state
+            .get_active_tag_windows()
+            .iter()
+            .filter(|w| !tabbed || w.is_tiled())
+            .map(|w| w.window)
+            .collect::<Vec<Window>>();
+        let layout = format!(
+            "{} {}",
+            self.state.get_layout_symbol(),
+            self.state.get_active_tag_windows().len()
+        );
         if let Err(error) = self.bar.draw_bar(
             self.state.active_tag,
             self.state.get_tag_bitmask(),
             &self.conn,
             self.state.get_focus(),
+            &windows,
+            tabbed,
+            &layout,
+            self.session.indicator(),
+            self.blocks.segments(BlockBar::Main),
         ) {
             log::error!("{error}");
         }
+        let segments = self.blocks.segments(BlockBar::Secondary);
+        for bar in &mut self.secondary_bars {
+            if let Err(error) = bar.draw_status(&self.conn, &segments) {
+                log::error!("{error}");
+            }
+        }
+        for window in self.state.get_active_tag_windows() {
+            if let Err(error) = tolerate_vanished(self.draw_title_bar(window)) {
+                log::error!("{error}");
+            }
+        }
+
+        let title = self
+            .state
+            .get_focus()
+            .and_then(|w| self.conn.get_window_name(w).ok())
+            .unwrap_or_default();
+        self.status
+            .publish(self.state.active_tag, self.state.get_tag_bitmask(), &title);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::protocol::xproto::{
+        CONFIGURE_REQUEST_EVENT, KEY_PRESS_EVENT, KeyButMask, StackMode, UNMAP_NOTIFY_EVENT,
+    };
+
+    use super::*;
+    use crate::{
+        config::ConfigDeserialized,
+        mock::{MOCK_ROOT, MockClient, MockConnection},
+    };
+
+    /// The keycode of the `2` key in the mock keyboard mapping.
+    const KEY_2: u8 = 10;
+
+    /// Creates a handler on a mock connection with the default config, knowing the specified client windows.
+    fn handler(clients: &[Window]) -> EventHandler<MockConnection> {
+        let mut config = Config::from(ConfigDeserialized::default());
+        // no font is loaded, so the tests don't depend on the fonts installed
+        config.font = String::new();
+        let mut conn = MockConnection::new(1920, 1080);
+        for &window in clients {
+            conn.add_client(window, MockClient::default());
+        }
+        // keycodes 8 and 9 are empty, 10 is the `2` key
+        let key = KeyHandler::from_mapping(8, KEY_2, 1, &[0, 0, u32::from(b'2')], &config);
+        let bar = BarPainter::new(&conn, &conn.colors, &config).unwrap();
+        let preview = Preview::new(&conn, &conn.colors, &config).unwrap();
+        let osd = Osd::new(&conn, &conn.colors, &config).unwrap();
+        let menu = Menu::new(&conn, &conn.colors, &config).unwrap();
+        EventHandler {
+            state: StateHandler::new(crate::state::TilingInfo {
+                inner_gap: config.inner_spacing as u16,
+                outer_gap: config.outer_spacing as u16,
+                ratio: config.ratio,
+                max_width: conn.screen.0,
+                max_height: conn.screen.1,
+                bar_height: bar.bar.height,
+                bar_position: config.bar_position,
+                border: config.border_size as u16,
+                smart_gaps: config.smart_gaps,
+                smart_borders: config.smart_borders,
+                snap_distance: config.snap_distance,
+            }),
+            conn,
+            key,
+            bar,
+            secondary_bars: Vec::new(),
+            session: SessionHandler::new(&config),
+            idle: IdleHandler::new(&config),
+            status: StatusServer::disabled(),
+            blocks: BlockHandler::new(&config),
+            battery: BatteryMonitor::new(&config),
+            places: Places::new(),
+            restore: Restore::new(),
+            preview,
+            osd,
+            menu,
+            config,
+            opacities: HashMap::new(),
+            closing: HashMap::new(),
+            pings: HashMap::new(),
+            pending_unmaps: HashMap::new(),
+            spawns: HashMap::new(),
+            spawn_count: 0,
+            focused: None,
+            user_time: 0,
+        }
+    }
+
+    /// Gets a request to map a client window.
+    fn map_request(window: Window) -> Event {
+        Event::MapRequest(MapRequestEvent {
+            response_type: MAP_REQUEST_EVENT,
+            sequence: 0,
+            parent: MOCK_ROOT,
+            window,
+        })
+    }
+
+    /// Gets a notification of a client window being unmapped, sent by the client itself if synthetic.
+    fn unmap_notify(window: Window, synthetic: bool) -> Event {
+        Event::UnmapNotify(UnmapNotifyEvent {
+            response_type: UNMAP_NOTIFY_EVENT | if synthetic { 0x80 } else { 0 },
+            sequence: 0,
+            event: MOCK_ROOT,
+            window,
+            from_configure: false,
+        })
+    }
+
+    /// Gets a press of a key with the specified modifiers.
+    fn key_press(detail: u8, state: KeyButMask) -> Event {
+        Event::KeyPress(KeyPressEvent {
+            response_type: KEY_PRESS_EVENT,
+            detail,
+            sequence: 0,
+            time: 1,
+            root: MOCK_ROOT,
+            event: MOCK_ROOT,
+            child: x11rb::NONE,
+            root_x: 0,
+            root_y: 0,
+            event_x: 0,
+            event_y: 0,
+            state,
+            same_screen: true,
+        })
+    }
+
+    #[test]
+    fn map_request_tiles_and_focuses_window() {
+        let mut handler = handler(&[10, 20]);
+        handler.handle_event(&map_request(10)).unwrap();
+        handler.handle_event(&map_request(20)).unwrap();
+
+        let windows = handler.state.get_active_tag_windows();
+        assert_eq!(windows.len(), 2);
+        assert!(windows.iter().all(WindowState::is_tiled));
+        assert_eq!(handler.state.get_focus(), Some(20));
+        assert!(handler.conn.was_called("add_window 20"));
+        let color = handler.conn.colors().focused[0];
+        assert!(
+            handler
+                .conn
+                .was_called(&format!("set_focus_window 20 {color}"))
+        );
+        assert_eq!(handler.conn.get_focus().unwrap(), 20);
+    }
+
+    #[test]
+    fn unmap_by_manager_is_ignored_but_withdrawal_removes_window() {
+        let mut handler = handler(&[10]);
+        handler.handle_event(&map_request(10)).unwrap();
+        handler.change_active_tag(1).unwrap();
+        assert!(handler.conn.was_called("unmap 10"));
+
+        handler.handle_event(&unmap_notify(10, false)).unwrap();
+        assert!(handler.state.find_window(10).is_some());
+        assert!(!handler.conn.was_called("wm_withdraw_window 10"));
+
+        handler.handle_event(&unmap_notify(10, true)).unwrap();
+        assert!(handler.state.find_window(10).is_none());
+        assert!(handler.conn.was_called("wm_withdraw_window 10"));
+    }
+
+    #[test]
+    fn stacking_request_keeps_floating_window_in_place() {
+        let mut handler = handler(&[10]);
+        handler.conn.clients.get_mut(&10).unwrap().floating = true;
+        handler.handle_event(&map_request(10)).unwrap();
+        let before = *handler.state.get_window_state(10).unwrap();
+        handler.conn.take_calls();
+
+        handler
+            .handle_event(&Event::ConfigureRequest(ConfigureRequestEvent {
+                response_type: CONFIGURE_REQUEST_EVENT,
+                stack_mode: StackMode::ABOVE,
+                sequence: 0,
+                parent: before.frame_window,
+                window: 10,
+                sibling: x11rb::NONE,
+                x: 0,
+                y: 0,
+                width: 100,
+                height: 100,
+                border_width: 0,
+                value_mask: ConfigWindow::STACK_MODE,
+            }))
+            .unwrap();
+        let after = handler.state.get_window_state(10).unwrap();
+        assert_eq!((after.x, after.y), (before.x, before.y));
+        assert!(!handler.conn.was_called("handle_config 10 0 0 100 100"));
+        assert!(handler.conn.was_called("send_configure_notify 10"));
+    }
+
+    #[test]
+    fn closing_window_without_delete_protocol_asks_for_confirmation() {
+        let mut handler = handler(&[10]);
+        handler.handle_event(&map_request(10)).unwrap();
+        handler.conn.take_calls();
+
+        handler.close_window(10).unwrap();
+        let calls = handler.conn.take_calls();
+        assert!(calls.iter().any(|c| c.starts_with("show_popup")));
+        assert!(!calls.contains(&"kill_client 10".to_owned()));
+
+        handler.close_window(10).unwrap();
+        assert!(handler.conn.was_called("kill_client 10"));
+    }
+
+    #[test]
+    fn secondary_bar_reserves_opposite_edge() {
+        let mut handler = handler(&[]);
+        let bar = BarPainter::new_secondary(&handler.conn, &handler.conn.colors, &handler.config)
+            .unwrap();
+        handler.secondary_bars.push(bar);
+        handler.reserve_secondary_bars();
+
+        let bar = &handler.secondary_bars[0].bar;
+        assert_eq!(bar.y, 1080 - bar.height as i16);
+        let (_, y, _, height) = handler.state.get_usable_area();
+        assert_eq!(y, handler.bar.bar.height as i16);
+        assert_eq!(y + height as i16, bar.y);
+    }
+
+    #[test]
+    fn hotkey_switches_tag() {
+        let mut handler = handler(&[10]);
+        handler.handle_event(&map_request(10)).unwrap();
+        handler.conn.take_calls();
+
+        handler
+            .handle_event(&key_press(KEY_2, KeyButMask::MOD4))
+            .unwrap();
+        assert_eq!(handler.state.active_tag, 1);
+        assert!(handler.conn.was_called("net_update_active_desktop 1"));
+        assert!(handler.conn.was_called("unmap 10"));
+        assert_eq!(handler.user_time, 1);
     }
 }
@@ -0,0 +1,84 @@
+//!
+//! This module parses the inline markup scripts can use in the status text to color parts of it.
+//!
+//! `^fg(#RRGGBB)` and `^bg(#RRGGBB)` change the text and background colors of the text after them, and `^fg()` and `^bg()` restore the bar's colors. `^^` draws a single `^`. Markup that can't be parsed is drawn as is.
+use x11rb::protocol::render::Color;
+
+use crate::config::hex_color_to_argb;
+
+/// A run of text drawn with the same colors.
+#[derive(Debug, Clone)]
+pub struct Span {
+    /// The text of the run.
+    pub text: String,
+    /// The color of the text. Uses the bar's foreground color if not set.
+    pub foreground: Option<Color>,
+    /// The color behind the text. Uses the bar's background color if not set.
+    pub background: Option<Color>,
+}
+
+/// Splits text into runs of the same colors, removing the markup.
+#[must_use]
+pub fn parse(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut current = Span {
+        text: String::new(),
+        foreground: None,
+        background: None,
+    };
+    let mut rest = text;
+    while let Some(i) = rest.find('^') {
+        current.text.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("^^") {
+            current.text.push('^');
+            rest = after;
+            continue;
+        }
+        let Some((tag, color, after)) = parse_tag(rest) else {
+            current.text.push('^');
+            rest = &rest[1..];
+            continue;
+        };
+        if !current.text.is_empty() {
+            spans.push(current.clone());
+            current.text.clear();
+        }
+        match tag {
+            "fg" => current.foreground = color,
+            _ => current.background = color,
+        }
+        rest = after;
+    }
+    current.text.push_str(rest);
+    if !current.text.is_empty() {
+        spans.push(current);
+    }
+    spans
+}
+
+/// Gets the text without its markup.
+#[must_use]
+pub fn strip(text: &str) -> String {
+    parse(text).into_iter().map(|s| s.text).collect()
+}
+
+/// Parses a color tag at the start of the text, such as `^fg(#ff0000)`.
+///
+/// Returns the tag's name, its color (`None` for an empty color) and the text after it.
+fn parse_tag(text: &str) -> Option<(&str, Option<Color>, &str)> {
+    let tag = ["fg", "bg"]
+        .into_iter()
+        .find(|t| text[1..].starts_with(&format!("{t}(")))?;
+    let (color, after) = text[tag.len() + 2..].split_once(')')?;
+    if color.is_empty() {
+        return Some((tag, None, after));
+    }
+    let valid = color.starts_with('#')
+        && matches!(color.len(), 7 | 9)
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if !valid {
+        return None;
+    }
+    Some((tag, hex_color_to_argb(color).ok(), after))
+}
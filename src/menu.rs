@@ -0,0 +1,370 @@
+//!
+//! This module provides a keyboard-driven menu, such as the built-in application launcher.
+//!
+//! The menu is a popup in the center of the screen listing its items, filtered by what is typed. Items match if they contain the typed characters in order, ignoring case, and closer matches are listed first. The keyboard is grabbed while the menu is open, so typing never reaches other windows.
+//!
+//! The launcher lists the applications of the `.desktop` files in the XDG data directories (e.g. `/usr/share/applications`), like `rofi -show drun`. The window finder lists the managed windows of every tag by class and title, like `rofi -show window`.
+use std::{collections::HashMap, fs, time::Instant};
+
+use x11rb::protocol::xproto::{Gcontext, Rectangle, Window};
+use xkeysym::Keysym;
+
+use crate::{
+    config::Config,
+    connection::{Colors, ConnectionActionExt, ConnectionStateExt, Res},
+    error::HematiteError,
+    render::ImageHandler,
+    state::{Layer, SizeHints, WindowGroup, WindowState},
+};
+
+/// The maximum number of items shown at once.
+const MAX_ROWS: usize = 10;
+
+/// What happens when a menu item is chosen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuItem {
+    /// Spawns the command.
+    Command(String),
+    /// Switches to the window's tag and focuses the window.
+    Window(Window),
+}
+
+/// The outcome of a key press in the menu.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuResult {
+    /// The menu stays open, and should be redrawn.
+    Continue,
+    /// The menu was closed without choosing an item.
+    Close,
+    /// The item was chosen, and the menu closed.
+    Choose(MenuItem),
+}
+
+/// A helper showing a filterable list of items in a popup.
+pub struct Menu {
+    /// The popup window the menu is drawn to.
+    popup: WindowState,
+    /// The graphics context drawing in the foreground color.
+    gc: Gcontext,
+    /// The graphics context drawing in the background color.
+    inverted_gc: Gcontext,
+    /// The helper rendering the text.
+    image: ImageHandler,
+    /// The items of the menu, with their labels.
+    items: Vec<(String, MenuItem)>,
+    /// The typed text the items are filtered by.
+    query: String,
+    /// The indices of the items matching the query, best matches first.
+    matches: Vec<usize>,
+    /// The position of the selected item in the matches.
+    selected: usize,
+    /// Whether the menu is shown.
+    open: bool,
+}
+
+impl Menu {
+    /// Creates a new helper, creating its (unmapped) popup window.
+    /// # Errors
+    /// Returns an error if the font can't be loaded or the popup window can't be created.
+    pub fn new(
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        colors: &Colors,
+        config: &Config,
+    ) -> Result<Self, HematiteError> {
+        let image = ImageHandler::new(config, !conn.has_argb_visual())?;
+        let window = conn.generate_id()?;
+        let popup = WindowState {
+            window,
+            frame_window: window,
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+            group: WindowGroup::Floating,
+            layer: Layer::Above,
+            sticky: false,
+            urgent: false,
+            decorated: false,
+            last_focus: Instant::now(),
+            hints: SizeHints::default(),
+            restore: None,
+            leader: None,
+        };
+        conn.create_popup_window(&popup)?;
+
+        let gc = conn.generate_id()?;
+        let inverted_gc = conn.generate_id()?;
+        conn.create_gc(gc, window, colors.bar_background, colors.bar_foreground)?;
+        conn.create_gc(
+            inverted_gc,
+            window,
+            colors.bar_foreground,
+            colors.bar_background,
+        )?;
+        Ok(Self {
+            popup,
+            gc,
+            inverted_gc,
+            image,
+            items: Vec::new(),
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            open: false,
+        })
+    }
+
+    /// Switches to the colors and font of the config, such as after switching themes.
+    /// # Errors
+    /// Returns an error if the font can't be loaded or the graphics contexts don't exist.
+    pub fn set_theme(
+        &mut self,
+        conn: &impl ConnectionActionExt,
+        colors: &Colors,
+        config: &Config,
+    ) -> Res {
+        self.image = ImageHandler::new(config, !conn.has_argb_visual())?;
+        conn.change_gc(self.gc, colors.bar_background, colors.bar_foreground)?;
+        conn.change_gc(
+            self.inverted_gc,
+            colors.bar_foreground,
+            colors.bar_background,
+        )?;
+        Ok(())
+    }
+
+    /// Whether the menu is shown.
+    #[must_use]
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Shows the menu with the specified items, grabbing the keyboard.
+    ///
+    /// The menu isn't shown if the keyboard can't be grabbed.
+    /// # Errors
+    /// Returns an error if the popup can't be shown or drawn to.
+    pub fn open(
+        &mut self,
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        items: Vec<(String, MenuItem)>,
+    ) -> Res {
+        if !conn.grab_keyboard()? {
+            return Ok(());
+        }
+        self.items = items;
+        self.query.clear();
+        self.filter();
+        self.open = true;
+
+        let line = self.image.metrics.height * 2;
+        let (screen_width, screen_height) = conn.get_screen_geometry();
+        self.popup.width = screen_width / 3;
+        self.popup.height = line as u16 * (MAX_ROWS as u16 + 1);
+        self.popup.x = (screen_width - self.popup.width) as i16 / 2;
+        self.popup.y = screen_height.saturating_sub(self.popup.height) as i16 / 3;
+        conn.show_popup(&self.popup)?;
+        self.draw(conn)
+    }
+
+    /// Hides the menu and releases the keyboard.
+    /// # Errors
+    /// Returns an error if the popup can't be hidden.
+    pub fn close(&mut self, conn: &(impl ConnectionActionExt + ConnectionStateExt)) -> Res {
+        self.open = false;
+        self.items.clear();
+        conn.hide(&self.popup)?;
+        conn.ungrab_keyboard()
+    }
+
+    /// Handles a key press: `Escape` closes the menu, `Return` chooses the selected item, the arrow keys and `Tab` move the selection, `BackSpace` deletes the last typed character, and any other character is typed.
+    pub fn handle_key(&mut self, keysym: Keysym) -> MenuResult {
+        match keysym {
+            Keysym::Escape => return MenuResult::Close,
+            Keysym::Return | Keysym::KP_Enter => {
+                return self
+                    .matches
+                    .get(self.selected)
+                    .map_or(MenuResult::Close, |&i| {
+                        MenuResult::Choose(self.items[i].1.clone())
+                    });
+            }
+            Keysym::Down | Keysym::Tab => {
+                self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1));
+            }
+            Keysym::Up | Keysym::ISO_Left_Tab => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            Keysym::BackSpace => {
+                self.query.pop();
+                self.filter();
+            }
+            _ => {
+                if let Some(c) = keysym.key_char().filter(|c| !c.is_control()) {
+                    self.query.push(c);
+                    self.filter();
+                }
+            }
+        }
+        MenuResult::Continue
+    }
+
+    /// Draws the typed text followed by the matching items, with the selected item highlighted.
+    /// # Errors
+    /// Returns an error if the popup can't be drawn to.
+    pub fn draw(&self, conn: &impl ConnectionActionExt) -> Res {
+        let line = self.image.metrics.height as i16 * 2;
+        let padding = line / 4;
+        let width = self.popup.width as i16 - padding * 2;
+        conn.fill_rectangle(
+            self.popup.window,
+            self.inverted_gc,
+            Rectangle {
+                x: 0,
+                y: 0,
+                width: self.popup.width,
+                height: self.popup.height,
+            },
+        )?;
+        self.draw_text(conn, &format!("> {}", self.query), 0, width, false)?;
+
+        let first = self.selected.saturating_sub(MAX_ROWS - 1);
+        for (row, (position, &i)) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_ROWS)
+            .enumerate()
+        {
+            let y = line * (row as i16 + 1);
+            let selected = position == self.selected;
+            if selected {
+                conn.fill_rectangle(
+                    self.popup.window,
+                    self.gc,
+                    Rectangle {
+                        x: 0,
+                        y,
+                        width: self.popup.width,
+                        height: line as u16,
+                    },
+                )?;
+            }
+            self.draw_text(conn, &self.items[i].0, y, width, selected)?;
+        }
+        Ok(())
+    }
+
+    /// Draws a line of text in the row starting at the specified y coordinate, truncated to the specified width. Inverted text is drawn on the highlight of the selected item.
+    fn draw_text(
+        &self,
+        conn: &impl ConnectionActionExt,
+        text: &str,
+        y: i16,
+        width: i16,
+        inverted: bool,
+    ) -> Res {
+        let line = self.image.metrics.height as i16 * 2;
+        let text = self.image.truncate_text(text, width);
+        let (foreground, background) = if inverted {
+            (self.image.colors.background, self.image.colors.foreground)
+        } else {
+            (self.image.colors.foreground, self.image.colors.background)
+        };
+        let (image, offset_x, offset_y) = self.image.rasterize_text(&text, foreground, background);
+        if image.width == 0 || image.height == 0 {
+            return Ok(());
+        }
+        conn.draw_to_pixmap(
+            self.popup.window,
+            self.gc,
+            line / 4 + offset_x,
+            y + line / 2 + self.image.metrics.height as i16 / 2 + offset_y,
+            image.width as u16,
+            image.height as u16,
+            &image.data,
+        )
+    }
+
+    /// Finds the items matching the query, best matches first, and selects the first one.
+    fn filter(&mut self) {
+        let mut matches = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (label, _))| fuzzy_score(label, &self.query).map(|s| (s, i)))
+            .collect::<Vec<(usize, usize)>>();
+        matches.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then_with(|| self.items[a.1].0.cmp(&self.items[b.1].0))
+        });
+        self.matches = matches.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+}
+
+/// Scores how well a label matches a query. The label matches if it contains the characters of the query in order, ignoring case.
+///
+/// Lower scores are better: the score is the number of characters skipped before and between the matched characters.
+fn fuzzy_score(label: &str, query: &str) -> Option<usize> {
+    let label = label.to_lowercase();
+    let mut chars = label.chars().enumerate();
+    let mut score = 0;
+    let mut last = None;
+    for q in query.to_lowercase().chars() {
+        let (i, _) = chars.find(|(_, c)| *c == q)?;
+        score += last.map_or(i, |l| i - l - 1);
+        last = Some(i);
+    }
+    Some(score)
+}
+
+/// Lists the applications of the `.desktop` files in the XDG data directories as launcher items.
+///
+/// Entries that are hidden or aren't applications are skipped. Field codes (e.g. `%U`) are removed from the commands, and applications meant to be run in a terminal are run in the specified terminal.
+#[must_use]
+pub fn desktop_entries(terminal: &str) -> Vec<(String, MenuItem)> {
+    let mut entries = xdg::BaseDirectories::new()
+        .list_data_files_once("applications")
+        .into_iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "desktop"))
+        .filter_map(|p| fs::read_to_string(p).ok())
+        .filter_map(|s| parse_desktop_entry(&s, terminal))
+        .collect::<Vec<(String, MenuItem)>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.dedup_by(|a, b| a.0 == b.0);
+    entries
+}
+
+/// Parses the `[Desktop Entry]` group of a `.desktop` file, getting the application's name and command.
+fn parse_desktop_entry(contents: &str, terminal: &str) -> Option<(String, MenuItem)> {
+    let mut in_entry = false;
+    let mut keys = HashMap::new();
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+        } else if in_entry && let Some((key, value)) = line.split_once('=') {
+            keys.entry(key.trim()).or_insert_with(|| value.trim());
+        }
+    }
+    let is = |key: &str| keys.get(key).is_some_and(|v| *v == "true");
+    if keys.get("Type").is_some_and(|t| *t != "Application") || is("NoDisplay") || is("Hidden") {
+        return None;
+    }
+
+    let name = (*keys.get("Name")?).to_owned();
+    let command = keys
+        .get("Exec")?
+        .split(' ')
+        .filter(|w| !(w.len() == 2 && w.starts_with('%') && *w != "%%"))
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .replace("%%", "%");
+    let command = if is("Terminal") {
+        crate::connection::terminal_command(terminal, &command)
+    } else {
+        command
+    };
+    Some((name, MenuItem::Command(command)))
+}
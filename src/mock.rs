@@ -0,0 +1,576 @@
+//!
+//! This module provides a mock connection, implementing the connection traits without an X11 server.
+//!
+//! Every call made through the traits is recorded as a short string (e.g. `map 5` or `net_set_active_window 5`), so the manager's flows can be checked headlessly by handling events with an `EventHandler` built on a `MockConnection` and looking at the calls it made.
+use core::time::Duration;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use x11rb::protocol::xproto::{Atom, ConfigureRequestEvent, Gcontext, Pixmap, Rectangle, Window};
+
+use crate::{
+    atoms::{ATOM_NAMES, Atoms},
+    config::Config,
+    connection::{Colors, ConnectionActionExt, ConnectionAtomExt, ConnectionStateExt, Id, Res},
+    error::HematiteError,
+    keys::KeyHandler,
+    state::{SizeHints, Struts, WindowState},
+};
+
+/// The root window's id in a mock connection.
+pub const MOCK_ROOT: Window = 1;
+/// The first id given out by a mock connection. Lower ids are free for clients.
+const FIRST_ID: u32 = 0x0100_0000;
+
+/// The properties of a mock client window, returned when the manager asks for them.
+#[derive(Clone, Debug)]
+pub struct MockClient {
+    /// The title of the window.
+    pub name: String,
+    /// The class of the window.
+    pub class: String,
+    /// The role of the window.
+    pub role: String,
+    /// The process id of the window's client.
+    pub pid: Option<u32>,
+    /// The size the window asks for.
+    pub size: (u16, u16),
+    /// The protocols (e.g. `WM_DELETE_WINDOW`) the window supports.
+    pub protocols: Vec<Atom>,
+    /// The size hints of the window.
+    pub hints: SizeHints,
+    /// The group leader of the window.
+    pub leader: Option<Window>,
+    /// The icon of the window, as `_NET_WM_ICON` data.
+    pub icon: Vec<u8>,
+    /// Whether the window should be floating.
+    pub floating: bool,
+    /// Whether the window shouldn't be managed (e.g. override-redirect windows or docks).
+    pub unmanaged: bool,
+    /// Whether the window wants the manager's decorations.
+    pub decorated: bool,
+    /// The space the window reserves if it's a dock, or `None` if it isn't.
+    pub dock: Option<Struts>,
+    /// The time of the last user interaction with the window.
+    pub user_time: Option<u32>,
+}
+
+impl Default for MockClient {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            class: String::new(),
+            role: String::new(),
+            pid: None,
+            size: (100, 100),
+            protocols: Vec::new(),
+            hints: SizeHints::default(),
+            leader: None,
+            icon: Vec::new(),
+            floating: false,
+            unmanaged: false,
+            decorated: true,
+            dock: None,
+            user_time: None,
+        }
+    }
+}
+
+/// A connection that doesn't talk to an X11 server, recording the calls made to it instead.
+///
+/// Atoms get the ids 1 to `ATOM_NAMES.len()` in order, and the colors of unfocused borders, urgent borders, the bar background and the bar foreground are 1 to 4, with each tag's focused and highlight colors after them.
+pub struct MockConnection {
+    /// The ids of the atoms.
+    pub atoms: Atoms,
+    /// The ids of the colors.
+    pub colors: Colors,
+    /// The width and height of the screen.
+    pub screen: (u16, u16),
+    /// The client windows known to the connection.
+    pub clients: HashMap<Window, MockClient>,
+    /// The calls made to the connection, in order.
+    calls: RefCell<Vec<String>>,
+    /// The focused window.
+    focus: Cell<Window>,
+    /// The next id to give out.
+    next_id: Cell<u32>,
+}
+
+impl MockConnection {
+    /// Creates a new mock connection with a screen of the given size and no clients.
+    #[must_use]
+    pub fn new(width: u16, height: u16) -> Self {
+        let atom_nums: Vec<Atom> = (1..=ATOM_NAMES.len() as u32).collect();
+        Self {
+            atoms: Atoms::from_atom_nums(&atom_nums),
+            colors: Colors {
+                unfocused: 1,
+                urgent: 2,
+                bar_background: 3,
+                bar_foreground: 4,
+                focused: (5..14).collect(),
+                tags: (14..23).collect(),
+            },
+            screen: (width, height),
+            clients: HashMap::new(),
+            calls: RefCell::new(Vec::new()),
+            focus: Cell::new(MOCK_ROOT),
+            next_id: Cell::new(FIRST_ID),
+        }
+    }
+
+    /// Adds a client window, which can then be mapped by handling a `MapRequestEvent` for it.
+    pub fn add_client(&mut self, window: Window, client: MockClient) {
+        self.clients.insert(window, client);
+    }
+
+    /// Gets the calls made so far.
+    #[must_use]
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+
+    /// Gets the calls made so far and forgets them, so the next calls can be checked on their own.
+    pub fn take_calls(&self) -> Vec<String> {
+        self.calls.take()
+    }
+
+    /// Returns true if a call was made.
+    #[must_use]
+    pub fn was_called(&self, call: &str) -> bool {
+        self.calls.borrow().iter().any(|c| c == call)
+    }
+
+    /// Records a call.
+    fn record(&self, call: String) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    /// Gets a client window's properties, or the default ones if the window is unknown.
+    fn client(&self, window: Window) -> MockClient {
+        self.clients.get(&window).cloned().unwrap_or_default()
+    }
+}
+
+impl ConnectionStateExt for MockConnection {
+    fn map(&self, window: &WindowState) -> Res {
+        self.record(format!("map {}", window.window));
+        Ok(())
+    }
+
+    fn unmap(&self, window: &WindowState) -> Res {
+        self.record(format!("unmap {}", window.window));
+        Ok(())
+    }
+
+    fn hide(&self, window: &WindowState) -> Res {
+        self.record(format!("hide {}", window.window));
+        Ok(())
+    }
+
+    fn show(&self, window: &WindowState) -> Res {
+        self.record(format!("show {}", window.window));
+        Ok(())
+    }
+
+    fn add_window(&self, window: &WindowState) -> Res {
+        self.record(format!("add_window {}", window.window));
+        Ok(())
+    }
+
+    fn apply_shape(&self, window: &WindowState) -> Res {
+        self.record(format!("apply_shape {}", window.window));
+        Ok(())
+    }
+
+    fn set_opacity(&self, window: &WindowState, opacity: f32) -> Res {
+        self.record(format!("set_opacity {} {opacity}", window.window));
+        Ok(())
+    }
+
+    fn update_frame_colors(&self, window: &WindowState) -> Res {
+        self.record(format!("update_frame_colors {}", window.window));
+        Ok(())
+    }
+
+    fn destroy_frame_window(&self, window: &WindowState) -> Res {
+        self.record(format!("destroy_frame_window {}", window.window));
+        Ok(())
+    }
+
+    fn create_window(&self, window: &WindowState) -> Res {
+        self.record(format!("create_window {}", window.window));
+        Ok(())
+    }
+
+    fn clear_window(&self, window: &WindowState) -> Res {
+        self.record(format!("clear_window {}", window.window));
+        Ok(())
+    }
+
+    fn config_window_from_state(&self, window: &WindowState) -> Res {
+        self.record(format!(
+            "config_window {} {} {} {} {}",
+            window.window, window.x, window.y, window.width, window.height
+        ));
+        Ok(())
+    }
+
+    fn send_configure_notify(&self, window: &WindowState) -> Res {
+        self.record(format!("send_configure_notify {}", window.window));
+        Ok(())
+    }
+
+    fn set_fullscreen(&self, window: &WindowState) -> Res {
+        self.record(format!("set_fullscreen {}", window.window));
+        Ok(())
+    }
+
+    fn remove_fullscreen(&self, window: &WindowState) -> Res {
+        self.record(format!("remove_fullscreen {}", window.window));
+        Ok(())
+    }
+
+    fn restack(&self, windows: &[Window]) -> Res {
+        self.record(format!("restack {windows:?}"));
+        Ok(())
+    }
+
+    fn create_pixmap_from_win(&self, pixmap: Pixmap, window: &WindowState) -> Res {
+        self.record(format!("create_pixmap {pixmap} {}", window.window));
+        Ok(())
+    }
+
+    fn set_focus_window(&self, _windows: &[WindowState], focus: &WindowState, color: Id) -> Res {
+        self.focus.set(focus.window);
+        self.record(format!("set_focus_window {} {color}", focus.window));
+        Ok(())
+    }
+
+    fn copy_window_to_window(
+        &self,
+        _gc: Gcontext,
+        window_1: Window,
+        window_2: &WindowState,
+    ) -> Res {
+        self.record(format!("copy_window {window_1} {}", window_2.window));
+        Ok(())
+    }
+
+    fn handle_config(&self, event: ConfigureRequestEvent, _window: &mut WindowState) -> Res {
+        self.record(format!(
+            "handle_config {} {} {} {} {}",
+            event.window, event.x, event.y, event.width, event.height
+        ));
+        Ok(())
+    }
+}
+
+impl ConnectionActionExt for MockConnection {
+    fn get_focus(&self) -> Result<u32, HematiteError> {
+        Ok(self.focus.get())
+    }
+
+    fn set_focus_to_root(&self) -> Res {
+        self.focus.set(MOCK_ROOT);
+        self.record("set_focus_to_root".to_owned());
+        Ok(())
+    }
+
+    fn kill_focus(&self, focus: Id) -> Res {
+        self.record(format!("kill_focus {focus}"));
+        Ok(())
+    }
+
+    fn ping(&self, window: Window) -> Res {
+        self.record(format!("ping {window}"));
+        Ok(())
+    }
+
+    fn kill_client(&self, window: Window) -> Res {
+        self.record(format!("kill_client {window}"));
+        Ok(())
+    }
+
+    fn get_window_name(&self, window: Window) -> Result<String, HematiteError> {
+        Ok(self.client(window).name)
+    }
+
+    fn get_window_size(&self, window: Window) -> Result<(u16, u16), HematiteError> {
+        Ok(self.client(window).size)
+    }
+
+    fn is_unmanaged(&self, window: Window) -> Result<bool, HematiteError> {
+        Ok(self.client(window).unmanaged)
+    }
+
+    fn map_unmanaged(&self, window: Window) -> Res {
+        self.record(format!("map_unmanaged {window}"));
+        Ok(())
+    }
+
+    fn configure_unmanaged(&self, event: ConfigureRequestEvent) -> Res {
+        self.record(format!(
+            "configure_unmanaged {} {} {} {} {}",
+            event.window, event.x, event.y, event.width, event.height
+        ));
+        Ok(())
+    }
+
+    fn get_top_level_windows(&self) -> Result<Vec<Window>, HematiteError> {
+        Ok(Vec::new())
+    }
+
+    fn create_gc(&self, gc: Id, drawable: Id, _color_background: Id, _color_foreground: Id) -> Res {
+        self.record(format!("create_gc {gc} {drawable}"));
+        Ok(())
+    }
+
+    fn has_argb_visual(&self) -> bool {
+        false
+    }
+
+    fn select_bar_events(&self, window: Window) -> Res {
+        self.record(format!("select_bar_events {window}"));
+        Ok(())
+    }
+
+    fn draw_to_pixmap(
+        &self,
+        _pixmap: Pixmap,
+        _gc: Gcontext,
+        _x: i16,
+        _y: i16,
+        _width: u16,
+        _height: u16,
+        _data: &[u8],
+    ) -> Res {
+        Ok(())
+    }
+
+    fn set_cursor(&self) -> Res {
+        Ok(())
+    }
+
+    fn generate_id(&self) -> Result<u32, HematiteError> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        Ok(id)
+    }
+
+    fn grab_keys(&self, handler: &KeyHandler) -> Res {
+        self.record(format!("grab_keys {}", handler.hotkeys.len()));
+        Ok(())
+    }
+
+    fn grab_pointer(&self) -> Result<bool, HematiteError> {
+        self.record("grab_pointer".to_owned());
+        Ok(true)
+    }
+
+    fn ungrab_pointer(&self) -> Res {
+        self.record("ungrab_pointer".to_owned());
+        Ok(())
+    }
+
+    fn grab_keyboard(&self) -> Result<bool, HematiteError> {
+        self.record("grab_keyboard".to_owned());
+        Ok(true)
+    }
+
+    fn ungrab_keyboard(&self) -> Res {
+        self.record("ungrab_keyboard".to_owned());
+        Ok(())
+    }
+
+    fn get_screen_geometry(&self) -> (u16, u16) {
+        self.screen
+    }
+
+    fn get_root(&self) -> u32 {
+        MOCK_ROOT
+    }
+
+    fn colors(&self) -> &Colors {
+        &self.colors
+    }
+
+    fn set_colors(&mut self, _config: &Config) -> Res {
+        self.record("set_colors".to_owned());
+        Ok(())
+    }
+
+    fn change_gc(&self, gc: Gcontext, _color_background: Id, _color_foreground: Id) -> Res {
+        self.record(format!("change_gc {gc}"));
+        Ok(())
+    }
+
+    fn add_heartbeat_window(&self) -> Res {
+        Ok(())
+    }
+
+    fn create_edge_window(&self, window: &WindowState) -> Res {
+        self.record(format!("create_edge_window {}", window.window));
+        Ok(())
+    }
+
+    fn create_popup_window(&self, window: &WindowState) -> Res {
+        self.record(format!("create_popup_window {}", window.window));
+        Ok(())
+    }
+
+    fn show_popup(&self, window: &WindowState) -> Res {
+        self.record(format!(
+            "show_popup {} {} {} {} {}",
+            window.window, window.x, window.y, window.width, window.height
+        ));
+        Ok(())
+    }
+
+    fn get_idle_time(&self) -> Result<Duration, HematiteError> {
+        Ok(Duration::ZERO)
+    }
+
+    fn get_outputs(&self) -> Result<Vec<String>, HematiteError> {
+        Ok(Vec::new())
+    }
+
+    fn get_window_image(&self, window: Window) -> Result<(u16, u16, Vec<u8>), HematiteError> {
+        self.record(format!("get_window_image {window}"));
+        let (width, height) = self.client(window).size;
+        Ok((
+            width,
+            height,
+            vec![u8::MAX; usize::from(width) * usize::from(height) * 4],
+        ))
+    }
+
+    fn fill_rectangle(&self, _pixmap: Pixmap, _gc: Gcontext, _rect: Rectangle) -> Res {
+        Ok(())
+    }
+}
+
+impl ConnectionAtomExt for MockConnection {
+    fn atoms(&self) -> &Atoms {
+        &self.atoms
+    }
+
+    fn get_atom_name(&self, atom: Atom) -> Result<String, HematiteError> {
+        Ok(ATOM_NAMES
+            .get((atom as usize).wrapping_sub(1))
+            .map(|n| (*n).to_owned())
+            .unwrap_or_default())
+    }
+
+    fn net_add_allowed_actions(&self, window: Window) -> Res {
+        self.record(format!("net_add_allowed_actions {window}"));
+        Ok(())
+    }
+
+    fn net_add_frame_extents(&self, window: Window, border: u32) -> Res {
+        self.record(format!("net_add_frame_extents {window} {border}"));
+        Ok(())
+    }
+
+    fn wm_activate_window(&self, window: Window) -> Res {
+        self.record(format!("wm_activate_window {window}"));
+        Ok(())
+    }
+
+    fn wm_withdraw_window(&self, window: Window) -> Res {
+        self.record(format!("wm_withdraw_window {window}"));
+        Ok(())
+    }
+
+    fn net_set_active_window(&self, window: Window) -> Res {
+        self.record(format!("net_set_active_window {window}"));
+        Ok(())
+    }
+
+    fn net_update_state(&self, window: &WindowState) -> Res {
+        self.record(format!("net_update_state {}", window.window));
+        Ok(())
+    }
+
+    fn net_update_active_desktop(&self, tag: u32) -> Res {
+        self.record(format!("net_update_active_desktop {tag}"));
+        Ok(())
+    }
+
+    fn net_update_showing_desktop(&self, showing: bool) -> Res {
+        self.record(format!("net_update_showing_desktop {showing}"));
+        Ok(())
+    }
+
+    fn net_update_workarea(&self, area: (i16, i16, u16, u16)) -> Res {
+        self.record(format!("net_update_workarea {area:?}"));
+        Ok(())
+    }
+
+    fn net_update_window_desktop(&self, window: Window, tag: u32) -> Res {
+        self.record(format!("net_update_window_desktop {window} {tag}"));
+        Ok(())
+    }
+
+    fn net_update_client_list(&self, windows: &[Window]) -> Res {
+        self.record(format!("net_update_client_list {windows:?}"));
+        Ok(())
+    }
+
+    fn net_update_client_list_stacking(&self, windows: &[Window]) -> Res {
+        self.record(format!("net_update_client_list_stacking {windows:?}"));
+        Ok(())
+    }
+
+    fn get_icon(&self, window: Window) -> Result<Vec<u8>, HematiteError> {
+        Ok(self.client(window).icon)
+    }
+
+    fn should_be_floating(&self, window: Window) -> Result<(u16, u16, bool), HematiteError> {
+        let client = self.client(window);
+        Ok((client.size.0, client.size.1, client.floating))
+    }
+
+    fn get_size_hints(&self, window: Window) -> Result<SizeHints, HematiteError> {
+        Ok(self.client(window).hints)
+    }
+
+    fn get_group_leader(&self, window: Window) -> Result<Option<Window>, HematiteError> {
+        Ok(self.client(window).leader)
+    }
+
+    fn wants_decorations(&self, window: Window) -> Result<bool, HematiteError> {
+        Ok(self.client(window).decorated)
+    }
+
+    fn get_protocols(&self, window: Window) -> Result<Vec<Atom>, HematiteError> {
+        Ok(self.client(window).protocols)
+    }
+
+    fn set_class(&self, class: &str, window: Window) -> Res {
+        self.record(format!("set_class {class} {window}"));
+        Ok(())
+    }
+
+    fn get_window_class(&self, window: Window) -> Result<String, HematiteError> {
+        Ok(self.client(window).class)
+    }
+
+    fn get_window_role(&self, window: Window) -> Result<String, HematiteError> {
+        Ok(self.client(window).role)
+    }
+
+    fn get_window_pid(&self, window: Window) -> Result<Option<u32>, HematiteError> {
+        Ok(self.client(window).pid)
+    }
+
+    fn get_dock_struts(&self, window: Window) -> Result<Option<Struts>, HematiteError> {
+        Ok(self.client(window).dock)
+    }
+
+    fn get_user_time(&self, window: Window) -> Result<Option<u32>, HematiteError> {
+        Ok(self.client(window).user_time)
+    }
+}
@@ -0,0 +1,243 @@
+//!
+//! This module provides the on-screen display (OSD), a popup briefly flashing what a hotkey changed.
+//!
+//! The OSD shows the new tag when switching tags, the new layout when toggling it, and the volume or brightness as a bar when they are changed. It is hidden again on the first tick after the configured timeout, or after a few seconds for warnings such as a low battery.
+use std::time::{Duration, Instant};
+
+use x11rb::protocol::xproto::{Gcontext, Rectangle};
+
+use crate::{
+    config::Config,
+    connection::{Colors, ConnectionActionExt, ConnectionStateExt, Res},
+    error::HematiteError,
+    render::ImageHandler,
+    state::{Layer, SizeHints, WindowGroup, WindowState},
+};
+
+/// The shortest time warnings are shown for.
+const WARNING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A helper showing short-lived messages in a popup at the bottom center of the screen.
+pub struct Osd {
+    /// The popup window messages are drawn to.
+    popup: WindowState,
+    /// The graphics context drawing in the foreground color.
+    gc: Gcontext,
+    /// The graphics context drawing in the background color, used to clear the popup.
+    inverted_gc: Gcontext,
+    /// The helper rendering the text.
+    image: ImageHandler,
+    /// How long a message is shown. The OSD is disabled if zero.
+    timeout: Duration,
+    /// When the current message is hidden, if one is shown.
+    hide_at: Option<Instant>,
+}
+
+impl Osd {
+    /// Creates a new helper, creating its (unmapped) popup window.
+    /// # Errors
+    /// Returns an error if the font can't be loaded or the popup window can't be created.
+    pub fn new(
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        colors: &Colors,
+        config: &Config,
+    ) -> Result<Self, HematiteError> {
+        let image = ImageHandler::new(config, !conn.has_argb_visual())?;
+        let window = conn.generate_id()?;
+        let popup = WindowState {
+            window,
+            frame_window: window,
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+            group: WindowGroup::Floating,
+            layer: Layer::Above,
+            sticky: false,
+            urgent: false,
+            decorated: false,
+            last_focus: Instant::now(),
+            hints: SizeHints::default(),
+            restore: None,
+            leader: None,
+        };
+        conn.create_popup_window(&popup)?;
+
+        let gc = conn.generate_id()?;
+        let inverted_gc = conn.generate_id()?;
+        conn.create_gc(gc, window, colors.bar_background, colors.bar_foreground)?;
+        conn.create_gc(
+            inverted_gc,
+            window,
+            colors.bar_foreground,
+            colors.bar_background,
+        )?;
+        Ok(Self {
+            popup,
+            gc,
+            inverted_gc,
+            image,
+            timeout: Duration::from_secs(config.osd_timeout),
+            hide_at: None,
+        })
+    }
+
+    /// Switches to the colors and font of the config, such as after switching themes.
+    /// # Errors
+    /// Returns an error if the font can't be loaded or the graphics contexts don't exist.
+    pub fn set_theme(
+        &mut self,
+        conn: &impl ConnectionActionExt,
+        colors: &Colors,
+        config: &Config,
+    ) -> Res {
+        self.image = ImageHandler::new(config, !conn.has_argb_visual())?;
+        conn.change_gc(self.gc, colors.bar_background, colors.bar_foreground)?;
+        conn.change_gc(
+            self.inverted_gc,
+            colors.bar_foreground,
+            colors.bar_background,
+        )?;
+        Ok(())
+    }
+
+    /// Shows a line of text, such as the number of the new tag.
+    /// # Errors
+    /// Returns an error if the popup can't be shown or drawn to.
+    pub fn show_text(
+        &mut self,
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        text: &str,
+    ) -> Res {
+        let line = self.image.metrics.height as i16;
+        let width = self.image.get_text_length(text) + line * 2;
+        self.show(conn, width.max(line * 3) as u16, line as u16 * 3)?;
+        self.draw_text(conn, text)
+    }
+
+    /// Shows a labelled level in percent as a bar, such as the volume.
+    /// # Errors
+    /// Returns an error if the popup can't be shown or drawn to.
+    pub fn show_level(
+        &mut self,
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        label: &str,
+        percent: u32,
+    ) -> Res {
+        let line = self.image.metrics.height as i16;
+        let text = format!("{label} {percent}%");
+        let width = (self.image.get_text_length(&text) + line * 2).max(line * 12);
+        self.show(conn, width as u16, line as u16 * 4)?;
+        if self.hide_at.is_none() {
+            return Ok(());
+        }
+        self.draw_text(conn, &text)?;
+
+        let track = Rectangle {
+            x: line,
+            y: line * 5 / 2,
+            width: (width - line * 2) as u16,
+            height: (line / 2).max(3) as u16,
+        };
+        conn.fill_rectangle(self.popup.window, self.gc, track)?;
+        conn.fill_rectangle(
+            self.popup.window,
+            self.inverted_gc,
+            Rectangle {
+                x: track.x + 1,
+                y: track.y + 1,
+                width: track.width.saturating_sub(2),
+                height: track.height.saturating_sub(2),
+            },
+        )?;
+        conn.fill_rectangle(
+            self.popup.window,
+            self.gc,
+            Rectangle {
+                width: (u32::from(track.width) * percent.min(100) / 100) as u16,
+                ..track
+            },
+        )
+    }
+
+    /// Shows a warning, such as a low battery, for longer than other messages.
+    /// # Errors
+    /// Returns an error if the popup can't be shown or drawn to.
+    pub fn show_warning(
+        &mut self,
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        text: &str,
+    ) -> Res {
+        self.show_text(conn, text)?;
+        if let Some(hide_at) = &mut self.hide_at {
+            *hide_at = (*hide_at).max(Instant::now() + WARNING_TIMEOUT);
+        }
+        Ok(())
+    }
+
+    /// Hides the message if it was shown for longer than the timeout.
+    /// # Errors
+    /// Returns an error if the popup can't be hidden.
+    pub fn hide_if_expired(&mut self, conn: &impl ConnectionStateExt) -> Res {
+        if self.hide_at.is_some_and(|h| Instant::now() >= h) {
+            self.hide_at = None;
+            conn.hide(&self.popup)?;
+        }
+        Ok(())
+    }
+
+    /// Resizes the popup, centers it horizontally in the bottom quarter of the screen, and shows it cleared.
+    fn show(
+        &mut self,
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        width: u16,
+        height: u16,
+    ) -> Res {
+        if self.timeout.is_zero() {
+            return Ok(());
+        }
+        let (screen_width, screen_height) = conn.get_screen_geometry();
+        self.popup.width = width;
+        self.popup.height = height;
+        self.popup.x = screen_width.saturating_sub(width) as i16 / 2;
+        self.popup.y = (screen_height / 4 * 3).saturating_sub(height / 2) as i16;
+        conn.show_popup(&self.popup)?;
+        conn.fill_rectangle(
+            self.popup.window,
+            self.inverted_gc,
+            Rectangle {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+        )?;
+        self.hide_at = Some(Instant::now() + self.timeout);
+        Ok(())
+    }
+
+    /// Draws a line of text at the top of the popup.
+    fn draw_text(&self, conn: &impl ConnectionActionExt, text: &str) -> Res {
+        if self.hide_at.is_none() {
+            return Ok(());
+        }
+        let line = self.image.metrics.height as i16;
+        let (image, offset_x, offset_y) = self.image.rasterize_text(
+            text,
+            self.image.colors.foreground,
+            self.image.colors.background,
+        );
+        if image.width == 0 || image.height == 0 {
+            return Ok(());
+        }
+        conn.draw_to_pixmap(
+            self.popup.window,
+            self.gc,
+            line + offset_x,
+            line * 2 + offset_y,
+            image.width as u16,
+            image.height as u16,
+            &image.data,
+        )
+    }
+}
@@ -0,0 +1,77 @@
+//!
+//! This module remembers where floating windows were left, for each application.
+//!
+//! The last geometry a floating window was moved or resized to is stored by window class in `$XDG_STATE_HOME/hematite/floating.toml`, and given to the next floating window of the same class. This way dialogs and small utilities come back where the user left them, even after a restart.
+use std::{collections::HashMap, path::PathBuf};
+
+/// A window's geometry (x, y, width, height).
+pub type Geometry = (i16, i16, u16, u16);
+
+/// A helper that keeps the last floating geometry of each window class.
+pub struct Places {
+    /// The path of the file the geometries are stored in. Is `None` if the file couldn't be placed.
+    path: Option<PathBuf>,
+    /// The last geometry of each window class.
+    geometries: HashMap<String, Geometry>,
+}
+
+impl Places {
+    /// Loads the remembered geometries from the state file.
+    ///
+    /// Errors are logged and leave no geometry remembered.
+    #[must_use]
+    pub fn new() -> Self {
+        let path = xdg::BaseDirectories::with_prefix("hematite")
+            .place_state_file("floating.toml")
+            .inspect_err(|e| log::error!("couldn't place floating geometry file {e:?}"))
+            .ok();
+        let geometries = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| {
+                toml::from_str(&s)
+                    .inspect_err(|e| log::error!("error parsing floating geometry file {e:?}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self { path, geometries }
+    }
+
+    /// Gets the last geometry of a window class, if any.
+    #[must_use]
+    pub fn get(&self, class: &str) -> Option<Geometry> {
+        self.geometries.get(class).copied()
+    }
+
+    /// Remembers the geometry of a window class, saving it to the state file if it changed.
+    ///
+    /// Windows without a class aren't remembered.
+    pub fn remember(&mut self, class: &str, geometry: Geometry) {
+        if class.is_empty() || self.get(class) == Some(geometry) {
+            return;
+        }
+        self.geometries.insert(class.to_owned(), geometry);
+        self.save();
+    }
+
+    /// Writes every remembered geometry to the state file.
+    ///
+    /// May log an error if the file couldn't be written.
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = toml::to_string(&self.geometries)
+            .map_err(|e| e.to_string())
+            .and_then(|s| std::fs::write(path, s).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::error!("couldn't save floating geometry to {}: {e}", path.display());
+        }
+    }
+}
+
+impl Default for Places {
+    fn default() -> Self {
+        Self::new()
+    }
+}
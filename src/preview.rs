@@ -0,0 +1,155 @@
+//!
+//! This module provides the popup previewing windows when the pointer hovers over the bar.
+//!
+//! Hovering a tag's square previews the tag's focused window, and hovering a taskbar entry previews its window. The contents of hidden windows can't be read, so windows are snapshotted while they're shown (e.g. right before their tag is switched away from) and the snapshot is kept until the window is shown again.
+use std::{collections::HashMap, time::Instant};
+
+use image::{ImageBuffer, Rgba, imageops};
+use x11rb::protocol::xproto::{Gcontext, Window};
+
+use crate::{
+    config::Config,
+    connection::{Colors, ConnectionActionExt, ConnectionStateExt, Res},
+    error::HematiteError,
+    render::Image,
+    state::{Layer, SizeHints, WindowGroup, WindowState},
+};
+
+/// A helper showing scaled down snapshots of windows in a popup.
+pub struct Preview {
+    /// The popup window previews are drawn to.
+    pub popup: WindowState,
+    /// The graphics context used to draw to the popup.
+    gc: Gcontext,
+    /// The largest width and height of a preview. Previews are disabled if 0.
+    size: u16,
+    /// The last snapshot of each window, already scaled down.
+    snapshots: HashMap<Window, Image>,
+    /// The window currently previewed, if any.
+    pub shown: Option<Window>,
+}
+
+impl Preview {
+    /// Creates a new helper, creating its (unmapped) popup window.
+    /// # Errors
+    /// Returns an error if the popup window can't be created.
+    pub fn new(
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        colors: &Colors,
+        config: &Config,
+    ) -> Result<Self, HematiteError> {
+        let window = conn.generate_id()?;
+        let popup = WindowState {
+            window,
+            frame_window: window,
+            x: 0,
+            y: 0,
+            width: config.preview_size.max(1),
+            height: config.preview_size.max(1),
+            group: WindowGroup::Floating,
+            layer: Layer::Above,
+            sticky: false,
+            urgent: false,
+            decorated: false,
+            last_focus: Instant::now(),
+            hints: SizeHints::default(),
+            restore: None,
+            leader: None,
+        };
+        conn.create_popup_window(&popup)?;
+
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, window, colors.bar_background, colors.bar_foreground)?;
+        Ok(Self {
+            popup,
+            gc,
+            size: config.preview_size,
+            snapshots: HashMap::new(),
+            shown: None,
+        })
+    }
+
+    /// Takes a snapshot of a shown window and scales it down to the preview size, replacing its previous snapshot.
+    /// # Errors
+    /// Returns an error if the window's contents can't be read, e.g. because it's hidden.
+    pub fn snapshot(&mut self, conn: &impl ConnectionActionExt, window: Window) -> Res {
+        if self.size == 0 {
+            return Ok(());
+        }
+        let (width, height, data) = conn.get_window_image(window)?;
+        let Some(buffer) =
+            ImageBuffer::<Rgba<u8>, _>::from_raw(u32::from(width), u32::from(height), data)
+        else {
+            return Err(HematiteError::Render(format!(
+                "snapshot of window {window} has the wrong size"
+            )));
+        };
+        let scale = (f32::from(self.size) / f32::from(width.max(height).max(1))).min(1.0);
+        let width = ((f32::from(width) * scale).round() as u32).max(1);
+        let height = ((f32::from(height) * scale).round() as u32).max(1);
+        let data = imageops::resize(&buffer, width, height, imageops::FilterType::Triangle);
+        self.snapshots.insert(
+            window,
+            Image {
+                width,
+                height,
+                data: data.into_raw(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Drops the snapshot of a window, such as when it's destroyed.
+    pub fn forget(&mut self, window: Window) {
+        self.snapshots.remove(&window);
+    }
+
+    /// Shows the snapshot of a window next to the bar, centered on the specified x coordinate as far as the screen allows.
+    ///
+    /// The preview is hidden instead if the window has no snapshot.
+    /// # Errors
+    /// Returns an error if the popup can't be shown or drawn to.
+    pub fn show(
+        &mut self,
+        conn: &(impl ConnectionActionExt + ConnectionStateExt),
+        window: Window,
+        x: i16,
+        bar: &WindowState,
+    ) -> Res {
+        let Some(image) = self.snapshots.get(&window) else {
+            return self.hide(conn);
+        };
+        let (screen_width, _) = conn.get_screen_geometry();
+        self.popup.width = image.width as u16;
+        self.popup.height = image.height as u16;
+        self.popup.x = (x - self.popup.width as i16 / 2)
+            .clamp(0, screen_width.saturating_sub(self.popup.width) as i16);
+        self.popup.y = if bar.y == 0 {
+            bar.height as i16
+        } else {
+            bar.y - self.popup.height as i16
+        };
+        conn.show_popup(&self.popup)?;
+        conn.draw_to_pixmap(
+            self.popup.window,
+            self.gc,
+            0,
+            0,
+            self.popup.width,
+            self.popup.height,
+            &image.data,
+        )?;
+        self.shown = Some(window);
+        Ok(())
+    }
+
+    /// Hides the preview, if it's shown.
+    /// # Errors
+    /// Returns an error if the popup can't be hidden.
+    pub fn hide(&mut self, conn: &impl ConnectionStateExt) -> Res {
+        if self.shown.take().is_some() {
+            conn.hide(&self.popup)?;
+        }
+        Ok(())
+    }
+}
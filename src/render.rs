@@ -1,17 +1,25 @@
 //!
 //! This module provides a font helper that rasterizes and paints the specified letters.
-use std::{fs, process::exit};
+//!
+//! Letters missing from the main font are drawn with the first fallback font that has them, or as an empty box if none do.
+use std::fs;
 
-use fontdue::{Font, Metrics};
+use fontdue::{Font, Metrics, OutlineBounds};
 use image::{ImageBuffer, Rgba, imageops};
 
-use crate::config::Config;
-/// The font's foreground and background color.
+use x11rb::protocol::render::Color;
+
+use crate::{config::Config, error::HematiteError};
+
+/// The text appended to truncated text.
+const ELLIPSIS: &str = "\u{2026}";
+
+/// The font's foreground and background color, as RGBA.
 pub struct Colors {
     /// This determines the text's color.
-    pub foreground: (u8, u8, u8),
+    pub foreground: (u8, u8, u8, u8),
     /// This determines the text's background.
-    pub background: (u8, u8, u8),
+    pub background: (u8, u8, u8, u8),
 }
 
 /// An image with width, height, and data.
@@ -26,8 +34,8 @@ pub struct Image {
 
 /// A helper for drawing with fonts and manipulating image byte sequences.
 pub struct ImageHandler {
-    /// The font provided in configuration.
-    font: Font,
+    /// The fonts provided in configuration, with the main font first and the fallback fonts after it.
+    fonts: Vec<Font>,
     /// The metrics (width, height) of the char 'A'.
     pub metrics: Metrics,
     /// The colors of the font to draw with.
@@ -36,60 +44,155 @@ pub struct ImageHandler {
 
 impl ImageHandler {
     /// Creates a new helper.
-    #[must_use]
-    pub fn new(config: &Config) -> Self {
-        let font = match get_font_file(&config.font) {
-            Ok(f) => f,
-            Err(e) => {
-                log::error!("couldnt open font! {e}");
-                exit(0);
-            }
-        };
+    ///
+    /// If `opaque` is set, the alpha of the configured colors is ignored. Fallback fonts that can't be loaded are skipped.
+    ///
+    /// If the main font's path is empty, no font is loaded and every letter is drawn as an empty box, such as in headless tests.
+    /// # Errors
+    /// Returns an error if the main font can't be loaded.
+    pub fn new(config: &Config, opaque: bool) -> Result<Self, HematiteError> {
+        let (fonts, metrics) = if config.font.is_empty() {
+            let size = config.font_size as usize;
+            let metrics = Metrics {
+                width: size / 2,
+                height: size,
+                advance_width: (size / 2) as f32,
+                ..Metrics::default()
+            };
+            (Vec::new(), metrics)
+        } else {
+            let font = get_font_file(&config.font)?;
+
+            let metrics = font.metrics('A', config.font_size as f32);
 
-        let metrics = font.metrics('A', config.font_size as f32);
+            let fallbacks = config
+                .fallback_fonts
+                .iter()
+                .filter_map(|path| get_font_file(path).inspect_err(|e| log::error!("{e}")).ok());
+            (std::iter::once(font).chain(fallbacks).collect(), metrics)
+        };
 
-        Self {
-            font,
+        Ok(Self {
+            fonts,
             metrics,
             colors: Colors {
-                foreground: (
-                    (config.main_color.red / 257) as u8,
-                    (config.main_color.green / 257) as u8,
-                    (config.main_color.blue / 257) as u8,
-                ),
-                background: (
-                    (config.secondary_color.red / 257) as u8,
-                    (config.secondary_color.green / 257) as u8,
-                    (config.secondary_color.blue / 257) as u8,
-                ),
+                foreground: color_to_rgba(config.bar_foreground_color, opaque),
+                background: color_to_rgba(config.bar_background_color, opaque),
             },
-        }
+        })
     }
 
-    /// Creates a BGRA byte array out of a letter.
+    /// Creates a premultiplied BGRA image out of a line of text, so that it can be drawn at once.
+    ///
+    /// Also returns the offset (x, y) of the image's top left corner from the start of the text's baseline.
     #[must_use]
-    pub fn rasterize_letter(
+    pub fn rasterize_text(
         &self,
-        c: char,
-        foreground: (u8, u8, u8),
-        background: (u8, u8, u8),
-    ) -> (Metrics, Vec<u8>) {
-        let (metrics, bytes) = self.font.rasterize(c, self.metrics.height as f32);
-        let mut data: Vec<u8> = vec![0u8; metrics.width * metrics.height * 4];
-        bytes.iter().enumerate().for_each(|(i, &a)| {
-            let j = i * 4;
-            data[j] = alpha_interpolate(foreground.2, background.2, a);
-            data[j + 1] = alpha_interpolate(foreground.1, background.1, a);
-            data[j + 2] = alpha_interpolate(foreground.0, background.0, a);
-            data[j + 3] = 0xFF;
-        });
-        (metrics, data)
+        text: &str,
+        foreground: (u8, u8, u8, u8),
+        background: (u8, u8, u8, u8),
+    ) -> (Image, i16, i16) {
+        let mut pen = 0;
+        let glyphs = text
+            .chars()
+            .map(|c| {
+                let (metrics, coverage) = self.rasterize_letter(c);
+                let x = pen + metrics.xmin;
+                pen += metrics.advance_width as i32;
+                (x, metrics, coverage)
+            })
+            .collect::<Vec<(i32, Metrics, Vec<u8>)>>();
+
+        let left = glyphs.iter().map(|g| g.0).min().unwrap_or(0).min(0);
+        let right = glyphs
+            .iter()
+            .map(|g| g.0 + g.1.width as i32)
+            .max()
+            .unwrap_or(0)
+            .max(pen);
+        let top = glyphs
+            .iter()
+            .map(|g| g.1.height as i32 + g.1.ymin)
+            .max()
+            .unwrap_or(0);
+        let bottom = glyphs.iter().map(|g| g.1.ymin).min().unwrap_or(0).min(0);
+        let (width, height) = ((right - left) as usize, (top - bottom).max(0) as usize);
+
+        let mut coverage = vec![0u8; width * height];
+        for (x, metrics, glyph) in &glyphs {
+            let offset_x = (x - left) as usize;
+            let offset_y = (top - metrics.height as i32 - metrics.ymin) as usize;
+            for (i, &a) in glyph.iter().enumerate() {
+                let j = (offset_y + i / metrics.width) * width + offset_x + i % metrics.width;
+                coverage[j] = coverage[j].max(a);
+            }
+        }
+
+        let image = Image {
+            width: width as u32,
+            height: height as u32,
+            data: colorize(&coverage, foreground, background),
+        };
+        (image, left as i16, -top as i16)
+    }
+
+    /// Rasterizes a letter into its metrics and coverage, using the first font that has it.
+    fn rasterize_letter(&self, c: char) -> (Metrics, Vec<u8>) {
+        match self.get_font(c) {
+            Some(font) => font.rasterize(c, self.metrics.height as f32),
+            None => self.rasterize_missing(),
+        }
     }
 
     /// Gets the metrics of the specified letter.
     #[must_use]
     pub fn get_metrics(&self, c: char) -> Metrics {
-        self.font.metrics(c, self.metrics.height as f32)
+        match self.get_font(c) {
+            Some(font) => font.metrics(c, self.metrics.height as f32),
+            None => self.rasterize_missing().0,
+        }
+    }
+
+    /// Gets the first font that has the specified letter.
+    ///
+    /// Returns `None` if no font has it, unless the letter is whitespace, which the main font draws as blank space.
+    fn get_font(&self, c: char) -> Option<&Font> {
+        self.fonts
+            .iter()
+            .find(|f| f.has_glyph(c))
+            .or_else(|| self.fonts.first().filter(|_| c.is_whitespace()))
+    }
+
+    /// Creates the coverage of an empty box (tofu), drawn in place of letters missing from every font.
+    fn rasterize_missing(&self) -> (Metrics, Vec<u8>) {
+        let width = (self.metrics.advance_width as usize).max(3);
+        let height = self.metrics.height.max(3);
+        let bytes = (0..height)
+            .flat_map(|y| {
+                (0..width).map(move |x| {
+                    if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                        0xFF
+                    } else {
+                        0
+                    }
+                })
+            })
+            .collect();
+        let metrics = Metrics {
+            xmin: 1,
+            ymin: 0,
+            width,
+            height,
+            advance_width: width as f32 + 2.0,
+            advance_height: 0.0,
+            bounds: OutlineBounds {
+                xmin: 1.0,
+                ymin: 0.0,
+                width: width as f32,
+                height: height as f32,
+            },
+        };
+        (metrics, bytes)
     }
 
     /// Gets the width of the specified string.
@@ -101,6 +204,26 @@ impl ImageHandler {
         })
     }
 
+    /// Truncates text to fit within the specified width, ending it with an ellipsis if anything was cut off.
+    ///
+    /// Text is cut between characters, never within one.
+    #[must_use]
+    pub fn truncate_text(&self, text: &str, max_width: i16) -> String {
+        if self.get_text_length(text) <= max_width {
+            return text.to_owned();
+        }
+        let mut width = self.get_text_length(ELLIPSIS);
+        let mut truncated = text
+            .chars()
+            .take_while(|&c| {
+                width += self.get_metrics(c).advance_width as i16;
+                width <= max_width
+            })
+            .collect::<String>();
+        truncated.push_str(ELLIPSIS);
+        truncated
+    }
+
     /// Resizes an image to the metric height.
     /// # Errors
     /// Converting to an rgba buffer may result in an error, in which case no Image is returned.
@@ -125,7 +248,7 @@ impl ImageHandler {
             height,
             data: crate::render::blend_image_with_background(
                 &imageops::resize(&buff, width, height, imageops::FilterType::Lanczos3),
-                self.colors.foreground,
+                self.colors.background,
             ),
         })
     }
@@ -140,42 +263,85 @@ fn alpha_interpolate(color1: u8, color2: u8, alpha: u8) -> u8 {
         as u8
 }
 
-/// Blends the image buffer with the specified background color. Image must be BGRA.
+/// Creates a premultiplied BGRA byte array out of a coverage mask, blending the foreground into the background.
+fn colorize(
+    coverage: &[u8],
+    foreground: (u8, u8, u8, u8),
+    background: (u8, u8, u8, u8),
+) -> Vec<u8> {
+    let (fg, bg) = (premultiply(foreground), premultiply(background));
+    coverage
+        .iter()
+        .flat_map(|&a| {
+            [
+                alpha_interpolate(fg.2, bg.2, a),
+                alpha_interpolate(fg.1, bg.1, a),
+                alpha_interpolate(fg.0, bg.0, a),
+                alpha_interpolate(foreground.3, background.3, a),
+            ]
+        })
+        .collect()
+}
+
+/// Creates a premultiplied BGRA image of a single RGBA color.
 #[must_use]
-pub fn blend_image_with_background(bytes: &[u8], background: (u8, u8, u8)) -> Vec<u8> {
+pub fn solid_image(width: u32, height: u32, color: (u8, u8, u8, u8)) -> Image {
+    Image {
+        width,
+        height,
+        data: colorize(&vec![0; (width * height) as usize], color, color),
+    }
+}
+
+/// Blends the image buffer with the specified RGBA background color, resulting in a premultiplied image. Image must be BGRA.
+#[must_use]
+pub fn blend_image_with_background(bytes: &[u8], background: (u8, u8, u8, u8)) -> Vec<u8> {
+    let bg = premultiply(background);
     (0..bytes.len() - 3)
         .step_by(4)
         .flat_map(|i| {
             [
-                alpha_interpolate(bytes[i], background.2, bytes[i + 3]),
-                alpha_interpolate(bytes[i + 1], background.1, bytes[i + 3]),
-                alpha_interpolate(bytes[i + 2], background.0, bytes[i + 3]),
-                0xFF,
+                alpha_interpolate(bytes[i], bg.2, bytes[i + 3]),
+                alpha_interpolate(bytes[i + 1], bg.1, bytes[i + 3]),
+                alpha_interpolate(bytes[i + 2], bg.0, bytes[i + 3]),
+                alpha_interpolate(0xFF, background.3, bytes[i + 3]),
             ]
         })
         .collect()
 }
 
+/// Converts an X11 color into an RGBA tuple. If `opaque` is set, the alpha is ignored.
+#[must_use]
+pub fn color_to_rgba(color: Color, opaque: bool) -> (u8, u8, u8, u8) {
+    (
+        (color.red / 257) as u8,
+        (color.green / 257) as u8,
+        (color.blue / 257) as u8,
+        if opaque {
+            0xFF
+        } else {
+            (color.alpha / 257) as u8
+        },
+    )
+}
+
+/// Multiplies an RGBA color's channels by its alpha, as expected by ARGB visuals.
+fn premultiply(color: (u8, u8, u8, u8)) -> (u8, u8, u8) {
+    (
+        alpha_interpolate(color.0, 0, color.3),
+        alpha_interpolate(color.1, 0, color.3),
+        alpha_interpolate(color.2, 0, color.3),
+    )
+}
+
 /// Loads a font based on the specified path.
 ///
 /// May return an error if the file is missing or the font is damaged.
-fn get_font_file(path: &str) -> Result<Font, Box<dyn std::error::Error>> {
+fn get_font_file(path: &str) -> Result<Font, HematiteError> {
     log::info!("loading font from {path}");
-    let file = match fs::read(path) {
-        Ok(f) => f,
-        Err(e) => {
-            log::error!("couldnt open file! {e}");
-            return Err(Box::new(e));
-        }
-    };
-
-    let font = match Font::from_bytes(file, fontdue::FontSettings::default()) {
-        Ok(f) => f,
-        Err(e) => {
-            log::error!("couldn't make font! {e}");
-            return Err(e.into());
-        }
-    };
+    let file = fs::read(path)
+        .map_err(|e| HematiteError::Render(format!("couldn't open font {path}: {e}")))?;
 
-    Ok(font)
+    Font::from_bytes(file, fontdue::FontSettings::default())
+        .map_err(|e| HematiteError::Render(format!("couldn't load font {path}: {e}")))
 }
@@ -0,0 +1,193 @@
+//!
+//! This module saves the session to disk, so it can be approximately restored on the next start.
+//!
+//! The layout and ratio of each tag, and the class, tag and floating geometry of each window are stored in `$XDG_STATE_HOME/hematite/session.toml` when the manager exits, and whenever they change in case it's killed. The file is TOML rather than JSON, like the config. Windows closed shortly before saving are still saved, as clients close their windows before the manager exits on logout. On the next start the tags get their layouts back, and each new or adopted window whose class matches a saved window is put back on that window's tag, at its geometry if it was floating. Each saved window is only matched once, so two terminals go back to the tags of the two saved terminals.
+use core::time::Duration;
+use std::{collections::HashMap, path::PathBuf, time::Instant};
+
+use serde::{Deserialize, Serialize};
+use x11rb::protocol::xproto::Window;
+
+use crate::{
+    places::Geometry,
+    state::{Layout, StateHandler, WindowGroup},
+};
+
+/// How long closed windows are still saved for, so a logout closing every client doesn't empty the session.
+pub const CLOSE_GRACE: Duration = Duration::from_secs(30);
+
+/// The saved state of a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SavedTag {
+    /// How the tag's tiled windows were arranged.
+    pub layout: Layout,
+    /// The ratio between the master and stack groups of the tag.
+    pub ratio: f32,
+}
+
+/// The saved state of a window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedWindow {
+    /// The class of the window, used to match it to a new window.
+    pub class: String,
+    /// The index of the tag the window was on.
+    pub tag: usize,
+    /// The geometry of the window, if it was floating.
+    pub floating: Option<Geometry>,
+}
+
+/// A saved session, as stored in the session file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct Session {
+    /// The saved state of each tag.
+    tags: Vec<SavedTag>,
+    /// The saved state of each window, across tags.
+    windows: Vec<SavedWindow>,
+}
+
+/// A helper that saves the session, and matches new windows to the windows of the last session.
+pub struct Restore {
+    /// The path of the file the session is stored in. Is `None` if the file couldn't be placed.
+    path: Option<PathBuf>,
+    /// The session as it was last loaded or saved.
+    saved: Session,
+    /// The session as of the last update, which is saved next.
+    current: Session,
+    /// The saved state of each managed window as of the last update.
+    live: Vec<(Window, SavedWindow)>,
+    /// The saved state of each window closed within the grace period, with when it was noticed.
+    closed: Vec<(Instant, SavedWindow)>,
+    /// The windows of the last session that no new window was matched to yet.
+    pending: Vec<SavedWindow>,
+    /// The class of each managed window, so saving doesn't need to ask the X11 server.
+    classes: HashMap<Window, String>,
+}
+
+impl Restore {
+    /// Loads the last session from the session file.
+    ///
+    /// Errors are logged and leave nothing to restore.
+    #[must_use]
+    pub fn new() -> Self {
+        let path = xdg::BaseDirectories::with_prefix("hematite")
+            .place_state_file("session.toml")
+            .inspect_err(|e| log::error!("couldn't place session file {e:?}"))
+            .ok();
+        let saved: Session = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .and_then(|s| {
+                toml::from_str(&s)
+                    .inspect_err(|e| log::error!("error parsing session file {e:?}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            pending: saved.windows.clone(),
+            current: saved.clone(),
+            saved,
+            live: Vec::new(),
+            closed: Vec::new(),
+            classes: HashMap::new(),
+        }
+    }
+
+    /// Gets the saved state of each tag of the last session.
+    #[must_use]
+    pub fn saved_tags(&self) -> &[SavedTag] {
+        &self.saved.tags
+    }
+
+    /// Matches a new window of a class to the first unmatched window of the same class in the last session, if any.
+    pub fn take(&mut self, class: &str) -> Option<SavedWindow> {
+        let i = self.pending.iter().position(|w| w.class == class)?;
+        Some(self.pending.remove(i))
+    }
+
+    /// Remembers the class of a managed window. Windows without a class aren't saved.
+    pub fn track(&mut self, window: Window, class: &str) {
+        if !class.is_empty() {
+            self.classes.insert(window, class.to_owned());
+        }
+    }
+
+    /// Forgets the class of a window, such as when it's destroyed.
+    pub fn forget(&mut self, window: Window) {
+        self.classes.remove(&window);
+    }
+
+    /// Updates the session to save from the tags and windows of the state.
+    ///
+    /// Windows that are no longer managed are kept in the session for `CLOSE_GRACE`.
+    pub fn update(&mut self, state: &StateHandler) {
+        let live: Vec<(Window, SavedWindow)> = state
+            .tags
+            .iter()
+            .enumerate()
+            .flat_map(|(tag, t)| t.windows.iter().map(move |w| (tag, w)))
+            .filter_map(|(tag, w)| {
+                let saved = SavedWindow {
+                    class: self.classes.get(&w.window)?.clone(),
+                    tag,
+                    floating: (w.group == WindowGroup::Floating)
+                        .then_some((w.x, w.y, w.width, w.height)),
+                };
+                Some((w.window, saved))
+            })
+            .collect();
+        let now = Instant::now();
+        for (window, saved) in self.live.drain(..) {
+            if !live.iter().any(|(w, _)| *w == window) {
+                self.closed.push((now, saved));
+            }
+        }
+        self.closed
+            .retain(|(closed, _)| closed.elapsed() < CLOSE_GRACE);
+        self.live = live;
+
+        self.current = Session {
+            tags: state
+                .tags
+                .iter()
+                .map(|t| SavedTag {
+                    layout: t.layout,
+                    ratio: t.ratio,
+                })
+                .collect(),
+            windows: self
+                .live
+                .iter()
+                .map(|(_, w)| w)
+                .chain(self.closed.iter().map(|(_, w)| w))
+                .cloned()
+                .collect(),
+        };
+    }
+
+    /// Saves the session of the last update to the session file, if it changed since the last save.
+    ///
+    /// May log an error if the file couldn't be written.
+    pub fn save(&mut self) {
+        if self.current == self.saved {
+            return;
+        }
+        self.saved = self.current.clone();
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = toml::to_string(&self.saved)
+            .map_err(|e| e.to_string())
+            .and_then(|s| std::fs::write(path, s).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::error!("couldn't save session to {}: {e}", path.display());
+        }
+    }
+}
+
+impl Default for Restore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,57 @@
+//!
+//! This module matches windows against rules based on their class, role and title, deciding how they should be placed.
+//!
+//! Applications can be assigned to tags and given an opacity by the user, and picture-in-picture windows of common browsers are detected.
+use crate::config::{AssignRule, OpacityRule};
+
+/// The classes (in lowercase) of browsers that can open picture-in-picture windows.
+const PIP_CLASSES: [&str; 5] = [
+    "firefox",
+    "librewolf",
+    "chromium",
+    "google-chrome",
+    "brave-browser",
+];
+/// The role Firefox gives its picture-in-picture windows.
+const PIP_ROLE: &str = "PictureInPicture";
+/// The titles (in lowercase) browsers give their picture-in-picture windows.
+const PIP_TITLES: [&str; 2] = ["picture-in-picture", "picture in picture"];
+
+/// Gets the rule assigning a window of the specified class to a tag, if any.
+///
+/// The first matching rule is used.
+#[must_use]
+pub fn find_assign_rule<'a>(rules: &'a [AssignRule], class: &str) -> Option<&'a AssignRule> {
+    rules.iter().find(|r| r.class.eq_ignore_ascii_case(class))
+}
+
+/// Gets the rule giving unfocused windows of the specified class an opacity, if any.
+///
+/// The first matching rule is used.
+#[must_use]
+pub fn find_opacity_rule<'a>(rules: &'a [OpacityRule], class: &str) -> Option<&'a OpacityRule> {
+    rules.iter().find(|r| r.class.eq_ignore_ascii_case(class))
+}
+
+/// Whether a window is a browser's picture-in-picture window, based on its class, role and title.
+#[must_use]
+pub fn is_picture_in_picture(class: &str, role: &str, title: &str) -> bool {
+    let class = class.to_lowercase();
+    let title = title.to_lowercase();
+    PIP_CLASSES.iter().any(|c| class.contains(c))
+        && (role == PIP_ROLE || PIP_TITLES.iter().any(|t| title.contains(t)))
+}
+
+/// Shrinks a size to fit within a maximum size, keeping its aspect ratio.
+///
+/// Sizes that already fit are left alone.
+#[must_use]
+pub fn cap_size(width: u16, height: u16, max_width: u16, max_height: u16) -> (u16, u16) {
+    let scale = (f32::from(max_width) / f32::from(width.max(1)))
+        .min(f32::from(max_height) / f32::from(height.max(1)))
+        .min(1.0);
+    (
+        ((f32::from(width) * scale) as u16).max(1),
+        ((f32::from(height) * scale) as u16).max(1),
+    )
+}
@@ -0,0 +1,186 @@
+//!
+//! This module generates a JSON schema of the config file, for editors and tools validating configs.
+//!
+//! The schema is derived from the default config, so it always matches the keys the manager reads: each table lists its keys with their type and default value. Keys that are unset by default (such as optional colors) aren't listed, and unknown keys are allowed.
+use core::fmt::Write as _;
+
+use toml::{Table, Value};
+
+/// The JSON schema version the generated schema follows.
+const SCHEMA_VERSION: &str = "http://json-schema.org/draft-07/schema#";
+
+/// Generates a pretty-printed JSON schema from the serialized default config.
+#[must_use]
+pub fn generate(default: &Table) -> String {
+    let mut schema = table_schema(default, true);
+    schema.insert(
+        "$schema".to_owned(),
+        Value::String(SCHEMA_VERSION.to_owned()),
+    );
+    schema.insert(
+        "title".to_owned(),
+        Value::String("hematite config".to_owned()),
+    );
+    let mut json = String::new();
+    write_json(&mut json, &Value::Table(schema), 0);
+    json.push('\n');
+    json
+}
+
+/// Describes a table, with the schema of each of its keys.
+fn table_schema(table: &Table, defaults: bool) -> Table {
+    let properties = table
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::Table(value_schema(value, defaults))))
+        .collect();
+    let mut schema = Table::new();
+    schema.insert("type".to_owned(), Value::String("object".to_owned()));
+    schema.insert("properties".to_owned(), Value::Table(properties));
+    schema
+}
+
+/// Describes a value by its type. Scalars also have their value as the default if `defaults` is set.
+///
+/// The items of an array are described by merging the schemas of its elements, so that e.g. every hotkey action is listed. Defaults only apply to single values, so items have none.
+fn value_schema(value: &Value, defaults: bool) -> Table {
+    match value {
+        Value::Table(table) => table_schema(table, defaults),
+        Value::Array(array) => {
+            let mut schema = Table::new();
+            schema.insert("type".to_owned(), Value::String("array".to_owned()));
+            let items = array
+                .iter()
+                .map(|v| value_schema(v, false))
+                .reduce(|a, b| merge(a, &b));
+            if let Some(items) = items {
+                schema.insert("items".to_owned(), Value::Table(items));
+            }
+            schema
+        }
+        Value::String(_) | Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => {
+            let mut schema = Table::new();
+            schema.insert(
+                "type".to_owned(),
+                Value::String(json_type(value).to_owned()),
+            );
+            if defaults {
+                schema.insert("default".to_owned(), value.clone());
+            }
+            schema
+        }
+        Value::Datetime(_) => {
+            let mut schema = Table::new();
+            schema.insert("type".to_owned(), Value::String("string".to_owned()));
+            schema
+        }
+    }
+}
+
+/// Merges two schemas describing elements of the same array.
+///
+/// Properties of either schema are kept, and the type is dropped if the schemas disagree on it.
+fn merge(mut a: Table, b: &Table) -> Table {
+    if a.get("type") != b.get("type") {
+        a.remove("type");
+    }
+    match (a.get_mut("properties"), b.get("properties")) {
+        (Some(Value::Table(properties)), Some(Value::Table(other))) => {
+            for (key, value) in other {
+                let Value::Table(value) = value else {
+                    continue;
+                };
+                let merged = match properties.remove(key) {
+                    Some(Value::Table(existing)) => merge(existing, value),
+                    _ => value.clone(),
+                };
+                properties.insert(key.clone(), Value::Table(merged));
+            }
+        }
+        (None, Some(other)) => {
+            a.insert("properties".to_owned(), other.clone());
+        }
+        _ => (),
+    }
+    match (a.remove("items"), b.get("items")) {
+        (Some(Value::Table(items)), Some(Value::Table(other))) => {
+            a.insert("items".to_owned(), Value::Table(merge(items, other)));
+        }
+        (Some(items), _) => {
+            a.insert("items".to_owned(), items);
+        }
+        (None, Some(items)) => {
+            a.insert("items".to_owned(), items.clone());
+        }
+        (None, None) => (),
+    }
+    a
+}
+
+/// Gets the JSON type of a scalar value.
+const fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Integer(_) => "integer",
+        Value::Float(_) => "number",
+        Value::Boolean(_) => "boolean",
+        Value::Table(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) | Value::Datetime(_) => "string",
+    }
+}
+
+/// Writes a value as JSON, indenting nested values by two spaces per level.
+fn write_json(out: &mut String, value: &Value, depth: usize) {
+    let indent = |depth: usize| "  ".repeat(depth);
+    match value {
+        Value::Table(table) if table.is_empty() => out.push_str("{}"),
+        Value::Table(table) => {
+            out.push_str("{\n");
+            for (i, (key, value)) in table.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                write_json_string(out, key);
+                out.push_str(": ");
+                write_json(out, value, depth + 1);
+                out.push_str(if i + 1 < table.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&indent(depth));
+            out.push('}');
+        }
+        Value::Array(array) if array.is_empty() => out.push_str("[]"),
+        Value::Array(array) => {
+            out.push_str("[\n");
+            for (i, value) in array.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                write_json(out, value, depth + 1);
+                out.push_str(if i + 1 < array.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&indent(depth));
+            out.push(']');
+        }
+        Value::String(s) => write_json_string(out, s),
+        Value::Datetime(datetime) => write_json_string(out, &datetime.to_string()),
+        Value::Integer(i) => out.push_str(&i.to_string()),
+        Value::Float(f) if f.is_finite() => {
+            let _ = write!(out, "{f:?}");
+        }
+        Value::Float(_) => out.push_str("null"),
+        Value::Boolean(b) => out.push_str(&b.to_string()),
+    }
+}
+
+/// Writes a string as a quoted JSON string, escaping quotes, backslashes and control characters.
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,226 @@
+//!
+//! This module integrates with logind (or elogind) to lock the screen when the session is locked or the system is about to suspend.
+//!
+//! A delay inhibitor is held with `systemd-inhibit` so that the lock command has time to start before the system goes to sleep. Signals are read from the system bus using `dbus-monitor`.
+use std::{
+    io::{BufRead as _, BufReader},
+    process::{Child, Command, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
+
+use crate::config::Config;
+
+/// The match rule for the signal logind sends before suspending and after resuming.
+const SLEEP_MATCH: &str =
+    "type='signal',interface='org.freedesktop.login1.Manager',member='PrepareForSleep'";
+/// The match rule for the signal logind sends when a session should be locked (e.g. `loginctl lock-session`), without the session's path.
+const LOCK_MATCH: &str = "type='signal',interface='org.freedesktop.login1.Session',member='Lock'";
+/// The object path logind publishes sessions under.
+const SESSION_PATH: &str = "/org/freedesktop/login1/session/";
+
+/// A helper that keeps track of whether the session is locked or inhibited.
+///
+/// Clones share their state with the original, so the session stays tracked when the manager connects to the X11 server again.
+#[derive(Clone)]
+pub struct SessionHandler {
+    /// Whether the lock command is currently running.
+    locked: Arc<AtomicBool>,
+    /// Whether a sleep inhibitor is currently held.
+    inhibited: Arc<AtomicBool>,
+}
+
+impl SessionHandler {
+    /// Creates a new handler.
+    ///
+    /// If a lock command is configured, a thread is spawned that takes a sleep inhibitor and locks the session when logind asks for it.
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        let handler = Self {
+            locked: Arc::new(AtomicBool::new(false)),
+            inhibited: Arc::new(AtomicBool::new(false)),
+        };
+
+        if !config.lock_command.is_empty() {
+            let command = config.lock_command.clone();
+            let locked = Arc::clone(&handler.locked);
+            let inhibited = Arc::clone(&handler.inhibited);
+            thread::spawn(move || monitor_logind(&command, &locked, &inhibited));
+        }
+
+        handler
+    }
+
+    /// Gets the character to display on the bar, if any.
+    ///
+    /// A filled circle is shown while the session is locked, and an empty circle while the inhibitor is held.
+    #[must_use]
+    pub fn indicator(&self) -> Option<char> {
+        if self.locked.load(Ordering::Relaxed) {
+            Some('●')
+        } else if self.inhibited.load(Ordering::Relaxed) {
+            Some('○')
+        } else {
+            None
+        }
+    }
+
+    /// Locks the session with the specified command, unless it's already locked.
+    pub fn lock(&self, command: &str) {
+        lock(command, &self.locked);
+    }
+}
+
+/// Reads logind signals from the system bus, locking the session before sleep or when asked to.
+///
+/// The inhibitor is released once the lock command is started, and taken again after resuming.
+fn monitor_logind(command: &str, locked: &Arc<AtomicBool>, inhibited: &Arc<AtomicBool>) {
+    let mut inhibitor = take_inhibitor(inhibited);
+
+    let mut monitor = Command::new("dbus-monitor");
+    monitor.arg("--system").arg(SLEEP_MATCH);
+    match get_session_path() {
+        Some(path) => {
+            log::debug!("listening for lock requests of {path}");
+            monitor.arg(format!("{LOCK_MATCH},path='{path}'"));
+        }
+        None => log::warn!("couldn't find the logind session, lock requests are ignored"),
+    }
+    let monitor = monitor.stdout(Stdio::piped()).spawn();
+    let Some((mut monitor, stdout)) = monitor
+        .ok()
+        .and_then(|mut m| m.stdout.take().map(|stdout| (m, stdout)))
+    else {
+        log::error!("couldn't start dbus-monitor, session locking is disabled");
+        release_inhibitor(&mut inhibitor, inhibited);
+        return;
+    };
+
+    let mut preparing_for_sleep = false;
+    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+        if line.contains("member=PrepareForSleep") {
+            preparing_for_sleep = true;
+        } else if line.contains("member=Lock") {
+            log::info!("session lock requested");
+            lock(command, locked);
+        } else if preparing_for_sleep && line.contains("boolean true") {
+            log::info!("system is going to sleep, locking session");
+            preparing_for_sleep = false;
+            lock(command, locked);
+            release_inhibitor(&mut inhibitor, inhibited);
+        } else if preparing_for_sleep && line.contains("boolean false") {
+            log::info!("system resumed from sleep");
+            preparing_for_sleep = false;
+            inhibitor = take_inhibitor(inhibited);
+        }
+    }
+    log::error!("dbus-monitor exited, session locking is disabled");
+    let _ = monitor.wait();
+    release_inhibitor(&mut inhibitor, inhibited);
+}
+
+/// Gets the object path of the logind session the manager runs in, so only lock requests for it are followed.
+///
+/// The session is found from `XDG_SESSION_ID`, or by asking logind for the session of the manager's process if it isn't set.
+fn get_session_path() -> Option<String> {
+    if let Ok(id) = std::env::var("XDG_SESSION_ID")
+        && !id.is_empty()
+    {
+        return Some(format!("{SESSION_PATH}{}", escape_object_path(&id)));
+    }
+    let output = Command::new("busctl")
+        .args([
+            "--system",
+            "call",
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            "GetSessionByPID",
+            "u",
+            &std::process::id().to_string(),
+        ])
+        .output()
+        .ok()?;
+    // the reply is printed as `o "/org/freedesktop/login1/session/_32"`
+    String::from_utf8_lossy(&output.stdout)
+        .split('"')
+        .nth(1)
+        .filter(|path| path.starts_with(SESSION_PATH))
+        .map(str::to_owned)
+}
+
+/// Escapes a session id the way logind does in object paths: characters other than letters, and digits after the first character, become `_` followed by their hex value.
+fn escape_object_path(id: &str) -> String {
+    id.bytes()
+        .enumerate()
+        .map(|(i, b)| {
+            if b.is_ascii_alphabetic() || (i > 0 && b.is_ascii_digit()) {
+                char::from(b).to_string()
+            } else {
+                format!("_{b:02x}")
+            }
+        })
+        .collect()
+}
+
+/// Takes a delay inhibitor on sleep.
+///
+/// The inhibitor is held for as long as the child's standard input stays open, so it is released even if the manager exits abruptly.
+fn take_inhibitor(inhibited: &Arc<AtomicBool>) -> Option<Child> {
+    match Command::new("systemd-inhibit")
+        .args([
+            "--what=sleep",
+            "--mode=delay",
+            "--who=hematite",
+            "--why=Locking the session before sleep",
+            "cat",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => {
+            inhibited.store(true, Ordering::Relaxed);
+            Some(child)
+        }
+        Err(e) => {
+            log::error!("couldn't take sleep inhibitor {e:?}");
+            None
+        }
+    }
+}
+
+/// Releases the sleep inhibitor, if it's held.
+fn release_inhibitor(inhibitor: &mut Option<Child>, inhibited: &Arc<AtomicBool>) {
+    if let Some(mut child) = inhibitor.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    inhibited.store(false, Ordering::Relaxed);
+}
+
+/// Runs the lock command, marking the session as locked until the command exits.
+///
+/// Nothing happens if the session is already locked.
+fn lock(command: &str, locked: &Arc<AtomicBool>) {
+    if locked.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    match Command::new("sh").arg("-c").arg(command).spawn() {
+        Ok(mut child) => {
+            let locked = Arc::clone(locked);
+            thread::spawn(move || {
+                let _ = child.wait();
+                log::info!("session unlocked");
+                locked.store(false, Ordering::Relaxed);
+            });
+        }
+        Err(e) => {
+            log::error!("error when spawning lock command {e:?}");
+            locked.store(false, Ordering::Relaxed);
+        }
+    }
+}
@@ -4,8 +4,17 @@
 use core::fmt;
 use core::fmt::Debug;
 use core::fmt::Write as _;
+use core::time::Duration;
+use std::collections::HashMap;
+use std::time::Instant;
 
-use x11rb::protocol::xproto::Window;
+use serde::{Deserialize, Serialize};
+use x11rb::protocol::xproto::{Gravity, StackMode, Window};
+
+/// The symbols shown on the bar for each layout: tiled, monocle (the focused window is maximized), floating (no window is tiled) and tabbed.
+pub const LAYOUT_SYMBOLS: [&str; 4] = ["[]=", "[M]", "><>", "[T]"];
+/// The distance in pixels between cascaded floating windows, on both axes.
+pub const CASCADE_STEP: i16 = 32;
 #[derive(Clone, Copy, PartialEq, Debug)]
 /// An enum to track which group a window should be in, affecting how they're tiled.
 pub enum WindowGroup {
@@ -15,10 +24,236 @@ pub enum WindowGroup {
     Stack,
     /// Floating windows do not obey tiling rules and can be dragged around.
     Floating,
+    /// Maximized windows take up the entire work area, keeping the bar and their borders visible.
+    Maximized,
     /// Fullscreen windows are maximised to the screen and hide other windows.
     Fullscreen,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+/// A stacking layer a window can be kept in, independently of its group.
+pub enum Layer {
+    /// The window is kept below all other windows.
+    Below,
+    /// The window is stacked according to its group.
+    Normal,
+    /// The window is kept above all other windows, except `Fullscreen` windows.
+    Above,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// A corner of the work area.
+pub enum Corner {
+    /// The top left corner.
+    TopLeft,
+    /// The top right corner.
+    TopRight,
+    /// The bottom left corner.
+    BottomLeft,
+    /// The bottom right corner.
+    BottomRight,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Where new floating windows that didn't ask for a position are placed.
+pub enum Placement {
+    /// At the center of the screen.
+    #[default]
+    Center,
+    /// Cascading down from the top left of the work area, each window a step away from the last.
+    Cascade,
+    /// Where they overlap the other floating windows of the tag the least, preferring the top left.
+    Smart,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// The edge of the screen the bar is placed at.
+pub enum BarPosition {
+    /// The top edge of the screen.
+    #[default]
+    Top,
+    /// The bottom edge of the screen.
+    Bottom,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// What the focus moves between when cycling through the windows of a tag.
+pub enum FocusCycle {
+    /// Every window is focused in turn.
+    #[default]
+    Windows,
+    /// Windows of the same window group are skipped, so each application is focused once.
+    Groups,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Whether new windows may take the focus from the focused window.
+pub enum FocusStealing {
+    /// New windows are always focused.
+    #[default]
+    Allow,
+    /// New windows the user didn't interact with since last using the manager or another window (by `_NET_WM_USER_TIME`) open unfocused and demanding attention.
+    Prevent,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Which tags are gone through when cycling through tags.
+pub enum TagCycle {
+    /// Every tag is switched to in turn.
+    #[default]
+    All,
+    /// Tags without windows are skipped.
+    Occupied,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// How the tiled windows of a tag are arranged.
+pub enum Layout {
+    /// Windows are split between the `Master` and `Stack` groups.
+    #[default]
+    Tile,
+    /// Every tiled window takes up the whole work area, with only the focused one visible. The bar lists the windows as tabs.
+    Tabbed,
+}
+
+impl Layout {
+    /// Gets the name of the layout, as shown on the OSD.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Tile => "tile",
+            Self::Tabbed => "tabbed",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Which windows get a title bar drawn on their frame.
+pub enum TitleBars {
+    /// No window gets a title bar.
+    #[default]
+    None,
+    /// Floating windows get a title bar, unless they ask for no decorations.
+    Floating,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Which windows the compositor is asked to stop compositing, drawing them directly to the screen.
+pub enum BypassCompositor {
+    /// The compositor isn't asked to bypass any window.
+    Never,
+    /// Fullscreen windows (e.g. games and videos) are bypassed, lowering their latency.
+    #[default]
+    Fullscreen,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// How the size increments of tiled windows (e.g. terminals sized in character cells) are treated.
+pub enum Increments {
+    /// Windows are shrunk to a whole number of increments inside their tile, and the leftover pixels show the frame's background.
+    #[default]
+    Respect,
+    /// Windows are stretched to fill their tile.
+    Ignore,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+/// The space a dock window (e.g. a panel or tray) reserves at each edge of the screen, read from `_NET_WM_STRUT_PARTIAL` or `_NET_WM_STRUT`.
+pub struct Struts {
+    /// The width reserved at the left edge.
+    pub left: u16,
+    /// The width reserved at the right edge.
+    pub right: u16,
+    /// The height reserved at the top edge.
+    pub top: u16,
+    /// The height reserved at the bottom edge.
+    pub bottom: u16,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+/// The size hints of a window, read from `WM_NORMAL_HINTS`.
+pub struct SizeHints {
+    /// The size the increments are counted from.
+    pub base: (u16, u16),
+    /// The steps the size of the window changes by (e.g. the size of a terminal's character cell). Is zero if the window has no increments.
+    pub increment: (u16, u16),
+    /// The minimum and maximum aspect ratios (width divided by height) of the window. A ratio of zero means there is no limit.
+    pub aspect: (f64, f64),
+    /// The position the client asked to be placed at (with `USPosition` or `PPosition`), or `None` if it left placing the window to the manager.
+    pub position: Option<(i16, i16)>,
+    /// Which point of the window stays at the requested position when the border and title bar are added around it.
+    pub gravity: Gravity,
+}
+
+impl SizeHints {
+    /// Shrinks a size to the nearest size made up of whole increments.
+    ///
+    /// Sizes smaller than the base size are left alone.
+    #[must_use]
+    pub const fn fit_increments(&self, width: u16, height: u16) -> (u16, u16) {
+        const fn fit(size: u16, base: u16, increment: u16) -> u16 {
+            if increment > 1 && size > base {
+                size - (size - base) % increment
+            } else {
+                size
+            }
+        }
+        (
+            fit(width, self.base.0, self.increment.0),
+            fit(height, self.base.1, self.increment.1),
+        )
+    }
+
+    /// Gets where the frame of a window goes for the window to be at its requested position, or `None` if it didn't request one.
+    ///
+    /// With static gravity the client itself stays where it asked to be. Otherwise the frame's edges or center line up with those of the client according to the gravity, so e.g. a window with south east gravity grows towards the top left.
+    #[must_use]
+    pub fn get_frame_position(&self, border: u16, title: u16) -> Option<(i16, i16)> {
+        let (x, y) = self.position?;
+        let (border, title) = (border as i16, title as i16);
+        let (extra_width, extra_height) = (border * 2, border * 2 + title);
+        let (column, row) = match self.gravity {
+            Gravity::STATIC => return Some((x - border, y - border - title)),
+            Gravity::NORTH => (1, 0),
+            Gravity::NORTH_EAST => (2, 0),
+            Gravity::WEST => (0, 1),
+            Gravity::CENTER => (1, 1),
+            Gravity::EAST => (2, 1),
+            Gravity::SOUTH_WEST => (0, 2),
+            Gravity::SOUTH => (1, 2),
+            Gravity::SOUTH_EAST => (2, 2),
+            _ => (0, 0),
+        };
+        Some((x - extra_width * column / 2, y - extra_height * row / 2))
+    }
+
+    /// Shrinks a size to fit within the window's aspect ratio limits.
+    ///
+    /// A window that is too wide loses width, and a window that is too tall loses height.
+    #[must_use]
+    pub fn fit_aspect(&self, width: u16, height: u16) -> (u16, u16) {
+        let (min, max) = self.aspect;
+        let ratio = f64::from(width) / f64::from(height.max(1));
+        if max > 0.0 && ratio > max {
+            (((f64::from(height) * max) as u16).max(1), height)
+        } else if min > 0.0 && ratio < min {
+            (width, ((f64::from(width) / min) as u16).max(1))
+        } else {
+            (width, height)
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 /// The geometry, group and ids of a window.
 pub struct WindowState {
@@ -36,6 +271,22 @@ pub struct WindowState {
     pub height: u16,
     /// The group of the window.
     pub group: WindowGroup,
+    /// The stacking layer of the window.
+    pub layer: Layer,
+    /// Whether the window is shown on every tag. Sticky windows follow the active tag.
+    pub sticky: bool,
+    /// Whether the window demands attention. Urgent windows have a different border color until they're focused.
+    pub urgent: bool,
+    /// Whether the window is drawn with a border. Applications drawing their own decorations can ask for no border through their Motif hints.
+    pub decorated: bool,
+    /// The last time the window was seen with the focus.
+    pub last_focus: Instant,
+    /// The size hints of the window.
+    pub hints: SizeHints,
+    /// The group and geometry (x, y, width, height) the window had before going fullscreen, restored once it leaves fullscreen.
+    pub restore: Option<(WindowGroup, i16, i16, u16, u16)>,
+    /// The group leader of the window, read from `WM_HINTS`. Windows with the same leader belong to the same application (e.g. GIMP and its tool windows) and are kept on the same tag.
+    pub leader: Option<Window>,
 }
 
 impl WindowState {
@@ -43,7 +294,7 @@ impl WindowState {
     ///
     /// New windows are immediately tiled, so these base values do not matter. New windows are `Stack` by default, but this can be changed immediately by the tiling logic.
     #[must_use]
-    pub const fn new(window: Window, frame_window: Window) -> Self {
+    pub fn new(window: Window, frame_window: Window) -> Self {
         Self {
             window,
             frame_window,
@@ -52,8 +303,87 @@ impl WindowState {
             width: 100,
             height: 100,
             group: WindowGroup::Stack,
+            layer: Layer::Normal,
+            sticky: false,
+            urgent: false,
+            decorated: true,
+            last_focus: Instant::now(),
+            hints: SizeHints::default(),
+            restore: None,
+            leader: None,
+        }
+    }
+
+    /// Whether the window belongs to the same window group as another window.
+    #[must_use]
+    pub fn is_grouped_with(&self, other: &Self) -> bool {
+        self.leader.is_some() && self.leader == other.leader
+    }
+
+    /// Gets the position of the window in the stacking order relative to other windows. Higher values are stacked on top.
+    ///
+    /// From the bottom up, the order is: `Below` windows, tiled windows, `Maximized` windows, `Floating` windows, `Above` windows and `Fullscreen` windows.
+    #[must_use]
+    pub const fn stacking_layer(&self) -> u8 {
+        match (self.group, self.layer) {
+            (WindowGroup::Fullscreen, _) => 5,
+            (_, Layer::Above) => 4,
+            (_, Layer::Below) => 0,
+            (WindowGroup::Floating, _) => 3,
+            (WindowGroup::Maximized, _) => 2,
+            (WindowGroup::Master | WindowGroup::Stack, _) => 1,
         }
     }
+
+    /// Whether the window is tiled, being in the `Master` or `Stack` group.
+    #[must_use]
+    pub const fn is_tiled(&self) -> bool {
+        matches!(self.group, WindowGroup::Master | WindowGroup::Stack)
+    }
+
+    /// Whether the window is stacked above the status bar. Only tiled and `Below` windows are stacked below it.
+    #[must_use]
+    pub const fn is_above_bar(&self) -> bool {
+        self.stacking_layer() > 1
+    }
+
+    /// Gets the height of the window's title bar, drawn at the top of its frame. Is 0 if the window has no title bar.
+    #[must_use]
+    pub const fn title_height(&self, title_bars: TitleBars, height: u16) -> u16 {
+        if matches!(title_bars, TitleBars::Floating)
+            && matches!(self.group, WindowGroup::Floating)
+            && self.decorated
+        {
+            height
+        } else {
+            0
+        }
+    }
+
+    /// Gets the geometry (x, y, width, height) of the client inside its frame, with its position relative to the root window as told to the client in synthetic configure notifies.
+    ///
+    /// The client sits inside the frame's border, below the title bar. Tiled and maximized windows are shrunk to whole size increments if they're respected.
+    #[must_use]
+    pub const fn client_geometry(
+        &self,
+        increments: Increments,
+        border: u16,
+        title: u16,
+    ) -> (i16, i16, u16, u16) {
+        let (width, height) = match (increments, self.group) {
+            (
+                Increments::Respect,
+                WindowGroup::Master | WindowGroup::Stack | WindowGroup::Maximized,
+            ) => self.hints.fit_increments(self.width, self.height),
+            _ => (self.width, self.height),
+        };
+        (
+            self.x + border as i16,
+            self.y + border as i16 + title as i16,
+            width,
+            height.saturating_sub(title),
+        )
+    }
 }
 
 impl fmt::Display for WindowState {
@@ -65,6 +395,115 @@ impl fmt::Display for WindowState {
         )
     }
 }
+/// The edge or corner an interactive resize is dragging, or a move of the whole window.
+///
+/// The order matches the directions of the `_NET_WM_MOVERESIZE` message.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DragDirection {
+    /// The top left corner is dragged.
+    TopLeft,
+    /// The top edge is dragged.
+    Top,
+    /// The top right corner is dragged.
+    TopRight,
+    /// The right edge is dragged.
+    Right,
+    /// The bottom right corner is dragged.
+    BottomRight,
+    /// The bottom edge is dragged.
+    Bottom,
+    /// The bottom left corner is dragged.
+    BottomLeft,
+    /// The left edge is dragged.
+    Left,
+    /// The whole window is moved.
+    Move,
+}
+
+impl DragDirection {
+    /// Gets the direction from a `_NET_WM_MOVERESIZE` direction.
+    ///
+    /// Returns `None` for keyboard driven directions and cancellation.
+    #[must_use]
+    pub const fn from_net(direction: u32) -> Option<Self> {
+        match direction {
+            0 => Some(Self::TopLeft),
+            1 => Some(Self::Top),
+            2 => Some(Self::TopRight),
+            3 => Some(Self::Right),
+            4 => Some(Self::BottomRight),
+            5 => Some(Self::Bottom),
+            6 => Some(Self::BottomLeft),
+            7 => Some(Self::Left),
+            8 => Some(Self::Move),
+            _ => None,
+        }
+    }
+}
+
+/// An interactive, pointer driven move or resize of a floating window.
+#[derive(Clone, Copy, Debug)]
+pub struct Drag {
+    /// The window being dragged.
+    pub window: Window,
+    /// The dragged edge, or a move.
+    pub direction: DragDirection,
+    /// The pointer position when the drag started.
+    pub pointer: (i16, i16),
+    /// The window's geometry (x, y, width, height) when the drag started.
+    pub origin: (i16, i16, u16, u16),
+}
+
+impl Drag {
+    /// Gets the window's geometry (x, y, width, height) with the pointer at the specified position.
+    ///
+    /// The window never shrinks below 1x1 pixels, and keeps to the aspect ratio limits of its hints. The edges opposite to the dragged ones stay in place.
+    #[must_use]
+    pub fn geometry(&self, x: i16, y: i16, hints: &SizeHints) -> (i16, i16, u16, u16) {
+        let dx = i32::from(x) - i32::from(self.pointer.0);
+        let dy = i32::from(y) - i32::from(self.pointer.1);
+        let (ox, oy) = (i32::from(self.origin.0), i32::from(self.origin.1));
+        let (ow, oh) = (i32::from(self.origin.2), i32::from(self.origin.3));
+
+        let (left, right, top, bottom) = match self.direction {
+            DragDirection::Move => {
+                return ((ox + dx) as i16, (oy + dy) as i16, ow as u16, oh as u16);
+            }
+            DragDirection::TopLeft => (true, false, true, false),
+            DragDirection::Top => (false, false, true, false),
+            DragDirection::TopRight => (false, true, true, false),
+            DragDirection::Right => (false, true, false, false),
+            DragDirection::BottomRight => (false, true, false, true),
+            DragDirection::Bottom => (false, false, false, true),
+            DragDirection::BottomLeft => (true, false, false, true),
+            DragDirection::Left => (true, false, false, false),
+        };
+
+        let (mut nx, mut nw) = (ox, ow);
+        if left {
+            nw = (ow - dx).max(1);
+            nx = ox + ow - nw;
+        } else if right {
+            nw = (ow + dx).max(1);
+        }
+        let (mut ny, mut nh) = (oy, oh);
+        if top {
+            nh = (oh - dy).max(1);
+            ny = oy + oh - nh;
+        } else if bottom {
+            nh = (oh + dy).max(1);
+        }
+        let (fw, fh) = hints.fit_aspect(nw as u16, nh as u16);
+        if left {
+            nx += nw - i32::from(fw);
+        }
+        if top {
+            ny += nh - i32::from(fh);
+        }
+        (nx as i16, ny as i16, fw, fh)
+    }
+}
+
 /// A virtual desktop containing windows and the id of the focused window.
 ///
 /// Tags are numbered from 1-9, though this will be configurable in the future.
@@ -75,14 +514,20 @@ pub struct Tag {
     pub focus: Option<u32>,
     /// The window states pertaining to the tag.
     pub windows: Vec<WindowState>,
+    /// The ratio between the master and stack groups of the tag.
+    pub ratio: f32,
+    /// How the tag's tiled windows are arranged.
+    pub layout: Layout,
 }
 impl Tag {
-    /// Creates a new empty tag.
-    const fn new(tag: usize) -> Self {
+    /// Creates a new empty tag with the specified tiling ratio.
+    const fn new(tag: usize, ratio: f32) -> Self {
         Self {
             num: tag,
             focus: None,
             windows: Vec::new(),
+            ratio,
+            layout: Layout::Tile,
         }
     }
 }
@@ -91,9 +536,11 @@ impl fmt::Display for Tag {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "tag {} | focus {:?} | windows:\n{}",
+            "tag {} | focus {:?} | ratio {} | layout {:?} | windows:\n{}",
             self.num,
             self.focus,
+            self.ratio,
+            self.layout,
             self.windows.iter().fold(String::new(), |mut acc, w| {
                 let _ = writeln!(acc, "{w}");
                 acc
@@ -103,9 +550,11 @@ impl fmt::Display for Tag {
 }
 /// Parameters that help with tiling windows. Values are obtained from configuration.
 pub struct TilingInfo {
-    /// The gap between a window and its surrounding edges.
-    pub gap: u16,
-    /// The ratio between the master and stack groups. The higher the number, the more space is allocated for the master group.
+    /// The gap between windows.
+    pub inner_gap: u16,
+    /// The gap between windows and the edges of the screen (or the bar).
+    pub outer_gap: u16,
+    /// The initial ratio between the master and stack groups of every tag. The higher the number, the more space is allocated for the master group.
     pub ratio: f32,
     /// The maximum possible width to be allocated. This is usually the width of the screen.
     pub max_width: u16,
@@ -113,6 +562,16 @@ pub struct TilingInfo {
     pub max_height: u16,
     /// The height of the status bar.
     pub bar_height: u16,
+    /// The edge of the screen the status bar is placed at.
+    pub bar_position: BarPosition,
+    /// The width of window borders.
+    pub border: u16,
+    /// Whether a tag's only tiled window drops its outer gaps.
+    pub smart_gaps: bool,
+    /// Whether a tag's only tiled window drops its border.
+    pub smart_borders: bool,
+    /// The distance in pixels within which moved floating windows snap to edges. Zero disables snapping.
+    pub snap_distance: u16,
 }
 
 /// A manager for window and tag states. Tiles windows and provides methods to manipulate the state.
@@ -123,6 +582,16 @@ pub struct StateHandler {
     pub active_tag: usize,
     /// Information that helps with tiling.
     pub tiling: TilingInfo,
+    /// The interactive move or resize in progress, if any.
+    pub drag: Option<Drag>,
+    /// Whether the active tag's windows are hidden to show the desktop.
+    pub showing_desktop: bool,
+    /// Every managed window across all tags, in the order they were mapped.
+    pub clients: Vec<Window>,
+    /// The mapped dock windows, which aren't managed, and the space each reserves.
+    pub docks: HashMap<Window, Struts>,
+    /// The space reserved by every dock, which is left out of the work area.
+    pub reserved: Struts,
 }
 
 impl fmt::Display for StateHandler {
@@ -146,14 +615,48 @@ impl StateHandler {
     /// Creates a new handler.
     ///
     /// Creates new empty tags and sets the active tag to be the first one.
+    #[must_use]
     pub fn new(tiling: TilingInfo) -> Self {
         Self {
-            tags: (0..=8).map(Tag::new).collect(),
+            tags: (0..=8).map(|t| Tag::new(t, tiling.ratio)).collect(),
             active_tag: 0,
             tiling,
+            drag: None,
+            showing_desktop: false,
+            clients: Vec::new(),
+            docks: HashMap::new(),
+            reserved: Struts::default(),
         }
     }
 
+    /// Adds a dock window, or updates the space it reserves, then recomputes the reserved space.
+    pub fn add_dock(&mut self, window: Window, struts: Struts) {
+        self.docks.insert(window, struts);
+        self.update_reserved();
+    }
+
+    /// Removes a dock window, then recomputes the reserved space. Returns whether the window was a dock.
+    pub fn remove_dock(&mut self, window: Window) -> bool {
+        let removed = self.docks.remove(&window).is_some();
+        if removed {
+            self.update_reserved();
+        }
+        removed
+    }
+
+    /// Recomputes the space reserved at each edge, which is the largest space any dock reserves there.
+    fn update_reserved(&mut self) {
+        self.reserved = self
+            .docks
+            .values()
+            .fold(Struts::default(), |acc, s| Struts {
+                left: acc.left.max(s.left),
+                right: acc.right.max(s.right),
+                top: acc.top.max(s.top),
+                bottom: acc.bottom.max(s.bottom),
+            });
+    }
+
     /// Gets the active tag's currently focused window. Returns `None` if no window is focused.
     #[must_use]
     pub fn get_focus(&self) -> Option<u32> {
@@ -188,11 +691,133 @@ impl StateHandler {
             .find(|w| w.window == window || w.frame_window == window)
     }
 
+    /// Finds the tag and state of a client window on any tag. Returns `None` if the window isn't managed.
+    #[must_use]
+    pub fn find_window(&self, window: Window) -> Option<(usize, &WindowState)> {
+        self.tags.iter().enumerate().find_map(|(i, t)| {
+            t.windows
+                .iter()
+                .find(|w| w.window == window)
+                .map(|w| (i, w))
+        })
+    }
+
+    /// Finds the tag of another window in the same group as the window, if any.
+    #[must_use]
+    pub fn find_group_tag(&self, window: &WindowState) -> Option<usize> {
+        self.tags.iter().position(|t| {
+            t.windows
+                .iter()
+                .any(|w| w.is_grouped_with(window) || window.leader == Some(w.window))
+        })
+    }
+
     /// Adds the window and its state to the currently active tag, and sets it to be the focused window.
     pub fn add_window(&mut self, window: WindowState) {
-        log::debug!("adding window to tag {}", self.active_tag);
-        self.tags[self.active_tag].windows.push(window);
-        self.tags[self.active_tag].focus = Some(window.window);
+        self.add_window_to_tag(window, self.active_tag);
+    }
+
+    /// Adds the window and its state to a tag, and sets it to be the tag's focused window.
+    pub fn add_window_to_tag(&mut self, window: WindowState, tag: usize) {
+        log::debug!("adding window to tag {tag}");
+        self.tags[tag].windows.push(window);
+        self.tags[tag].focus = Some(window.window);
+        self.clients.push(window.window);
+    }
+
+    /// Removes the window from its tag and the list of managed windows.
+    ///
+    /// If the window was focused on an inactive tag, that tag's focus is set to its master window.
+    pub fn remove_window(&mut self, window: Window) {
+        let Some((tag, _)) = self.find_window(window) else {
+            return;
+        };
+        log::debug!("removing window from tag {tag}");
+        self.tags[tag].windows.retain(|w| w.window != window);
+        if tag != self.active_tag && self.tags[tag].focus == Some(window) {
+            self.tags[tag].focus = self.tags[tag].windows.last().map(|w| w.window);
+        }
+        self.clients.retain(|w| *w != window);
+    }
+
+    /// Gets the active tag's windows from the bottom to the top of the stacking order.
+    ///
+    /// Windows are ordered by their stacking layer. Windows in the same layer keep their order in the tag, except that in the tabbed layout the focused tiled window is put on top of the others.
+    #[must_use]
+    pub fn get_active_stacking_order(&self) -> Vec<WindowState> {
+        let tag = &self.tags[self.active_tag];
+        let mut windows = tag.windows.clone();
+        windows.sort_by_key(|w| {
+            let on_top = tag.layout == Layout::Tabbed && tag.focus == Some(w.window);
+            (w.stacking_layer(), on_top)
+        });
+        windows
+    }
+
+    /// Moves a window within its stacking layer as asked by a configure request, either directly above or below a sibling, or to the top or bottom of the layer if there's no sibling.
+    ///
+    /// Windows never leave their layer: a sibling in a lower layer counts as the bottom of the layer, and one in a higher layer as the top. `TopIf` and `BottomIf` are treated like `Above` and `Below`, and `Opposite` is ignored. Tiled windows aren't restacked, as their order decides how they're tiled.
+    pub fn restack_window(&mut self, window: Window, sibling: Option<Window>, mode: StackMode) {
+        let Some((tag, state)) = self.find_window(window) else {
+            return;
+        };
+        let layer = state.stacking_layer();
+        if state.is_tiled() {
+            return;
+        }
+        let windows = &mut self.tags[tag].windows;
+        let slots: Vec<usize> = (0..windows.len())
+            .filter(|&i| !windows[i].is_tiled() && windows[i].stacking_layer() == layer)
+            .collect();
+        let mut layer_windows: Vec<WindowState> = slots.iter().map(|&i| windows[i]).collect();
+        let Some(from) = layer_windows.iter().position(|w| w.window == window) else {
+            return;
+        };
+        let moved = layer_windows.remove(from);
+
+        let above = match mode {
+            StackMode::ABOVE | StackMode::TOP_IF => true,
+            StackMode::BELOW | StackMode::BOTTOM_IF => false,
+            _ => return,
+        };
+        let sibling = sibling.and_then(|s| {
+            self.tags[tag]
+                .windows
+                .iter()
+                .find(|w| w.window == s || w.frame_window == s)
+                .copied()
+        });
+        let to = match sibling {
+            Some(s) if s.stacking_layer() < layer => 0,
+            Some(s) if s.stacking_layer() > layer => layer_windows.len(),
+            Some(s) => match layer_windows.iter().position(|w| w.window == s.window) {
+                Some(i) if above => i + 1,
+                Some(i) => i,
+                None => from,
+            },
+            None if above => layer_windows.len(),
+            None => 0,
+        };
+        layer_windows.insert(to, moved);
+
+        let windows = &mut self.tags[tag].windows;
+        for (&i, w) in slots.iter().zip(layer_windows) {
+            windows[i] = w;
+        }
+    }
+
+    /// Gets every managed window from the bottom to the top of the stacking order.
+    ///
+    /// Windows of inactive tags are hidden and placed at the bottom, followed by the active tag's windows.
+    #[must_use]
+    pub fn get_stacking_order(&self) -> Vec<Window> {
+        self.tags
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != self.active_tag)
+            .flat_map(|(_, t)| t.windows.iter().map(|w| w.window))
+            .chain(self.get_active_stacking_order().iter().map(|w| w.window))
+            .collect()
     }
 
     /// Sets the tag's master window to be the focused window.
@@ -202,90 +827,388 @@ impl StateHandler {
             self.tags[self.active_tag].windows.last().map(|w| w.window);
     }
 
-    /// Sets all windows in a tag that are not in the `Floating` group to be `Stack`, then sets the last non floating window to `Master`.
+    /// Sets all tiled windows in a tag to be `Stack`, then sets the last window to `Master` if it is tiled.
     pub fn set_last_master_others_stack(&mut self) {
         self.get_mut_active_tag_windows()
             .iter_mut()
-            .filter(|w| w.group != WindowGroup::Floating && w.group != WindowGroup::Fullscreen)
+            .filter(|w| w.is_tiled())
             .for_each(|w| w.group = WindowGroup::Stack);
 
         if let Some(w) = self.get_mut_active_tag_windows().last_mut() {
-            if w.group == WindowGroup::Floating || w.group == WindowGroup::Fullscreen {
+            if !w.is_tiled() {
                 return;
             }
             w.group = WindowGroup::Master;
         }
     }
 
+    /// Gets the area (x, y, width, height) windows are tiled in, which excludes the bar, the space reserved by docks and the gaps at the edges of the screen.
+    #[must_use]
+    pub const fn get_workarea(&self) -> (i16, i16, u16, u16) {
+        let TilingInfo { outer_gap, .. } = self.tiling;
+        let (x, y, width, height) = self.get_usable_area();
+        (
+            x + outer_gap as i16,
+            y + outer_gap as i16,
+            width.saturating_sub(outer_gap * 2),
+            height.saturating_sub(outer_gap * 2),
+        )
+    }
+
+    /// Gets the area (x, y, width, height) of the screen left by the bar and the space reserved by docks, without gaps.
+    #[must_use]
+    pub const fn get_usable_area(&self) -> (i16, i16, u16, u16) {
+        let TilingInfo {
+            max_width,
+            max_height,
+            bar_height,
+            ..
+        } = self.tiling;
+        let Struts {
+            left,
+            right,
+            top,
+            bottom,
+        } = self.reserved;
+        (
+            left as i16,
+            top as i16 + self.get_bar_offset(),
+            max_width.saturating_sub(left.saturating_add(right)),
+            max_height.saturating_sub(bar_height.saturating_add(top).saturating_add(bottom)),
+        )
+    }
+
+    /// Gets how far down the bar pushes the top of the work area, which is zero if the bar is at the bottom of the screen.
+    #[must_use]
+    pub const fn get_bar_offset(&self) -> i16 {
+        match self.tiling.bar_position {
+            BarPosition::Top => self.tiling.bar_height as i16,
+            BarPosition::Bottom => 0,
+        }
+    }
+
     /// Tiles the windows of a tag, changing their position and size.
     ///
-    /// Tiling is based around the dividing line that separates `Master` and `Stack` windows. The tiling ratio determines where this line sits.
+    /// Tiling is based around the dividing line that separates `Master` and `Stack` windows. The tag's tiling ratio determines where this line sits.
     ///
-    /// The `Master` window occupies the entirety of its side of the dividing line.
+    /// Tiled windows are separated from each other by the inner gap, and from the edges of the work area by the outer gap.
+    ///
+    /// The `Master` window occupies the entirety of its side of the dividing line. If it is the only tiled window and smart gaps are enabled, it occupies the whole screen except the bar instead, only keeping its border (if any) on screen.
     ///
     /// `Stack` windows are in a "stack group", where they are positioned top to bottom according to where they are in the list. Their size depends on how many windows there are, with the whole Stack group taking the entire space of its side of the dividing line.
     ///
+    /// In the tabbed layout, `Master` and `Stack` windows all take up the entire work area instead.
+    ///
     /// `Floating` windows do not obey stacking rules are are drawn on top of all other windows (except `Fullscreen` windows) and at the center of the screen.
     ///
+    /// `Maximized` windows take up the entire work area.
+    ///
     /// `Fullscreen` windows take up the entire screen and hide all other windows.
     pub fn tile_windows(&mut self) {
         log::debug!("tiling tag {}", self.active_tag);
 
-        let (gap, ratio) = (self.tiling.gap, self.tiling.ratio);
+        let workarea = self.get_workarea();
+        let smart_gaps = self.tiling.smart_gaps;
+        let border = if self.tiling.smart_borders {
+            0
+        } else {
+            self.tiling.border
+        };
+
+        let ratio = self.tags[self.active_tag].ratio;
+        let tabbed = self.tags[self.active_tag].layout == Layout::Tabbed;
+        let inner_gap = self.tiling.inner_gap;
         let (max_width, max_height) = (self.tiling.max_width, self.tiling.max_height);
-        let bar_height = self.tiling.bar_height;
+        let usable = self.get_usable_area();
 
         let stack_count = self
             .get_active_tag_windows()
             .iter()
             .filter(|w| w.group == WindowGroup::Stack)
             .count()
-            .clamp(0, 100);
+            .clamp(0, 100) as u16;
 
-        self.get_mut_active_tag_windows()
-            .iter_mut()
-            .enumerate()
-            .for_each(|(i, w)| match w.group {
+        let (area_x, area_y, area_width, area_height) = workarea;
+        let master_width = if stack_count == 0 {
+            area_width
+        } else {
+            ((f32::from(area_width) * (1.0 - ratio)) as u16).saturating_sub(inner_gap / 2)
+        };
+        let stack_x = area_x + master_width.saturating_add(inner_gap) as i16;
+        let stack_width = area_width.saturating_sub(master_width.saturating_add(inner_gap));
+        let stack_height = area_height
+            .saturating_sub(inner_gap.saturating_mul(stack_count.saturating_sub(1)))
+            / stack_count.max(1);
+
+        let mut stack_index = 0;
+        for w in self.get_mut_active_tag_windows() {
+            match w.group {
+                WindowGroup::Master | WindowGroup::Stack if tabbed => {
+                    (w.x, w.y, w.width, w.height) = workarea;
+                }
+                WindowGroup::Master if smart_gaps && stack_count == 0 => {
+                    w.x = usable.0;
+                    w.y = usable.1;
+                    w.width = usable.2.saturating_sub(border * 2);
+                    w.height = usable.3.saturating_sub(border * 2);
+                }
                 WindowGroup::Master => {
-                    w.x = gap as i16;
-                    w.y = gap as i16 + bar_height as i16;
-                    w.width = if stack_count == 0 {
-                        max_width - gap * 2
-                    } else {
-                        f32::from(max_width).mul_add(1.0 - ratio, -(f32::from(gap) * 2.0)) as u16
-                    };
-                    w.height = max_height - gap * 2 - bar_height;
+                    w.x = area_x;
+                    w.y = area_y;
+                    w.width = master_width;
+                    w.height = area_height;
                 }
                 WindowGroup::Stack => {
-                    w.x = (f32::from(max_width) * (1.0 - ratio)) as i16;
-                    w.y = if i == 0 {
-                        (i * (max_height as usize / stack_count) + gap as usize) as i16
-                            + bar_height as i16
-                    } else {
-                        (i * (max_height as usize / stack_count)) as i16
-                    };
-                    w.width = (f32::from(max_width) * ratio) as u16 - gap;
-
-                    w.height = if i == 0 {
-                        (max_height as usize / stack_count) as u16 - gap * 2 - bar_height
-                    } else {
-                        (max_height as usize / stack_count) as u16 - gap
-                    };
+                    w.x = stack_x;
+                    w.y = area_y + (stack_index * (stack_height + inner_gap)) as i16;
+                    w.width = stack_width;
+                    w.height = stack_height;
+                    stack_index += 1;
                 }
                 WindowGroup::Floating => (),
+                WindowGroup::Maximized => {
+                    (w.x, w.y, w.width, w.height) = workarea;
+                }
                 WindowGroup::Fullscreen => {
                     w.x = 0;
                     w.y = 0;
                     w.width = max_width;
                     w.height = max_height;
                 }
+            }
+        }
+    }
+
+    /// Gets the position (x, y) a window of the specified size has when snapped to a corner of the work area.
+    #[must_use]
+    pub const fn get_corner_position(&self, corner: Corner, width: u16, height: u16) -> (i16, i16) {
+        let (x, y, area_width, area_height) = self.get_workarea();
+        let right = x + area_width.saturating_sub(width) as i16;
+        let bottom = y + area_height.saturating_sub(height) as i16;
+        match corner {
+            Corner::TopLeft => (x, y),
+            Corner::TopRight => (right, y),
+            Corner::BottomLeft => (x, bottom),
+            Corner::BottomRight => (right, bottom),
+        }
+    }
+
+    /// Gets the position (x, y) of a new floating window of the specified size on the active tag, which didn't ask for a position.
+    ///
+    /// The size includes the title bar, but not the border.
+    #[must_use]
+    pub fn get_floating_position(
+        &self,
+        placement: Placement,
+        width: u16,
+        height: u16,
+    ) -> (i16, i16) {
+        let border = self.tiling.border;
+        // saturate so oversized windows or borders clamp instead of overflowing
+        let edges = border.saturating_mul(2);
+        let (outer_width, outer_height) =
+            (width.saturating_add(edges), height.saturating_add(edges));
+        let (x, y, area_width, area_height) = self.get_workarea();
+        let right = x + area_width.saturating_sub(outer_width) as i16;
+        let bottom = y + area_height.saturating_sub(outer_height) as i16;
+        let floating: Vec<&WindowState> = self
+            .get_active_tag_windows()
+            .iter()
+            .filter(|w| w.group == WindowGroup::Floating)
+            .collect();
+
+        match placement {
+            Placement::Center => (
+                self.tiling.max_width as i16 / 2 - width as i16 / 2,
+                self.tiling.max_height as i16 / 2 - height as i16 / 2,
+            ),
+            Placement::Cascade => {
+                let steps = ((right - x).min(bottom - y) / CASCADE_STEP).max(0) + 1;
+                let position = (0..steps)
+                    .map(|i| (x + i * CASCADE_STEP, y + i * CASCADE_STEP))
+                    .find(|&(px, py)| !floating.iter().any(|w| w.x == px && w.y == py));
+                position.unwrap_or((x, y))
+            }
+            Placement::Smart => {
+                // the best spots are along the edges of the work area and of other windows
+                let mut xs = vec![x, right];
+                let mut ys = vec![y, bottom];
+                for w in &floating {
+                    xs.push(w.x.saturating_add_unsigned(w.width.saturating_add(edges)));
+                    xs.push(w.x.saturating_sub_unsigned(outer_width));
+                    ys.push(w.y.saturating_add_unsigned(w.height.saturating_add(edges)));
+                    ys.push(w.y.saturating_sub_unsigned(outer_height));
+                }
+                let overlap = |(px, py): (i16, i16)| -> i64 {
+                    floating
+                        .iter()
+                        .map(|w| {
+                            let overlap_x = (i32::from(px) + i32::from(outer_width))
+                                .min(i32::from(w.x) + i32::from(w.width) + i32::from(edges))
+                                - i32::from(px.max(w.x));
+                            let overlap_y = (i32::from(py) + i32::from(outer_height))
+                                .min(i32::from(w.y) + i32::from(w.height) + i32::from(edges))
+                                - i32::from(py.max(w.y));
+                            i64::from(overlap_x.max(0)) * i64::from(overlap_y.max(0))
+                        })
+                        .sum()
+                };
+                ys.iter()
+                    .flat_map(|&py| xs.iter().map(move |&px| (px, py)))
+                    .filter(|&(px, py)| (x..=right).contains(&px) && (y..=bottom).contains(&py))
+                    .min_by_key(|&(px, py)| (overlap((px, py)), py, px))
+                    .unwrap_or((x, y))
+            }
+        }
+    }
+
+    /// Moves the sticky windows of a tag to another tag.
+    ///
+    /// The windows are placed at the start of the tag so they don't take the place of the `Master` window. If the tag loses its focused window its focus is set to its master window.
+    ///
+    /// `Fullscreen` windows stay on their tag, so they never cover the windows of other tags.
+    pub fn move_sticky_windows(&mut self, from: usize, to: usize) {
+        let follows = |w: &WindowState| w.sticky && w.group != WindowGroup::Fullscreen;
+        let sticky: Vec<WindowState> = self.tags[from]
+            .windows
+            .iter()
+            .filter(|w| follows(w))
+            .copied()
+            .collect();
+        self.tags[from].windows.retain(|w| !follows(w));
+        if self.tags[from]
+            .focus
+            .is_some_and(|f| sticky.iter().any(|w| w.window == f))
+        {
+            self.tags[from].focus = self.tags[from].windows.last().map(|w| w.window);
+        }
+        self.tags[to].windows.splice(0..0, sticky);
+    }
+
+    /// Clamps the geometry of the active tag's windows to sane bounds, logging any violations.
+    ///
+    /// Windows are made at least 1x1 pixels in size, and are moved so that they at least partly intersect the work area.
+    pub fn validate_geometry(&mut self) {
+        let area = self.get_workarea();
+        self.get_mut_active_tag_windows()
+            .iter_mut()
+            .for_each(|w| clamp_geometry(w, area));
+    }
+
+    /// Snaps the position of a moved floating window so its edges line up with the edges of the screen, the bar or the active tag's other floating windows, if they're close enough.
+    ///
+    /// Each axis snaps to its closest edge within the snapping distance.
+    #[must_use]
+    pub fn snap_position(
+        &self,
+        window: Window,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> (i16, i16) {
+        let TilingInfo {
+            max_width,
+            max_height,
+            bar_height,
+            border,
+            snap_distance,
+            ..
+        } = self.tiling;
+        if snap_distance == 0 {
+            return (x, y);
+        }
+
+        let mut targets_x = vec![0, i32::from(max_width)];
+        let mut targets_y = vec![0, i32::from(max_height)];
+        if bar_height > 0 {
+            targets_y.push(match self.tiling.bar_position {
+                BarPosition::Top => i32::from(bar_height),
+                BarPosition::Bottom => i32::from(max_height) - i32::from(bar_height),
             });
+        }
+        for w in self
+            .get_active_tag_windows()
+            .iter()
+            .filter(|w| w.group == WindowGroup::Floating && w.window != window)
+        {
+            targets_x.extend([
+                i32::from(w.x),
+                i32::from(w.x) + i32::from(w.width) + i32::from(border) * 2,
+            ]);
+            targets_y.extend([
+                i32::from(w.y),
+                i32::from(w.y) + i32::from(w.height) + i32::from(border) * 2,
+            ]);
+        }
+
+        let distance = i32::from(snap_distance);
+        let outer = |size: u16| i32::from(size) + i32::from(border) * 2;
+        (
+            snap_axis(i32::from(x), outer(width), &targets_x, distance) as i16,
+            snap_axis(i32::from(y), outer(height), &targets_y, distance) as i16,
+        )
     }
 
     /// Sets the window groups and tiles the windows of the active tag.
+    ///
+    /// The focused window's activity timestamp is also updated, and the resulting geometries are validated.
     pub fn refresh(&mut self) {
+        self.touch_focus();
         self.set_last_master_others_stack();
         self.tile_windows();
+        self.validate_geometry();
+    }
+
+    /// Marks the focused window of the active tag as being used right now.
+    pub fn touch_focus(&mut self) {
+        let Some(focus) = self.get_focus() else {
+            return;
+        };
+        if let Some(w) = self.get_mut_window_state(focus) {
+            w.last_focus = Instant::now();
+        }
+    }
+
+    /// Moves every window that hasn't been focused for longer than `idle` to the parking tag.
+    ///
+    /// Windows already on the parking tag are left alone. Tags losing their focused window have their focus set to their master window.
+    ///
+    /// Returns the windows that were moved along with the index of the tag they were moved from.
+    pub fn park_idle_windows(
+        &mut self,
+        parking_tag: usize,
+        idle: Duration,
+    ) -> Vec<(usize, WindowState)> {
+        let mut parked = Vec::new();
+
+        self.tags
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| *i != parking_tag)
+            .for_each(|(i, tag)| {
+                tag.windows.retain(|w| {
+                    if w.group == WindowGroup::Fullscreen
+                        || w.sticky
+                        || w.last_focus.elapsed() < idle
+                    {
+                        return true;
+                    }
+                    parked.push((i, *w));
+                    false
+                });
+                if tag
+                    .focus
+                    .is_some_and(|f| !tag.windows.iter().any(|w| w.window == f))
+                {
+                    tag.focus = tag.windows.last().map(|w| w.window);
+                }
+            });
+
+        self.tags[parking_tag]
+            .windows
+            .extend(parked.iter().map(|(_, w)| *w));
+        parked
     }
 
     /// Swaps the currently focused window with the `Master` window, changing their positions and sizes.
@@ -309,22 +1232,120 @@ impl StateHandler {
         self.tags[self.active_tag].windows.swap(index_f, index_m);
     }
 
+    /// Promotes the focused `Stack` window to `Master`, pushing the old `Master` window to the top of the stack. The rest of the stack keeps its order.
+    ///
+    /// If the `Master` window is focused, the window at the top of the stack is promoted and focused instead. Nothing changes for windows that aren't tiled.
+    pub fn zoom(&mut self) {
+        let tag = &mut self.tags[self.active_tag];
+        let Some(focus) = tag.focus else {
+            return;
+        };
+        let windows = &mut tag.windows;
+        let Some(index) = windows.iter().position(|w| w.window == focus) else {
+            return;
+        };
+        if !windows[index].is_tiled() {
+            return;
+        }
+        let last = windows.len() - 1;
+        if index == last {
+            let Some(top) = windows.iter().position(|w| w.group == WindowGroup::Stack) else {
+                return;
+            };
+            windows.swap(top, last);
+            tag.focus = Some(windows[last].window);
+            return;
+        }
+        let window = windows.remove(index);
+        if windows[last - 1].group == WindowGroup::Master {
+            let master = windows.remove(last - 1);
+            let top = windows
+                .iter()
+                .position(|w| w.group == WindowGroup::Stack)
+                .unwrap_or(windows.len());
+            windows.insert(top, master);
+        }
+        windows.push(window);
+    }
+
+    /// Rotates the tiled windows of the active tag by the specified change, going from the `Master` window down the stack. A positive change moves each window further down, with the bottom of the stack wrapping around to `Master`.
+    ///
+    /// The focus stays at the same position on the screen, so it moves to whichever window is rotated there. Windows that aren't tiled keep their place.
+    pub fn rotate_stack(&mut self, change: i16) {
+        let tag = &mut self.tags[self.active_tag];
+        let windows = &mut tag.windows;
+        // the positions of the tiled windows, from the master window down the stack
+        let mut slots: Vec<usize> = (0..windows.len())
+            .filter(|&i| windows[i].is_tiled())
+            .collect();
+        if windows.last().is_some_and(WindowState::is_tiled) {
+            slots.rotate_right(1);
+        }
+        if slots.len() < 2 {
+            return;
+        }
+        let focus_slot = slots
+            .iter()
+            .position(|&i| Some(windows[i].window) == tag.focus);
+
+        let mut tiled: Vec<WindowState> = slots.iter().map(|&i| windows[i]).collect();
+        tiled.rotate_right(i32::from(change).rem_euclid(slots.len() as i32) as usize);
+        for (&i, window) in slots.iter().zip(tiled) {
+            windows[i] = window;
+        }
+        if let Some(slot) = focus_slot {
+            tag.focus = Some(windows[slots[slot]].window);
+        }
+    }
+
+    /// Gets the tag the specified change away from the active tag, wrapping around. If negative, tags are counted in the opposite order.
+    ///
+    /// When only cycling through occupied tags, empty tags aren't counted. The active tag is returned if every other tag is empty.
+    #[must_use]
+    pub fn get_next_tag(&self, change: i16, cycle: TagCycle) -> usize {
+        let len = self.tags.len() as i16;
+        if cycle == TagCycle::All {
+            return (self.active_tag as i16 + change).rem_euclid(len) as usize;
+        }
+        let step = change.signum();
+        let mut tag = self.active_tag;
+        for _ in 0..change.unsigned_abs() {
+            let next = (1..len)
+                .map(|i| (tag as i16 + step * i).rem_euclid(len) as usize)
+                .find(|&t| !self.tags[t].windows.is_empty());
+            let Some(next) = next else {
+                break;
+            };
+            tag = next;
+        }
+        tag
+    }
+
     /// Changes the focused window to be the next one in the list, with change denoting the jump in index. If negative, the focus is changed in the opposite order.
-    pub fn switch_focus_next(&mut self, change: i16) {
+    ///
+    /// When cycling through groups, windows in the same group as the focused window are skipped.
+    pub fn switch_focus_next(&mut self, change: i16, cycle: FocusCycle) {
         let Some(focus_window) = self.tags[self.active_tag].focus else {
             return;
         };
-        let Some(focus_index) = self
-            .get_active_tag_windows()
-            .iter()
-            .position(|w| w.window == focus_window)
-        else {
+        let windows = self.get_active_tag_windows();
+        let Some(focus_index) = windows.iter().position(|w| w.window == focus_window) else {
             return;
         };
-        let focus_index = focus_index as i16 + change;
-        let focus_index = focus_index.rem_euclid(self.get_active_tag_windows().len() as i16);
-        self.tags[self.active_tag].focus =
-            Some(self.get_active_tag_windows()[focus_index as usize].window);
+        let focus = windows[focus_index];
+        let len = windows.len() as i16;
+        let mut index = focus_index as i16;
+        for _ in 0..len {
+            index = (index + change).rem_euclid(len);
+            let w = &windows[index as usize];
+            if cycle == FocusCycle::Windows
+                || w.window == focus.window
+                || !w.is_grouped_with(&focus)
+            {
+                break;
+            }
+        }
+        self.tags[self.active_tag].focus = Some(windows[index as usize].window);
     }
 
     /// Logs the state of the manager:
@@ -335,6 +1356,27 @@ impl StateHandler {
         log::trace!("Manager state:\n{self}");
     }
 
+    /// Gets the symbol of the active tag's layout, as shown on the bar.
+    ///
+    /// Tags whose focused window is maximized are shown as monocle, and tags with windows but none of them tiled are shown as floating.
+    #[must_use]
+    pub fn get_layout_symbol(&self) -> &'static str {
+        let windows = self.get_active_tag_windows();
+        let monocle = self
+            .get_focus()
+            .and_then(|f| self.get_window_state(f))
+            .is_some_and(|w| matches!(w.group, WindowGroup::Maximized | WindowGroup::Fullscreen));
+        if monocle {
+            LAYOUT_SYMBOLS[1]
+        } else if self.tags[self.active_tag].layout == Layout::Tabbed {
+            LAYOUT_SYMBOLS[3]
+        } else if !windows.is_empty() && !windows.iter().any(WindowState::is_tiled) {
+            LAYOUT_SYMBOLS[2]
+        } else {
+            LAYOUT_SYMBOLS[0]
+        }
+    }
+
     /// Gets the tags that have a window in them and represents them in a bitmask.
     #[must_use]
     pub fn get_tag_bitmask(&self) -> u16 {
@@ -355,3 +1397,129 @@ impl StateHandler {
             .position(|w| w.window == window || w.frame_window == window)
     }
 }
+
+/// Moves a span (start, size) along an axis so that its closest edge to any of the targets lines up with it.
+///
+/// The span is left alone if no target is within the distance.
+fn snap_axis(start: i32, size: i32, targets: &[i32], distance: i32) -> i32 {
+    targets
+        .iter()
+        .flat_map(|t| [t - start, t - (start + size)])
+        .filter(|d| d.abs() <= distance)
+        .min_by_key(|d| d.abs())
+        .map_or(start, |d| start + d)
+}
+
+/// Clamps a window's geometry to be at least 1x1 pixels and to at least partly intersect the area (x, y, width, height).
+fn clamp_geometry(window: &mut WindowState, area: (i16, i16, u16, u16)) {
+    if window.width == 0 || window.height == 0 {
+        log::warn!(
+            "window {} has invalid size {}x{}, clamping",
+            window.window,
+            window.width,
+            window.height
+        );
+        window.width = window.width.max(1);
+        window.height = window.height.max(1);
+    }
+
+    let (ax, ay) = (i32::from(area.0), i32::from(area.1));
+    let (aw, ah) = (i32::from(area.2.max(1)), i32::from(area.3.max(1)));
+    let (x, y) = (i32::from(window.x), i32::from(window.y));
+    let (width, height) = (i32::from(window.width), i32::from(window.height));
+
+    let clamped_x = x.clamp(ax - width + 1, ax + aw - 1);
+    let clamped_y = y.clamp(ay - height + 1, ay + ah - 1);
+    if clamped_x != x || clamped_y != y {
+        log::warn!(
+            "window {} at {x},{y} is outside the work area, moving it to {clamped_x},{clamped_y}",
+            window.window
+        );
+        window.x = clamped_x as i16;
+        window.y = clamped_y as i16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets a floating window with a title bar, sized in 7x15 increments like a terminal.
+    fn terminal(group: WindowGroup) -> WindowState {
+        let mut window = WindowState::new(10, 11);
+        window.x = 100;
+        window.y = 50;
+        window.width = 500;
+        window.height = 300;
+        window.group = group;
+        window.hints.base = (2, 4);
+        window.hints.increment = (7, 15);
+        window
+    }
+
+    #[test]
+    fn client_geometry_is_below_title_bar_and_inside_border() {
+        let window = terminal(WindowGroup::Floating);
+        let title = window.title_height(TitleBars::Floating, 20);
+        assert_eq!(title, 20);
+        assert_eq!(
+            window.client_geometry(Increments::Respect, 2, title),
+            (102, 72, 500, 280)
+        );
+    }
+
+    #[test]
+    fn client_geometry_of_tiled_windows_respects_increments() {
+        let window = terminal(WindowGroup::Stack);
+        let title = window.title_height(TitleBars::Floating, 20);
+        assert_eq!(title, 0);
+        // 2 + 71 * 7 = 499 and 4 + 19 * 15 = 289
+        assert_eq!(
+            window.client_geometry(Increments::Respect, 2, title),
+            (102, 52, 499, 289)
+        );
+        assert_eq!(
+            window.client_geometry(Increments::Ignore, 2, title),
+            (102, 52, 500, 300)
+        );
+    }
+
+    #[test]
+    fn client_geometry_fits_increments_before_removing_title_bar() {
+        let window = terminal(WindowGroup::Maximized);
+        assert_eq!(
+            window.client_geometry(Increments::Respect, 0, 20),
+            (100, 70, 499, 269)
+        );
+    }
+
+    #[test]
+    fn floating_position_of_oversized_window_does_not_overflow() {
+        let mut state = StateHandler::new(TilingInfo {
+            inner_gap: 0,
+            outer_gap: 0,
+            ratio: 0.5,
+            max_width: 1920,
+            max_height: 1080,
+            bar_height: 0,
+            bar_position: BarPosition::Top,
+            border: u16::MAX / 2,
+            smart_gaps: false,
+            smart_borders: false,
+            snap_distance: 0,
+        });
+        let mut window = terminal(WindowGroup::Floating);
+        window.width = u16::MAX;
+        window.height = u16::MAX;
+        state.add_window(window);
+        for placement in [Placement::Center, Placement::Cascade, Placement::Smart] {
+            assert_eq!(
+                state.get_floating_position(placement, u16::MAX, u16::MAX).0,
+                match placement {
+                    Placement::Center => 960 - u16::MAX as i16 / 2,
+                    _ => 0,
+                }
+            );
+        }
+    }
+}
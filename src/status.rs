@@ -0,0 +1,130 @@
+//!
+//! This module provides a read-only status socket for external bars (e.g. lemonbar or polybar).
+//!
+//! Every connected client receives one line of text each time the tags or the focused window's title change, which can be displayed as is. A client receives the current line as soon as it connects.
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+/// The number of tags shown in a status line.
+const TAG_COUNT: usize = 9;
+
+/// A helper that streams the manager's status to the clients of a unix socket.
+pub struct StatusServer {
+    /// The socket's listener and path. Is `None` if the socket couldn't be created.
+    listener: Option<(UnixListener, PathBuf)>,
+    /// The connected clients.
+    clients: Vec<UnixStream>,
+    /// The last line sent to clients.
+    line: String,
+}
+
+impl StatusServer {
+    /// Creates a new server listening on `$XDG_RUNTIME_DIR/hematite/status.sock`.
+    ///
+    /// Errors are logged and leave the server disabled.
+    #[must_use]
+    pub fn new() -> Self {
+        let listener = match bind() {
+            Ok(listener) => Some(listener),
+            Err(e) => {
+                log::error!("couldn't create status socket {e:?}");
+                None
+            }
+        };
+        Self {
+            listener,
+            clients: Vec::new(),
+            line: String::new(),
+        }
+    }
+
+    /// Creates a server without a socket, which publishes nothing.
+    ///
+    /// This is useful when a running manager's socket must be left alone, e.g. with a mock connection.
+    #[must_use]
+    pub const fn disabled() -> Self {
+        Self {
+            listener: None,
+            clients: Vec::new(),
+            line: String::new(),
+        }
+    }
+
+    /// Sends the status to every client if it changed, accepting any pending clients first.
+    ///
+    /// Clients that can't keep up or have disconnected are dropped.
+    pub fn publish(&mut self, active_tag: usize, tag_bitmask: u16, title: &str) {
+        let Some((listener, _)) = &self.listener else {
+            return;
+        };
+
+        let line = format_line(active_tag, tag_bitmask, title);
+        let changed = line != self.line;
+        self.line = line;
+
+        let mut new_clients = Vec::new();
+        while let Ok((client, _)) = listener.accept() {
+            if client.set_nonblocking(true).is_ok() {
+                log::debug!("status client connected");
+                new_clients.push(client);
+            }
+        }
+
+        let send = |c: &mut UnixStream| c.write_all(self.line.as_bytes()).is_ok();
+        new_clients.retain_mut(send);
+        if changed {
+            self.clients.retain_mut(send);
+        }
+        self.clients.append(&mut new_clients);
+    }
+}
+
+impl Default for StatusServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        if let Some((_, path)) = &self.listener {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Binds a non blocking listener to the status socket, replacing any stale socket left behind.
+fn bind() -> std::io::Result<(UnixListener, PathBuf)> {
+    let path = xdg::BaseDirectories::with_prefix("hematite").place_runtime_file("status.sock")?;
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    listener.set_nonblocking(true)?;
+    log::info!("status socket listening on {}", path.display());
+    Ok((listener, path))
+}
+
+/// Formats a status line, such as `1 [2] 5 | Firefox`.
+///
+/// Occupied tags are listed by number, with the active tag in brackets. The title is omitted if no window is focused.
+fn format_line(active_tag: usize, tag_bitmask: u16, title: &str) -> String {
+    let mut line = (0..TAG_COUNT)
+        .filter(|&i| i == active_tag || tag_bitmask & (1 << i) != 0)
+        .map(|i| {
+            if i == active_tag {
+                format!("[{}]", i + 1)
+            } else {
+                (i + 1).to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+    if !title.is_empty() {
+        let _ = write!(line, " | {}", title.replace('\n', " "));
+    }
+    line.push('\n');
+    line
+}
@@ -0,0 +1,557 @@
+//!
+//! This module reads the config file, reporting each invalid entry with its key path and line, and dropping only that entry.
+//!
+//! Invalid entries are found one at a time by deserializing the config: an invalid element of a list (such as a hotkey) is dropped, and any other invalid value is replaced by its default. Lists can also be checked for values that deserialize fine but can't be used, such as a hotkey with an unknown key. A syntax error still falls back to the default config, as nothing after it can be read reliably.
+//!
+//! The config can be split into several files with `include = ["keys.toml"]`. Included files are merged into the config table by table, with the including file taking precedence, and each invalid entry is reported in the file it comes from.
+use core::fmt;
+use std::{collections::HashMap, path::Path};
+
+use serde::de::DeserializeOwned;
+use toml::{
+    Spanned, Table, Value,
+    de::{DeTable, DeValue},
+};
+
+use crate::connection::expand_vars;
+
+/// The most invalid entries fixed before giving up on the config.
+const MAX_FIXES: usize = 64;
+
+/// An invalid entry of the config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The name of the file the entry is in, such as `config.toml`.
+    pub file: String,
+    /// The key path of the entry (e.g. `hotkeys[3].key`). Is empty for the whole config.
+    pub path: String,
+    /// The line of the entry in the config file, if it's in the file.
+    pub line: Option<usize>,
+    /// What is wrong with the entry, and what was done about it.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+        }
+        f.write_str(": ")?;
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// A step of a key path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// A key of a table.
+    Key(String),
+    /// An index of a list.
+    Index(usize),
+}
+
+/// A helper that reads a config file and the files it includes, fixing their invalid entries.
+pub struct Validator {
+    /// The name and text of each file, starting with the including file.
+    files: Vec<(String, String)>,
+    /// The config with the invalid entries fixed so far.
+    table: Table,
+    /// The serialized default config, replacing invalid entries.
+    default: Table,
+    /// The indices in the config file of the dropped list elements, for each list.
+    dropped: HashMap<String, Vec<usize>>,
+    /// The invalid entries found so far.
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    /// Parses a config file, named e.g. `config.toml` in diagnostics.
+    /// # Errors
+    /// Returns a diagnostic pointing at the syntax error if the file isn't valid TOML.
+    pub fn new(name: &str, source: &str, default: Table) -> Result<Self, Diagnostic> {
+        Ok(Self {
+            table: parse(name, source)?,
+            files: vec![(name.to_owned(), source.to_owned())],
+            default,
+            dropped: HashMap::new(),
+            diagnostics: Vec::new(),
+        })
+    }
+
+    /// Merges the files listed in the config's `include` key into the config, with paths relative to the specified directory.
+    ///
+    /// Files that can't be read or parsed are reported and skipped. Included files can't include other files.
+    pub fn include_files(&mut self, dir: &Path) {
+        let names: Vec<String> = self
+            .table
+            .get("include")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(String::from)
+            .collect();
+        for name in names {
+            let path = dir.join(expand_vars(&name));
+            log::info!("including config from {}", path.display());
+            let table = std::fs::read_to_string(&path)
+                .map_err(|e| Diagnostic {
+                    file: name.clone(),
+                    path: String::new(),
+                    line: None,
+                    message: format!("couldn't read file: {e}"),
+                })
+                .and_then(|source| Ok((parse(&name, &source)?, source)));
+            match table {
+                Ok((table, source)) => {
+                    merge(&mut self.table, table);
+                    self.files.push((name, source));
+                }
+                Err(diagnostic) => self.diagnostics.push(Diagnostic {
+                    message: format!("{}, skipping the file", diagnostic.message),
+                    ..diagnostic
+                }),
+            }
+        }
+    }
+
+    /// Drops the elements of a list that the check rejects.
+    ///
+    /// The check returns the key of the element's offending field along with what is wrong with it.
+    pub fn check_list(&mut self, key: &str, check: impl Fn(&Value) -> Option<(String, String)>) {
+        let invalid: Vec<(usize, String, String)> = self
+            .table
+            .get(key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .filter_map(|(i, v)| check(v).map(|(field, message)| (i, field, message)))
+            .collect();
+        // dropped from the back, so the indices of the rest stay the same
+        let reported = self.diagnostics.len();
+        for (i, field, message) in invalid.into_iter().rev() {
+            let path = [
+                Segment::Key(key.to_owned()),
+                Segment::Index(i),
+                Segment::Key(field),
+            ];
+            self.drop_element(&path, 1, &message);
+        }
+        self.diagnostics[reported..].reverse();
+    }
+
+    /// Deserializes the config, fixing invalid entries until it's valid.
+    ///
+    /// Returns `None` if the config couldn't be fixed, along with the invalid entries found.
+    #[must_use]
+    pub fn deserialize<T: DeserializeOwned>(mut self) -> (Option<T>, Vec<Diagnostic>) {
+        for _ in 0..MAX_FIXES {
+            let Ok(text) = toml::to_string(&self.table) else {
+                break;
+            };
+            let error = match toml::from_str(&text) {
+                Ok(config) => return (Some(config), self.diagnostics),
+                Err(error) => error,
+            };
+            // an empty span points at the whole config, e.g. for a missing section
+            let path = error
+                .span()
+                .filter(|s| !s.is_empty())
+                .zip(DeTable::parse(&text).ok())
+                .and_then(|(span, table)| find_in_table(table.get_ref(), span.start))
+                .unwrap_or_default();
+            if !self.fix(&path, error.message()) {
+                self.report(&path, format!("{}, couldn't be fixed", error.message()));
+                break;
+            }
+        }
+        (None, self.diagnostics)
+    }
+
+    /// Fixes the invalid entry at a path, dropping the list element it's in or replacing it by its default.
+    ///
+    /// A table missing keys gets them from its default instead. Returns false if nothing could be changed.
+    fn fix(&mut self, path: &[Segment], message: &str) -> bool {
+        if let Some(index) = path.iter().position(|s| matches!(s, Segment::Index(_))) {
+            return self.drop_element(path, index, message);
+        }
+        let Some((Segment::Key(key), parent)) = path.split_last() else {
+            if !fill(&mut self.table, self.default.clone()) {
+                return false;
+            }
+            self.report(path, format!("{message}, using the default"));
+            return true;
+        };
+        let default = get(&self.default, path).cloned();
+        let parent = if parent.is_empty() {
+            &mut self.table
+        } else if let Some(Value::Table(parent)) = get_mut(&mut self.table, parent) {
+            parent
+        } else {
+            return false;
+        };
+
+        let fixed = match (parent.get_mut(key), default) {
+            (Some(Value::Table(table)), Some(Value::Table(default))) => {
+                fill(table, default).then_some("using the default")
+            }
+            (value, Some(default)) if value.as_deref() != Some(&default) => {
+                parent.insert(key.clone(), default);
+                Some("using the default")
+            }
+            (_, None) => parent.remove(key).map(|_| "ignoring it"),
+            _ => None,
+        };
+        let Some(fix) = fixed else {
+            return false;
+        };
+        self.report(path, format!("{message}, {fix}"));
+        true
+    }
+
+    /// Drops the list element containing the invalid entry at a path, where `index` is the position of the element's index in the path.
+    ///
+    /// Returns false if the element doesn't exist.
+    fn drop_element(&mut self, path: &[Segment], index: usize, message: &str) -> bool {
+        let (Some(Value::Array(list)), Segment::Index(i)) =
+            (get_mut(&mut self.table, &path[..index]), &path[index])
+        else {
+            return false;
+        };
+        if *i >= list.len() {
+            return false;
+        }
+        list.remove(*i);
+        self.report(path, format!("{message}, dropping the entry"));
+
+        let original = self.original_path(&path[..=index]);
+        if let Some((Segment::Index(i), list)) = original.split_last() {
+            self.dropped.entry(path_string(list)).or_default().push(*i);
+        }
+        true
+    }
+
+    /// Records an invalid entry, pointing at its line in the config file.
+    ///
+    /// The entry is reported in the file defining the most of its path, preferring the including file.
+    fn report(&mut self, path: &[Segment], message: String) {
+        let original = self.original_path(path);
+        let (file, line) = self
+            .files
+            .iter()
+            .filter_map(|(name, source)| {
+                let table = DeTable::parse(source).ok()?;
+                let (depth, position) = find_span(table.get_ref(), &original)?;
+                Some((depth, name, line_of(source, position)))
+            })
+            .rev()
+            .max_by_key(|(depth, ..)| *depth)
+            .map_or((self.files[0].0.clone(), None), |(_, name, line)| {
+                (name.clone(), Some(line))
+            });
+        self.diagnostics.push(Diagnostic {
+            file,
+            path: path_string(&original),
+            line,
+            message,
+        });
+    }
+
+    /// Converts a path in the fixed config to a path in the config file, counting the list elements dropped before each index.
+    fn original_path(&self, path: &[Segment]) -> Vec<Segment> {
+        let mut original = Vec::new();
+        for segment in path {
+            let segment = match segment {
+                Segment::Index(i) => {
+                    let dropped = self.dropped.get(&path_string(&original));
+                    let is_dropped = |j: &usize| dropped.is_some_and(|d| d.contains(j));
+                    let j = (0..).filter(|j| !is_dropped(j)).nth(*i).unwrap_or(*i);
+                    Segment::Index(j)
+                }
+                Segment::Key(_) => segment.clone(),
+            };
+            original.push(segment);
+        }
+        original
+    }
+}
+
+/// Adds the keys of the default table that a table is missing.
+///
+/// Returns false if no key was missing.
+fn fill(table: &mut Table, default: Table) -> bool {
+    let missing: Vec<(String, Value)> = default
+        .into_iter()
+        .filter(|(k, _)| !table.contains_key(k))
+        .collect();
+    let filled = !missing.is_empty();
+    table.extend(missing);
+    filled
+}
+
+/// Formats a key path, such as `hotkeys[3].key`.
+fn path_string(path: &[Segment]) -> String {
+    let mut string = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if string.is_empty() => string.push_str(key),
+            Segment::Key(key) => {
+                string.push('.');
+                string.push_str(key);
+            }
+            Segment::Index(i) => {
+                string.push('[');
+                string.push_str(&i.to_string());
+                string.push(']');
+            }
+        }
+    }
+    string
+}
+
+/// Gets the line (starting from 1) of a byte position in a text.
+fn line_of(source: &str, position: usize) -> usize {
+    source
+        .get(..position)
+        .map_or(0, |s| s.matches('\n').count())
+        + 1
+}
+
+/// Gets the value at a path.
+fn get<'t>(table: &'t Table, path: &[Segment]) -> Option<&'t Value> {
+    let (first, rest) = path.split_first()?;
+    let Segment::Key(key) = first else {
+        return None;
+    };
+    rest.iter()
+        .try_fold(table.get(key)?, |value, segment| match (value, segment) {
+            (Value::Table(table), Segment::Key(key)) => table.get(key),
+            (Value::Array(list), Segment::Index(i)) => list.get(*i),
+            _ => None,
+        })
+}
+
+/// Gets the value at a path mutably. An empty path gets the whole table.
+fn get_mut<'t>(table: &'t mut Table, path: &[Segment]) -> Option<&'t mut Value> {
+    let (first, rest) = path.split_first()?;
+    let Segment::Key(key) = first else {
+        return None;
+    };
+    rest.iter().try_fold(table.get_mut(key)?, |value, segment| {
+        match (value, segment) {
+            (Value::Table(table), Segment::Key(key)) => table.get_mut(key),
+            (Value::Array(list), Segment::Index(i)) => list.get_mut(*i),
+            _ => None,
+        }
+    })
+}
+
+/// Finds the path of the deepest entry of a parsed table at a byte position.
+fn find_in_table(table: &DeTable<'_>, position: usize) -> Option<Vec<Segment>> {
+    table.iter().find_map(|(key, value)| {
+        let path = find_in_value(value, position)
+            .or_else(|| key.span().contains(&position).then(Vec::new))?;
+        Some([vec![Segment::Key(key.get_ref().to_string())], path].concat())
+    })
+}
+
+/// Finds the path of the deepest entry of a parsed value at a byte position, relative to the value.
+fn find_in_value(value: &Spanned<DeValue<'_>>, position: usize) -> Option<Vec<Segment>> {
+    let inner = match value.get_ref() {
+        DeValue::Table(table) => find_in_table(table, position),
+        DeValue::Array(list) => list.iter().enumerate().find_map(|(i, v)| {
+            let path = find_in_value(v, position)?;
+            Some([vec![Segment::Index(i)], path].concat())
+        }),
+        _ => None,
+    };
+    inner.or_else(|| value.span().contains(&position).then(Vec::new))
+}
+
+/// Finds the byte position of the deepest entry of a parsed table along a path, along with the number of steps of the path it's at.
+fn find_span(table: &DeTable<'_>, path: &[Segment]) -> Option<(usize, usize)> {
+    let (Segment::Key(key), rest) = path.split_first()? else {
+        return None;
+    };
+    let (key, mut value) = table.iter().find(|(k, _)| k.get_ref() == key)?;
+    let mut position = key.span().start;
+    let mut depth = 1;
+    for segment in rest {
+        let next = match (value.get_ref(), segment) {
+            (DeValue::Table(table), Segment::Key(key)) => table
+                .iter()
+                .find(|(k, _)| k.get_ref() == key)
+                .map(|(k, v)| (k.span().start, v)),
+            (DeValue::Array(list), Segment::Index(i)) => list.get(*i).map(|v| (v.span().start, v)),
+            _ => None,
+        };
+        let Some((start, next)) = next else {
+            break;
+        };
+        position = start;
+        value = next;
+        depth += 1;
+    }
+    Some((depth, position))
+}
+
+/// Parses a file into a table.
+/// # Errors
+/// Returns a diagnostic pointing at the syntax error if the file isn't valid TOML.
+fn parse(name: &str, source: &str) -> Result<Table, Diagnostic> {
+    toml::from_str(source).map_err(|e| Diagnostic {
+        file: name.to_owned(),
+        path: String::new(),
+        line: e.span().map(|s| line_of(source, s.start)),
+        message: e.message().to_owned(),
+    })
+}
+
+/// Merges a table into another, table by table. Keys the table already has are kept.
+fn merge(table: &mut Table, other: Table) {
+    for (key, value) in other {
+        match (table.get_mut(&key), value) {
+            (Some(Value::Table(table)), Value::Table(other)) => merge(table, other),
+            (Some(_), _) => (),
+            (None, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::config::check_hotkey;
+
+    /// A config with a list of hotkeys and a plain value, like the sections of the real one.
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct TestConfig {
+        /// The gap between windows.
+        #[serde(default)]
+        gap: u16,
+        /// The hotkeys.
+        hotkeys: Vec<TestHotkey>,
+    }
+
+    /// A hotkey without an action, rejected by `check_hotkey` if its key or a modifier is unknown.
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestHotkey {
+        /// The modifiers, separated by `|`.
+        modifiers: String,
+        /// The non modifier key.
+        key: String,
+    }
+
+    /// Validates a config, returning it with its invalid entries fixed along with the diagnostics.
+    fn validate(source: &str) -> (Option<TestConfig>, Vec<Diagnostic>) {
+        let default = Table::try_from(TestConfig::default()).unwrap();
+        let mut validator = Validator::new("config.toml", source, default).unwrap();
+        validator.check_list("hotkeys", check_hotkey);
+        validator.deserialize()
+    }
+
+    /// Gets the keys of a config's hotkeys.
+    fn keys(config: &TestConfig) -> Vec<&str> {
+        config.hotkeys.iter().map(|h| h.key.as_str()).collect()
+    }
+
+    #[test]
+    fn unknown_keysym_drops_only_its_hotkey() {
+        let (config, diagnostics) = validate(
+            r#"
+[[hotkeys]]
+modifiers = "MOD"
+key = "q"
+
+[[hotkeys]]
+modifiers = "MOD"
+key = "XK_Nope"
+
+[[hotkeys]]
+modifiers = "MOD"
+key = "w"
+"#,
+        );
+        assert_eq!(keys(&config.unwrap()), ["q", "w"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, "hotkeys[1].key");
+        assert_eq!(diagnostics[0].line, Some(8));
+        assert!(diagnostics[0].message.contains("`XK_Nope`"));
+    }
+
+    #[test]
+    fn unknown_modifier_is_reported_with_its_line() {
+        let (config, diagnostics) = validate(
+            r#"
+[[hotkeys]]
+modifiers = "MOD|HYPER"
+key = "q"
+
+[[hotkeys]]
+modifiers = "CONTROL|SHIFT"
+key = "w"
+"#,
+        );
+        assert_eq!(keys(&config.unwrap()), ["w"]);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "config.toml:3: hotkeys[0].modifiers: unknown modifier `HYPER`, dropping the entry"
+        );
+    }
+
+    #[test]
+    fn invalid_entries_keep_their_original_index() {
+        let (config, diagnostics) = validate(
+            r#"
+gap = "wide"
+
+[[hotkeys]]
+modifiers = "MOD"
+key = "XK_Nope"
+
+[[hotkeys]]
+modifiers = "MOD"
+key = "q"
+
+[[hotkeys]]
+modifiers = "MOD"
+key = 5
+"#,
+        );
+        let config = config.unwrap();
+        assert_eq!(config.gap, 0);
+        assert_eq!(keys(&config), ["q"]);
+        let reported: Vec<(&str, Option<usize>)> = diagnostics
+            .iter()
+            .map(|d| (d.path.as_str(), d.line))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                ("hotkeys[0].key", Some(6)),
+                ("gap", Some(2)),
+                ("hotkeys[2].key", Some(14)),
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_error_points_at_its_line() {
+        let default = Table::try_from(TestConfig::default()).unwrap();
+        let Err(diagnostic) = Validator::new("config.toml", "gap = 1\nhotkeys = [\n", default)
+        else {
+            panic!("invalid TOML was parsed");
+        };
+        assert_eq!(diagnostic.file, "config.toml");
+        assert_eq!(diagnostic.line, Some(3));
+    }
+}
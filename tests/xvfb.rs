@@ -0,0 +1,302 @@
+//! Integration tests running hematite on a headless X server.
+//!
+//! Each test starts its own `Xvfb` display and a hematite process managing it, then creates client windows with x11rb and checks the EWMH properties, tiling geometry and focus the manager gives them. The tests are skipped if `Xvfb` isn't in `PATH`.
+#![warn(clippy::pedantic)]
+#![warn(clippy::missing_docs_in_private_items)]
+#![allow(clippy::cast_possible_truncation, reason = "")]
+#![allow(clippy::cast_possible_wrap, reason = "")]
+use std::{
+    env, fs,
+    io::{BufRead as _, BufReader},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use x11rb::{
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT,
+    connection::Connection as _,
+    protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, Window, WindowClass},
+    rust_connection::RustConnection,
+};
+
+/// How long to wait for the X server, the manager, or a change made by the manager.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a condition is checked while waiting for it.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// The config hematite is started with. No font is loaded, so the tests don't depend on the fonts installed.
+const CONFIG: &str = "[font]\npath = \"\"\n\n[sizing]\nsmart_gaps = false\nsmart_borders = false\n";
+
+/// A headless X server with hematite managing it, both killed on drop.
+struct Display {
+    /// The `Xvfb` process.
+    server: Child,
+    /// The hematite process.
+    manager: Child,
+    /// The directory holding hematite's config and state.
+    dir: PathBuf,
+    /// A client connection to the X server.
+    conn: RustConnection,
+    /// The root window of the screen.
+    root: Window,
+}
+
+impl Display {
+    /// Starts an X server and hematite, waiting until hematite manages the screen.
+    ///
+    /// Returns `None` if `Xvfb` isn't installed.
+    fn start(test: &str) -> Option<Self> {
+        let installed = env::var_os("PATH")
+            .is_some_and(|paths| env::split_paths(&paths).any(|p| p.join("Xvfb").is_file()));
+        if !installed {
+            eprintln!("Xvfb isn't in PATH, skipping {test}");
+            return None;
+        }
+
+        // the server picks a free display and prints its number, so tests can run in parallel
+        let mut server = Command::new("Xvfb")
+            .args([
+                "-displayfd",
+                "1",
+                "-screen",
+                "0",
+                "1280x800x24",
+                "-nolisten",
+                "tcp",
+            ])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("couldn't start Xvfb");
+        let mut number = String::new();
+        BufReader::new(server.stdout.take().expect("no Xvfb output"))
+            .read_line(&mut number)
+            .expect("couldn't read the display number");
+        let display = format!(":{}", number.trim());
+        let (conn, screen) = x11rb::connect(Some(&display)).expect("couldn't connect to Xvfb");
+        let root = conn.setup().roots[screen].root;
+
+        let dir = env::temp_dir().join(format!("hematite-{test}-{}", std::process::id()));
+        fs::create_dir_all(dir.join("config/hematite"))
+            .expect("couldn't create the config directory");
+        fs::write(dir.join("config/hematite/config.toml"), CONFIG)
+            .expect("couldn't write the config");
+        let manager = Command::new(env!("CARGO_BIN_EXE_hematite"))
+            .env("DISPLAY", &display)
+            .env("XDG_CONFIG_HOME", dir.join("config"))
+            .env("XDG_STATE_HOME", dir.join("state"))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("couldn't start hematite");
+
+        let mut this = Self {
+            server,
+            manager,
+            dir,
+            conn,
+            root,
+        };
+        this.wait_for("hematite to manage the screen", |d| {
+            !d.property(d.root, "_NET_SUPPORTING_WM_CHECK").is_empty()
+        });
+        Some(this)
+    }
+
+    /// Waits until a condition holds, panicking after the timeout or if hematite exited.
+    fn wait_for(&mut self, what: &str, condition: impl Fn(&Self) -> bool) {
+        let start = Instant::now();
+        while !condition(self) {
+            if let Ok(Some(status)) = self.manager.try_wait() {
+                panic!("hematite exited with {status} while waiting for {what}");
+            }
+            assert!(start.elapsed() < TIMEOUT, "timed out waiting for {what}");
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Gets an atom by its name.
+    fn atom(&self, name: &str) -> Atom {
+        self.conn
+            .intern_atom(false, name.as_bytes())
+            .expect("couldn't intern atom")
+            .reply()
+            .expect("couldn't intern atom")
+            .atom
+    }
+
+    /// Gets a property made of 32-bit values, which is empty if the window doesn't have it.
+    fn property(&self, window: Window, name: &str) -> Vec<u32> {
+        self.conn
+            .get_property(false, window, self.atom(name), AtomEnum::ANY, 0, 1024)
+            .ok()
+            .and_then(|c| c.reply().ok())
+            .and_then(|r| r.value32().map(Iterator::collect))
+            .unwrap_or_default()
+    }
+
+    /// Creates and maps a client window.
+    fn open_window(&self) -> Window {
+        let window = self.conn.generate_id().expect("couldn't generate an id");
+        self.conn
+            .create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                self.root,
+                0,
+                0,
+                100,
+                100,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )
+            .expect("couldn't create a window");
+        self.conn.map_window(window).expect("couldn't map a window");
+        self.conn.flush().expect("couldn't flush");
+        window
+    }
+
+    /// Unmaps a client window, withdrawing it.
+    fn close_window(&self, window: Window) {
+        self.conn
+            .unmap_window(window)
+            .expect("couldn't unmap a window");
+        self.conn.flush().expect("couldn't flush");
+    }
+
+    /// Gets the outer geometry (x, y, width, height) of a window's frame, including its border. Is `None` until the window is reparented.
+    fn frame_geometry(&self, window: Window) -> Option<(i16, i16, u16, u16)> {
+        let parent = self.conn.query_tree(window).ok()?.reply().ok()?.parent;
+        if parent == self.root {
+            return None;
+        }
+        let geometry = self.conn.get_geometry(parent).ok()?.reply().ok()?;
+        let border = geometry.border_width * 2;
+        Some((
+            geometry.x,
+            geometry.y,
+            geometry.width + border,
+            geometry.height + border,
+        ))
+    }
+
+    /// Gets the published work area (x, y, width, height) of the first desktop.
+    fn workarea(&self) -> (i16, i16, u16, u16) {
+        let area = self.property(self.root, "_NET_WORKAREA");
+        assert!(area.len() >= 4, "no work area published");
+        (
+            area[0] as i16,
+            area[1] as i16,
+            area[2] as u16,
+            area[3] as u16,
+        )
+    }
+
+    /// Whether a window is in the managed client list.
+    fn is_listed(&self, window: Window) -> bool {
+        self.property(self.root, "_NET_CLIENT_LIST")
+            .contains(&window)
+    }
+
+    /// Whether a window is the active window.
+    fn is_active(&self, window: Window) -> bool {
+        self.property(self.root, "_NET_ACTIVE_WINDOW") == [window]
+    }
+}
+
+impl Drop for Display {
+    fn drop(&mut self) {
+        let _ = self.manager.kill();
+        let _ = self.manager.wait();
+        let _ = self.server.kill();
+        let _ = self.server.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Whether a rectangle (x, y, width, height) lies within another.
+fn contains(outer: (i16, i16, u16, u16), inner: (i16, i16, u16, u16)) -> bool {
+    let right = |r: (i16, i16, u16, u16)| i32::from(r.0) + i32::from(r.2);
+    let bottom = |r: (i16, i16, u16, u16)| i32::from(r.1) + i32::from(r.3);
+    inner.0 >= outer.0
+        && inner.1 >= outer.1
+        && right(inner) <= right(outer)
+        && bottom(inner) <= bottom(outer)
+}
+
+#[test]
+fn new_window_is_listed_and_focused() {
+    let Some(mut display) = Display::start("listed") else {
+        return;
+    };
+    let window = display.open_window();
+    display.wait_for("the window to be listed", |d| d.is_listed(window));
+    display.wait_for("the window to be focused", |d| d.is_active(window));
+}
+
+#[test]
+fn lone_window_takes_the_work_area() {
+    let Some(mut display) = Display::start("lone") else {
+        return;
+    };
+    let window = display.open_window();
+    display.wait_for("the window to be tiled", |d| {
+        d.frame_geometry(window)
+            .is_some_and(|f| (f.0, f.1) == (d.workarea().0, d.workarea().1))
+    });
+    let frame = display.frame_geometry(window).expect("window isn't framed");
+    let workarea = display.workarea();
+    assert!(
+        contains(workarea, frame),
+        "{frame:?} is outside {workarea:?}"
+    );
+    assert!(
+        frame.2 > workarea.2 / 2,
+        "{frame:?} doesn't take the width of {workarea:?}"
+    );
+}
+
+#[test]
+fn second_window_is_tiled_beside_the_first() {
+    let Some(mut display) = Display::start("split") else {
+        return;
+    };
+    let first = display.open_window();
+    display.wait_for("the first window to be listed", |d| d.is_listed(first));
+    let second = display.open_window();
+    display.wait_for("the windows to be side by side", |d| {
+        d.frame_geometry(first)
+            .zip(d.frame_geometry(second))
+            .is_some_and(|(a, b)| a.0 != b.0)
+    });
+
+    let workarea = display.workarea();
+    let mut frames =
+        [first, second].map(|w| display.frame_geometry(w).expect("window isn't framed"));
+    frames.sort_by_key(|f| f.0);
+    let [left, right] = frames;
+    assert_eq!((left.0, left.1), (workarea.0, workarea.1));
+    assert_eq!(right.1, workarea.1);
+    assert!(i32::from(left.0) + i32::from(left.2) <= i32::from(right.0));
+    assert!(contains(workarea, left) && contains(workarea, right));
+}
+
+#[test]
+fn focus_returns_to_remaining_window_when_one_closes() {
+    let Some(mut display) = Display::start("focus") else {
+        return;
+    };
+    let first = display.open_window();
+    display.wait_for("the first window to be focused", |d| d.is_active(first));
+    let second = display.open_window();
+    display.wait_for("the second window to be focused", |d| d.is_active(second));
+
+    display.close_window(second);
+    display.wait_for("the second window to be unlisted", |d| !d.is_listed(second));
+    display.wait_for("the first window to be focused again", |d| {
+        d.is_active(first)
+    });
+}